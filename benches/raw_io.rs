@@ -0,0 +1,155 @@
+/*
+Throughput benchmark for raw pixel data.
+
+Reads and writes synthetic 8-bit and 16-bit P6 images in memory, and compares
+the block based decoder in imageio against reading one pixel at a time, which is
+how raw pixel data used to be read. The baseline always stores 16-bit samples, while
+the bulk decoder stores 8-bit images with 8-bit samples. Run with `cargo bench --bench raw_io`.
+*/
+
+use std::hint::black_box;
+use std::io::{BufWriter, Cursor, Read, Write};
+use std::time::{Duration, Instant};
+
+use ppm_parser::imageio::{parse_header, read_image_data, write_image};
+use ppm_parser::ppm::{PPM, Pixel, IntegerSample};
+
+const WIDTH: u32 = 2048;
+const HEIGHT: u32 = 2048;
+const ITERATIONS: u32 = 20;
+
+/**
+Creates a P6 image with a repeating gradient, so that every sample value is used.
+*/
+fn fixture<S: IntegerSample>(maxc: u32) -> PPM<S> {
+    let mut image = PPM::new();
+    image.magic = String::from("P6");
+    image.width = WIDTH;
+    image.height = HEIGHT;
+    image.maxc = maxc;
+    image.pixels = (0..WIDTH * HEIGHT).map(|index| Pixel {
+        r: S::from_u16((index % (maxc + 1)) as u16),
+        g: S::from_u16(((index * 7) % (maxc + 1)) as u16),
+        b: S::from_u16(((index * 13) % (maxc + 1)) as u16),
+    }).collect();
+    image
+}
+
+/**
+Reads the raw pixel data one pixel at a time, as a baseline to compare against.
+*/
+fn read_per_pixel(reader: &mut impl Read, image: &mut PPM) {
+    let bytes_per_sample = if image.maxc < 256 { 1 } else { 2 };
+    let mut buffer = vec![0u8; 3 * bytes_per_sample];
+    let count = image.pixel_count().unwrap();
+    image.pixels = Vec::with_capacity(count);
+    for _ in 0..count {
+        reader.read_exact(&mut buffer).unwrap();
+        let sample = |i: usize| match bytes_per_sample {
+            1 => buffer[i] as u16,
+            _ => u16::from_be_bytes([buffer[i * 2], buffer[i * 2 + 1]]),
+        };
+        image.pixels.push(Pixel { r: sample(0), g: sample(1), b: sample(2) });
+    }
+}
+
+/**
+Writes the raw pixel data one pixel at a time through a buffered writer, as a baseline to compare against.
+*/
+fn write_per_pixel<S: IntegerSample>(writer: &mut impl Write, image: &PPM<S>) {
+    let mut writer = BufWriter::new(writer);
+    for pixel in &image.pixels {
+        for sample in [pixel.r.to_u16(), pixel.g.to_u16(), pixel.b.to_u16()] {
+            match image.maxc < 256 {
+                true => writer.write_all(&[sample as u8]).unwrap(),
+                false => writer.write_all(&sample.to_be_bytes()).unwrap(),
+            }
+        }
+    }
+    writer.flush().unwrap();
+}
+
+/**
+Copies the header information of an image, without any pixel data.
+*/
+fn header_copy<S: IntegerSample, T: IntegerSample>(image: &PPM<S>) -> PPM<T> {
+    let mut copy = PPM::new();
+    copy.magic = image.magic.clone();
+    copy.tupltype = image.tupltype.clone();
+    copy.width = image.width;
+    copy.height = image.height;
+    copy.maxc = image.maxc;
+    copy
+}
+
+/**
+Runs `operation` several times and returns the fastest time it took, which is the least affected by noise.
+*/
+fn time(mut operation: impl FnMut()) -> Duration {
+    (0..ITERATIONS).map(|_| {
+        let start = Instant::now();
+        operation();
+        start.elapsed()
+    }).min().unwrap()
+}
+
+/**
+Prints the throughput of one operation in megabytes of pixel data per second.
+*/
+fn report(name: &str, bytes: usize, duration: Duration) {
+    let throughput = bytes as f64 / duration.as_secs_f64() / 1_000_000.0;
+    println!("{:<28} {:>10.2?} {:>10.1} MB/s", name, duration, throughput);
+}
+
+/**
+Benchmarks reading and writing an image with the given max color value, stored with `S` samples.
+*/
+fn run<S: IntegerSample>(label: &str, maxc: u32) {
+    let image: PPM<S> = fixture(maxc);
+
+    // encode the fixture once, to have something to read
+    let mut encoded = Vec::new();
+    write_image(&mut encoded, &image).unwrap();
+    let header_length = parse_header(&mut Cursor::new(&encoded), &mut PPM::<S>::new()).unwrap() as usize;
+    let data_bytes = encoded.len() - header_length;
+
+    println!("{} P6, {}x{} ({} bytes of pixel data)", label, WIDTH, HEIGHT, data_bytes);
+
+    let per_pixel = time(|| {
+        let mut reader = Cursor::new(&encoded[header_length..]);
+        let mut decoded: PPM = header_copy(&image);
+        read_per_pixel(&mut reader, &mut decoded);
+        black_box(decoded);
+    });
+    report("read (per pixel)", data_bytes, per_pixel);
+
+    let bulk = time(|| {
+        let mut reader = Cursor::new(&encoded[header_length..]);
+        let mut decoded: PPM<S> = header_copy(&image);
+        read_image_data(&mut reader, &mut decoded).unwrap();
+        black_box(decoded);
+    });
+    report("read (bulk)", data_bytes, bulk);
+    println!("{:<28} {:>10.1}x", "read speedup", per_pixel.as_secs_f64() / bulk.as_secs_f64());
+
+    let per_pixel = time(|| {
+        let mut output = Vec::with_capacity(data_bytes);
+        write_per_pixel(&mut output, &image);
+        black_box(output);
+    });
+    report("write (per pixel)", data_bytes, per_pixel);
+
+    let bulk = time(|| {
+        let mut output = Vec::with_capacity(encoded.len());
+        write_image(&mut output, &image).unwrap();
+        black_box(output);
+    });
+    report("write (bulk)", data_bytes, bulk);
+    println!("{:<28} {:>10.1}x", "write speedup", per_pixel.as_secs_f64() / bulk.as_secs_f64());
+    println!();
+}
+
+fn main() {
+    run::<u8>("8-bit", 255);
+    run::<u16>("16-bit", 65535);
+}
@@ -0,0 +1,310 @@
+/*
+Contains the conversions between the rgb values of a pixel and other color models.
+
+Every conversion works on rgb values scaled from 0.0 to 1.0, which can be taken from
+a pixel of any image with Pixel::to_rgb and turned back into one with Pixel::from_rgb.
+The rgb values are sRGB, the gamma encoded values stored in the image, and are decoded
+to linear light for the models that need it (XYZ and Lab).
+*/
+
+/**
+Decodes an sRGB value from 0.0 to 1.0 to linear light.
+*/
+pub fn srgb_to_linear(value: f32) -> f32 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+/**
+Encodes a linear light value from 0.0 to 1.0 as sRGB.
+*/
+pub fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/**
+Decodes the sRGB values of a pixel to linear rgb.
+*/
+pub fn rgb_to_linear(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(srgb_to_linear)
+}
+
+/**
+Encodes linear rgb values as sRGB.
+*/
+pub fn linear_to_rgb(linear: [f32; 3]) -> [f32; 3] {
+    linear.map(linear_to_srgb)
+}
+
+/**
+Returns the hue in degrees, and the largest and smallest channel of the rgb values.
+The hue of a gray color is 0.
+*/
+fn hue_max_min(rgb: [f32; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb;
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+
+    // the hue is the angle around the color wheel, depending on which channel is largest
+    let hue = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    (hue, max, min)
+}
+
+/**
+Returns the rgb values with the given hue and chroma, shifted up so that the
+smallest channel is `min`. Shared by the HSV and HSL conversions.
+*/
+fn hue_to_rgb(hue: f32, chroma: f32, min: f32) -> [f32; 3] {
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let [r, g, b] = match sector as u32 {
+        0 => [chroma, x, 0.0],
+        1 => [x, chroma, 0.0],
+        2 => [0.0, chroma, x],
+        3 => [0.0, x, chroma],
+        4 => [x, 0.0, chroma],
+        _ => [chroma, 0.0, x],
+    };
+    [r + min, g + min, b + min]
+}
+
+/*
+The Hsv type is a color as hue, saturation, and value.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hsv {
+    pub h: f32, // hue in degrees, from 0.0 to 360.0
+    pub s: f32, // saturation from 0.0 to 1.0
+    pub v: f32, // value, the largest channel, from 0.0 to 1.0
+}
+
+impl Hsv {
+    /**
+    Converts rgb values to HSV.
+    */
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        let (h, max, min) = hue_max_min(rgb);
+        let s = match max == 0.0 {
+            true => 0.0,
+            false => (max - min) / max,
+        };
+        Hsv { h, s, v: max }
+    }
+
+    /**
+    Converts the color back to rgb values.
+    */
+    pub fn to_rgb(&self) -> [f32; 3] {
+        let chroma = self.v * self.s;
+        hue_to_rgb(self.h, chroma, self.v - chroma)
+    }
+}
+
+/*
+The Hsl type is a color as hue, saturation, and lightness.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hsl {
+    pub h: f32, // hue in degrees, from 0.0 to 360.0
+    pub s: f32, // saturation from 0.0 to 1.0
+    pub l: f32, // lightness, the average of the largest and smallest channel, from 0.0 to 1.0
+}
+
+impl Hsl {
+    /**
+    Converts rgb values to HSL. Colors past white, which HDR values can be, have a saturation of 0.
+    */
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        let (h, max, min) = hue_max_min(rgb);
+        let l = (max + min) / 2.0;
+
+        // colors at or past white, which HDR values can be, have no room left for saturation
+        let room = 1.0 - (2.0 * l - 1.0).abs();
+        let s = match max == min || room <= 0.0 {
+            true => 0.0,
+            false => (max - min) / room,
+        };
+        Hsl { h, s, l }
+    }
+
+    /**
+    Converts the color back to rgb values.
+    */
+    pub fn to_rgb(&self) -> [f32; 3] {
+        let chroma = (1.0 - (2.0 * self.l - 1.0).abs()) * self.s;
+        hue_to_rgb(self.h, chroma, self.l - chroma / 2.0)
+    }
+}
+
+/*
+The YCbCrStandard type picks the luma weights used for YCbCr.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum YCbCrStandard {
+    Rec601, // standard definition video and JPEG, 0.299 r + 0.587 g + 0.114 b
+    Rec709, // high definition video, 0.2126 r + 0.7152 g + 0.0722 b
+}
+
+impl YCbCrStandard {
+    /**
+    Returns the red and blue luma weights. The green weight is what's left over.
+    */
+    fn weights(&self) -> (f32, f32) {
+        match self {
+            YCbCrStandard::Rec601 => (0.299, 0.114),
+            YCbCrStandard::Rec709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/*
+The YCbCr type is a color as luma and two color differences, using the full range
+like JPEG does, instead of the reduced range of broadcast video.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct YCbCr {
+    pub y: f32, // luma from 0.0 to 1.0
+    pub cb: f32, // blue difference from -0.5 to 0.5
+    pub cr: f32, // red difference from -0.5 to 0.5
+}
+
+impl YCbCr {
+    /**
+    Converts rgb values to YCbCr with the given standard.
+    */
+    pub fn from_rgb(rgb: [f32; 3], standard: YCbCrStandard) -> Self {
+        let [r, g, b] = rgb;
+        let (kr, kb) = standard.weights();
+        let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+        YCbCr {
+            y,
+            cb: (b - y) / (2.0 * (1.0 - kb)),
+            cr: (r - y) / (2.0 * (1.0 - kr)),
+        }
+    }
+
+    /**
+    Converts the color back to rgb values, with the standard it was made with.
+    */
+    pub fn to_rgb(&self, standard: YCbCrStandard) -> [f32; 3] {
+        let (kr, kb) = standard.weights();
+        let r = self.y + 2.0 * (1.0 - kr) * self.cr;
+        let b = self.y + 2.0 * (1.0 - kb) * self.cb;
+        let g = (self.y - kr * r - kb * b) / (1.0 - kr - kb);
+        [r, g, b]
+    }
+}
+
+/*
+The Xyz type is a color in the CIE 1931 XYZ space, with the D65 white point of sRGB.
+Y is the luminance, from 0.0 to 1.0.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Xyz {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+// the XYZ values of the D65 white point
+const WHITE: Xyz = Xyz { x: 0.95047, y: 1.0, z: 1.08883 };
+
+impl Xyz {
+    /**
+    Converts rgb values to XYZ, decoding them to linear light first.
+    */
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        let [r, g, b] = rgb_to_linear(rgb);
+        Xyz {
+            x: 0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+            y: 0.2126729 * r + 0.7151522 * g + 0.072175 * b,
+            z: 0.0193339 * r + 0.119192 * g + 0.9503041 * b,
+        }
+    }
+
+    /**
+    Converts the color back to rgb values. Colors outside of sRGB give values
+    below 0.0 or above 1.0, which are clamped when stored in a pixel.
+    */
+    pub fn to_rgb(&self) -> [f32; 3] {
+        let Xyz { x, y, z } = *self;
+        linear_to_rgb([
+            3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+            -0.969266 * x + 1.8760108 * y + 0.041556 * z,
+            0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+        ])
+    }
+}
+
+/*
+The Lab type is a color in the CIELAB space, where equal distances look about equally different.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lab {
+    pub l: f32, // lightness from 0.0 to 100.0
+    pub a: f32, // green (negative) to red (positive)
+    pub b: f32, // blue (negative) to yellow (positive)
+}
+
+impl Lab {
+    /**
+    Converts rgb values to Lab, through XYZ.
+    */
+    pub fn from_rgb(rgb: [f32; 3]) -> Self {
+        Lab::from_xyz(Xyz::from_rgb(rgb))
+    }
+
+    /**
+    Converts the color back to rgb values, through XYZ.
+    */
+    pub fn to_rgb(&self) -> [f32; 3] {
+        self.to_xyz().to_rgb()
+    }
+
+    /**
+    Converts an XYZ color to Lab.
+    */
+    pub fn from_xyz(xyz: Xyz) -> Self {
+        // the cube root is replaced by a line near black, so it doesn't get infinitely steep
+        let f = |t: f32| match t > 216.0 / 24389.0 {
+            true => t.cbrt(),
+            false => t * 841.0 / 108.0 + 4.0 / 29.0,
+        };
+        let (fx, fy, fz) = (f(xyz.x / WHITE.x), f(xyz.y / WHITE.y), f(xyz.z / WHITE.z));
+        Lab {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    /**
+    Converts the color to XYZ.
+    */
+    pub fn to_xyz(&self) -> Xyz {
+        let f_inverse = |t: f32| match t > 6.0 / 29.0 {
+            true => t * t * t,
+            false => (t - 4.0 / 29.0) * 108.0 / 841.0,
+        };
+        let fy = (self.l + 16.0) / 116.0;
+        Xyz {
+            x: WHITE.x * f_inverse(fy + self.a / 500.0),
+            y: WHITE.y * f_inverse(fy),
+            z: WHITE.z * f_inverse(fy - self.b / 200.0),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+/*
+Contains the error type returned by the image reading and writing functions.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/*
+The PpmError type describes everything that can go wrong while reading
+or writing an image. Library functions return it instead of exiting the
+process, so that the caller can decide how to handle a bad file.
+*/
+#[derive(Debug)]
+pub enum PpmError {
+    TruncatedHeader, // the file ended before all header information was read
+    InvalidWidth(String), // the width block of the header could not be parsed
+    InvalidHeight(String), // the height block of the header could not be parsed
+    InvalidMaxColor(String), // the max color block of the header could not be parsed
+    InvalidScale(String), // the scale block of a PFM header could not be parsed, or is zero
+    UnsupportedMaxColor(u32), // the max color value parsed, but cannot be handled
+    ImageTooLarge(u32, u32), // the width and height parsed, but there are more pixels than fit in a u32
+    UnsupportedMagic(String), // the magic number is not a supported format
+    InvalidPamHeader(String), // a PAM header line is unknown, malformed, or missing
+    UnsupportedTupleType(String), // the PAM tuple type is not supported, or doesn't match the depth
+    ShortPixelData, // the file ended before all pixel data was read
+    InvalidPixelData(String), // a sample in a plain (ASCII) image could not be parsed or is above the max color value
+    RowMismatch(String), // the rows written to a RowWriter don't match its header
+    InvalidLut(String), // a color lookup table file or HALD image is malformed
+    Io(io::Error), // any other I/O error from the underlying reader or writer
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpmError::TruncatedHeader => write!(f, "End of file reached before all header information was read."),
+            PpmError::InvalidWidth(block) => write!(f, "Header block for width ('{}') failed to parse. Possibly invalid file type.", block),
+            PpmError::InvalidHeight(block) => write!(f, "Header block for height ('{}') failed to parse. Possibly invalid file type.", block),
+            PpmError::InvalidMaxColor(block) => write!(f, "Header block for max color ('{}') failed to parse. Possibly invalid file type.", block),
+            PpmError::InvalidScale(block) => write!(f, "Header block for scale ('{}') failed to parse. It must be a nonzero number.", block),
+            PpmError::UnsupportedMaxColor(maxc) => write!(f, "Cannot handle pixel data for image with max color value of {}.", maxc),
+            PpmError::ImageTooLarge(width, height) => write!(f, "Cannot handle an image of {}x{} pixels, which is more than {} pixels.", width, height, u32::MAX),
+            PpmError::UnsupportedMagic(magic) => write!(f, "This image is in {} format, which is not supported.", magic),
+            PpmError::InvalidPamHeader(line) => write!(f, "Invalid PAM header: {}.", line),
+            PpmError::UnsupportedTupleType(tupltype) => write!(f, "PAM tuple type {} is not supported. Supported tuple types are GRAYSCALE, RGB, GRAYSCALE_ALPHA and RGB_ALPHA.", tupltype),
+            PpmError::ShortPixelData => write!(f, "End of file reached before all pixel data was read. File may be corrupted."),
+            PpmError::InvalidPixelData(sample) => write!(f, "Pixel data sample '{}' is not a valid value for this image.", sample),
+            PpmError::RowMismatch(reason) => write!(f, "Rows don't match the image being written: {}.", reason),
+            PpmError::InvalidLut(reason) => write!(f, "Invalid color lookup table: {}.", reason),
+            PpmError::Io(error) => write!(f, "I/O error: {}", error),
+        }
+    }
+}
+
+impl Error for PpmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PpmError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PpmError {
+    fn from(error: io::Error) -> Self {
+        PpmError::Io(error)
+    }
+}
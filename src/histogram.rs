@@ -0,0 +1,287 @@
+/*
+Contains histograms, which count how many samples of an image are at each level, and the
+effects that change the tones of an image with them: equalization, histogram matching, and
+contrast limited adaptive histogram equalization (CLAHE).
+
+Equalization and CLAHE change the luma of each pixel, the Y of YCbCr with the Rec. 601 weights,
+and keep its color, so they work the same on grayscale images and don't shift the colors of
+color images. Histogram matching changes each channel on its own, so it also matches the colors.
+*/
+
+use rayon::prelude::*;
+
+use crate::color::{YCbCr, YCbCrStandard};
+use crate::ppm::{PPM, Pixel, Sample};
+
+// CLAHE counts the levels of each tile in up to this many bins, so the clip limit works the same
+// at any bit depth, and the curves are blended between the bins so 16-bit images stay smooth
+const CLAHE_BINS: usize = 256;
+
+/*
+The Histogram type holds the number of samples of each channel, and of the luma, in each bin.
+The bins are spread evenly from 0 to the max color value, so the first bin holds black and the
+last bin holds the max color value.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub red: Vec<u64>,
+    pub green: Vec<u64>,
+    pub blue: Vec<u64>,
+    pub luma: Vec<u64>, // the Rec. 601 luma, which image editors show as the luminance histogram
+}
+
+/**
+Returns the number of bins needed for one bin for each level of an image with the given max
+color value, which is 256 for floating point images since they have no levels.
+*/
+pub fn level_bins<S: Sample>(maxc: u32) -> usize {
+    match S::INTEGER {
+        true => maxc as usize + 1,
+        false => 256,
+    }
+}
+
+/**
+Returns the bin of a value from 0.0 to 1.0, out of `bins` bins.
+*/
+fn bin(value: f32, bins: usize) -> usize {
+    (value.clamp(0.0, 1.0) * (bins - 1) as f32).round() as usize
+}
+
+/**
+Returns the Rec. 601 luma of a pixel, from 0.0 to 1.0.
+*/
+fn luma<S: Sample>(pixel: &Pixel<S>, maxc: u32) -> f32 {
+    (0.299 * pixel.r.to_f32() + 0.587 * pixel.g.to_f32() + 0.114 * pixel.b.to_f32()) / maxc as f32
+}
+
+impl Histogram {
+    /**
+    Counts the samples of the image, with one bin for each level, or 256 bins for floating point images.
+    */
+    pub fn new<S: Sample>(image: &PPM<S>) -> Self {
+        Histogram::with_bins(image, level_bins::<S>(image.maxc))
+    }
+
+    /**
+    Counts the samples of the image in the given number of bins, which must be at least 1.
+    */
+    pub fn with_bins<S: Sample>(image: &PPM<S>, bins: usize) -> Self {
+        let maxc = image.maxc;
+        let scale = 1.0 / maxc as f32;
+        let empty = || Histogram { red: vec![0; bins], green: vec![0; bins], blue: vec![0; bins], luma: vec![0; bins] };
+
+        // count each chunk of pixels in parallel, then add the counts together
+        image.pixels.par_chunks(65536).fold(empty, |mut histogram, pixels| {
+            for pixel in pixels {
+                histogram.red[bin(pixel.r.to_f32() * scale, bins)] += 1;
+                histogram.green[bin(pixel.g.to_f32() * scale, bins)] += 1;
+                histogram.blue[bin(pixel.b.to_f32() * scale, bins)] += 1;
+                histogram.luma[bin(luma(pixel, maxc), bins)] += 1;
+            }
+            histogram
+        }).reduce(empty, |mut total, histogram| {
+            for (total, counts) in [&mut total.red, &mut total.green, &mut total.blue, &mut total.luma].into_iter().zip([histogram.red, histogram.green, histogram.blue, histogram.luma]) {
+                total.iter_mut().zip(counts).for_each(|(total, count)| *total += count);
+            }
+            total
+        })
+    }
+
+    /**
+    Returns the number of bins.
+    */
+    pub fn bins(&self) -> usize {
+        self.red.len()
+    }
+
+    /**
+    Draws the histogram as a P6 chart `height` pixels tall, with a column for each bin. The red,
+    green, and blue bars are added together, so they are white where all three overlap, as they
+    do for a grayscale image, and the luma is drawn as a gray bar behind them. The bars are
+    scaled so the largest bin of any of them reaches the top.
+    */
+    pub fn chart(&self, height: u32) -> PPM<u8> {
+        let mut chart = PPM::new();
+        chart.magic = String::from("P6");
+        chart.tupltype = String::from("RGB");
+        chart.maxc = 255;
+        chart.width = self.bins() as u32;
+        chart.height = height;
+        chart.pixels = vec![Pixel::new(); chart.width as usize * chart.height as usize];
+
+        let largest = [&self.red, &self.green, &self.blue, &self.luma].iter().flat_map(|counts| counts.iter()).copied().max().unwrap_or(0).max(1);
+        let bar = |count: u64| (count as f64 / largest as f64 * height as f64).round() as u32;
+
+        // the bars grow up from the bottom of the chart
+        let width = chart.width;
+        chart.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+            let x = index % width as usize;
+            let level = height - (index / width as usize) as u32; // from 1 at the bottom to height at the top
+            let lit = |counts: &Vec<u64>| bar(counts[x]) >= level;
+            *pixel = match (lit(&self.red), lit(&self.green), lit(&self.blue)) {
+                (false, false, false) if lit(&self.luma) => Pixel { r: 96, g: 96, b: 96 },
+                (r, g, b) => Pixel { r: r as u8 * 255, g: g as u8 * 255, b: b as u8 * 255 },
+            };
+        });
+
+        chart
+    }
+}
+
+/**
+Returns the fraction of the samples at or below each bin of a histogram.
+*/
+fn cumulative(counts: &[u64]) -> Vec<f32> {
+    let total = counts.iter().sum::<u64>().max(1) as f64;
+    counts.iter().scan(0, |sum, &count| {
+        *sum += count;
+        Some((*sum as f64 / total) as f32)
+    }).collect()
+}
+
+/**
+Returns the equalization curve of a histogram of luma levels, which is the fraction of the pixels
+at or below each level, stretched so the darkest level used becomes 0.0 and the brightest 1.0.
+*/
+fn equalize_curve(counts: &[f64]) -> Vec<f32> {
+    let total = counts.iter().sum::<f64>();
+    let darkest = counts.iter().copied().find(|&count| count > 0.0).unwrap_or(0.0);
+    counts.iter().scan(0.0, |sum, &count| {
+        *sum += count;
+        Some(match total > darkest {
+            true => ((*sum - darkest) / (total - darkest)).max(0.0) as f32,
+            false => (*sum / total.max(1.0)) as f32,
+        })
+    }).collect()
+}
+
+/**
+Returns the value of a curve with a point for each bin at a luma from 0.0 to 1.0, blended between
+the two nearest bins so luma between the levels, from color and 16-bit pixels, stays smooth.
+*/
+fn curve_at(curve: &[f32], luma: f32) -> f32 {
+    let position = luma.clamp(0.0, 1.0) * (curve.len() - 1) as f32;
+    let (level, fraction) = (position.floor() as usize, position.fract());
+    let next = (level + 1).min(curve.len() - 1);
+    curve[level] * (1.0 - fraction) + curve[next] * fraction
+}
+
+/**
+Changes the luma of every pixel with `change`, which is given the index of the pixel and its
+luma from 0.0 to 1.0. The color of each pixel is kept.
+*/
+fn change_luma<S: Sample>(image: &mut PPM<S>, change: impl Fn(usize, f32) -> f32 + Sync) {
+    let maxc = image.maxc;
+    image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let mut color = YCbCr::from_rgb(pixel.to_rgb(maxc), YCbCrStandard::Rec601);
+        color.y = change(index, color.y);
+        *pixel = Pixel::from_rgb(color.to_rgb(YCbCrStandard::Rec601), maxc);
+    });
+}
+
+/**
+Spreads the tones of the image evenly over the whole range, so that each luma level is used by
+about as many pixels, which brings out detail in flat and washed out images. The darkest level in
+the image becomes black and the brightest becomes white. The alpha channel is kept.
+*/
+pub fn equalize<S: Sample>(mut image: PPM<S>) -> PPM<S> {
+    let histogram = Histogram::new(&image);
+
+    // the fraction of the pixels below each level, with the darkest level at 0.0
+    let curve = equalize_curve(&histogram.luma.iter().map(|&count| count as f64).collect::<Vec<f64>>());
+
+    change_luma(&mut image, |_, luma| curve_at(&curve, luma));
+    image
+}
+
+/**
+Changes the tones of each channel of the image so that its histogram matches the histogram of the
+reference image, which can have any sample type and max color value. Each level is moved to the
+level of the reference with the same fraction of the samples below it, so the image takes on the
+tones and colors of the reference. The alpha channel is kept.
+*/
+pub fn match_histogram<S: Sample, T: Sample>(mut image: PPM<S>, reference: &PPM<T>) -> PPM<S> {
+    let source = Histogram::new(&image);
+    let target = Histogram::with_bins(reference, source.bins());
+
+    // the level of the reference for each bin of each channel, from 0.0 to 1.0
+    let last = (source.bins() - 1).max(1) as f32;
+    let curves = [(&source.red, &target.red), (&source.green, &target.green), (&source.blue, &target.blue)].map(|(source, target)| {
+        let target = cumulative(target);
+        cumulative(source).iter().map(|&fraction| target.partition_point(|&target| target < fraction).min(target.len() - 1) as f32 / last).collect::<Vec<f32>>()
+    });
+
+    let maxc = image.maxc;
+    let bins = source.bins();
+    let scale = 1.0 / maxc as f32;
+    let level = |curve: &Vec<f32>, value: S| S::from_f32_clamped(curve[bin(value.to_f32() * scale, bins)] * maxc as f32, maxc);
+    image.pixels.par_iter_mut().for_each(|pixel| {
+        *pixel = Pixel {
+            r: level(&curves[0], pixel.r),
+            g: level(&curves[1], pixel.g),
+            b: level(&curves[2], pixel.b),
+        };
+    });
+
+    image
+}
+
+/**
+Equalizes the tones of each square tile of the image separately, with contrast limited adaptive
+histogram equalization (CLAHE), which brings out detail in both the shadows and the highlights.
+The curve of each pixel is blended from the four tiles nearest to it, so the tiles don't show.
+`tile_size` is the size of the tiles in pixels, and `clip_limit` is how many times more pixels
+than average each level of a tile can have before the extra pixels are spread over every level,
+which stops flat areas and noise from getting too much contrast. A clip limit of 0 doesn't limit
+the contrast. The color of each pixel and the alpha channel are kept.
+*/
+pub fn clahe<S: Sample>(mut image: PPM<S>, tile_size: u32, clip_limit: f32) -> PPM<S> {
+    if image.pixels.is_empty() || tile_size == 0 {
+        return image;
+    }
+
+    let (width, height, maxc) = (image.width, image.height, image.maxc);
+    let bins = level_bins::<S>(maxc).min(CLAHE_BINS);
+    let (tiles_x, tiles_y) = (width.div_ceil(tile_size), height.div_ceil(tile_size));
+
+    // find the curve of each tile
+    let curves = (0..tiles_x * tiles_y).into_par_iter().map(|tile| {
+        let (left, top) = ((tile % tiles_x) * tile_size, (tile / tiles_x) * tile_size);
+        let (right, bottom) = ((left + tile_size).min(width), (top + tile_size).min(height));
+        let mut counts = vec![0.0f64; bins];
+        for y in top..bottom {
+            let row = y as usize * width as usize;
+            for pixel in &image.pixels[row + left as usize..row + right as usize] {
+                counts[bin(luma(pixel, maxc), bins)] += 1.0;
+            }
+        }
+
+        // clip each level, and spread what was clipped over every level
+        if clip_limit > 0.0 {
+            let total = (right - left) as f64 * (bottom - top) as f64;
+            let limit = (clip_limit as f64 * total / bins as f64).max(1.0);
+            let clipped = counts.iter().map(|&count| (count - limit).max(0.0)).sum::<f64>();
+            counts.iter_mut().for_each(|count| *count = count.min(limit) + clipped / bins as f64);
+        }
+
+        equalize_curve(&counts)
+    }).collect::<Vec<Vec<f32>>>();
+
+    // blend the curves of the four tiles around each pixel, from the centers of the tiles
+    let tile_position = |position: u32, tiles: u32| {
+        let position = ((position as f32 + 0.5) / tile_size as f32 - 0.5).clamp(0.0, (tiles - 1) as f32);
+        let first = position.floor() as u32;
+        (first, (first + 1).min(tiles - 1), position - first as f32)
+    };
+    change_luma(&mut image, |index, luma| {
+        let (x0, x1, fx) = tile_position((index % width as usize) as u32, tiles_x);
+        let (y0, y1, fy) = tile_position((index / width as usize) as u32, tiles_y);
+        let curve = |x: u32, y: u32| curve_at(&curves[(y * tiles_x + x) as usize], luma);
+        let top = curve(x0, y0) * (1.0 - fx) + curve(x1, y0) * fx;
+        let bottom = curve(x0, y1) * (1.0 - fx) + curve(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    });
+
+    image
+}
@@ -1,18 +1,46 @@
-use crate::ppm::{PPM, Pixel};
+use crate::ppm::{PPM, Pixel, Sample};
+#[cfg(feature = "mmap")]
+use crate::ppm::IntegerSample;
 use rayon::prelude::*;
 
-pub fn rotate_left(image: PPM) -> PPM {
+#[cfg(feature = "mmap")]
+use crate::imageio::MappedImage;
+
+/**
+Applies a transformation to the alpha channel of an image, if it has one.
+The alpha channel is turned into a grayscale image, so that it is moved and
+resampled in exactly the same way as the color channels.
+*/
+fn transform_alpha<S: Sample>(image: &PPM<S>, transform: impl FnOnce(PPM<S>) -> PPM<S>) -> Option<Vec<S>> {
+    let alpha = image.alpha.as_ref()?;
+
+    // create a grayscale image from the alpha channel
+    let mut alpha_image = PPM::new();
+    alpha_image.magic = String::from("P5");
+    alpha_image.tupltype = String::from("GRAYSCALE");
+    alpha_image.maxc = image.maxc;
+    alpha_image.width = image.width;
+    alpha_image.height = image.height;
+    alpha_image.pixels = alpha.iter().map(|&value| Pixel { r: value, g: value, b: value }).collect();
+
+    // transform it, then take the alpha values back out
+    let transformed = transform(alpha_image);
+    Some(transformed.pixels.iter().map(|pixel| pixel.r).collect())
+}
+
+pub fn rotate_left<S: Sample>(image: PPM<S>) -> PPM<S> {
     // create a new PPM object to represent the rotated image
     let mut rotated_image = PPM::new();
 
     // copy header information
     rotated_image.magic = image.magic.clone();
+    rotated_image.tupltype = image.tupltype.clone();
     rotated_image.maxc = image.maxc;
     rotated_image.width = image.height;
     rotated_image.height = image.width;
 
     // assign the pixel array for the rotated image
-    rotated_image.pixels = vec![Pixel::new(); image.pixel_count() as usize];
+    rotated_image.pixels = vec![Pixel::new(); image.pixels.len()];
 
     // loop through each pixel in the new image
     rotated_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -25,24 +53,28 @@ pub fn rotate_left(image: PPM) -> PPM {
 
         // get original pixel
         let old_pixel = image.get_pixel(old_x, old_y).unwrap();
-        *pixel = old_pixel.clone();
+        *pixel = *old_pixel;
     });
 
-    return rotated_image;
+    // carry the alpha channel through the same transformation
+    rotated_image.alpha = transform_alpha(&image, rotate_left);
+
+    rotated_image
 }
 
-pub fn rotate_right(image: PPM) -> PPM {
+pub fn rotate_right<S: Sample>(image: PPM<S>) -> PPM<S> {
     // create a new PPM object to represent the rotated image
     let mut rotated_image = PPM::new();
 
     // copy header information
     rotated_image.magic = image.magic.clone();
+    rotated_image.tupltype = image.tupltype.clone();
     rotated_image.maxc = image.maxc;
     rotated_image.width = image.height;
     rotated_image.height = image.width;
 
     // assign the pixel array for the rotated image
-    rotated_image.pixels = vec![Pixel::new(); image.pixel_count() as usize];
+    rotated_image.pixels = vec![Pixel::new(); image.pixels.len()];
 
     // loop through each pixel in the new image
     rotated_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -55,7 +87,7 @@ pub fn rotate_right(image: PPM) -> PPM {
 
         // get original pixel
         let old_pixel = image.get_pixel(old_x, old_y).unwrap();
-        *pixel = old_pixel.clone();
+        *pixel = *old_pixel;
     });
     
     // for x in 0..image.width {
@@ -73,39 +105,49 @@ pub fn rotate_right(image: PPM) -> PPM {
     //     }
     // }
 
-    return rotated_image;
+    // carry the alpha channel through the same transformation
+    rotated_image.alpha = transform_alpha(&image, rotate_right);
+
+    rotated_image
 }
 
 /**
 Halfs the size of the image by averaging the pixels.
+Odd sized images keep their last row and column, which are averaged in pairs instead of squares.
  */
-pub fn half_size(image: PPM) -> PPM {
+pub fn half_size<S: Sample>(image: PPM<S>) -> PPM<S> {
     let mut half_image = PPM::new();
 
     // copy header info
     half_image.magic = image.magic.clone();
+    half_image.tupltype = image.tupltype.clone();
     half_image.maxc = image.maxc;
-    half_image.width = image.width / 2;
-    half_image.height = image.height / 2;
+    half_image.width = image.width.div_ceil(2);
+    half_image.height = image.height.div_ceil(2);
 
     // assign the pixel array for the new image
-    half_image.pixels = vec![Pixel::new(); half_image.pixel_count() as usize];
+    half_image.pixels = vec![Pixel::new(); half_image.width as usize * half_image.height as usize];
 
     // loop through each pixel in the new image
     half_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
         let x = (index as u32) % half_image.width;
         let y = (index as u32) / half_image.width;
 
-        // get the 4 pixels that will be averaged
-        let pixel1 = image.get_pixel(x * 2, y * 2).unwrap();
-        let pixel2 = image.get_pixel(x * 2 + 1, y * 2 + 1).unwrap();
-        let pixel3 = image.get_pixel(x * 2 + 1, y * 2).unwrap();
-        let pixel4 = image.get_pixel(x * 2, y * 2 + 1).unwrap();
+        // add up the 4 pixels that will be averaged, the last row and column of an odd sized image have fewer
+        let (mut sum_r, mut sum_g, mut sum_b, mut count) = (0.0, 0.0, 0.0, 0.0);
+        for (dx, dy) in [(0, 0), (1, 1), (1, 0), (0, 1)] {
+            if let Some(old_pixel) = image.get_pixel(x * 2 + dx, y * 2 + dy) {
+                sum_r += old_pixel.r.to_f32();
+                sum_g += old_pixel.g.to_f32();
+                sum_b += old_pixel.b.to_f32();
+                count += 1.0;
+            }
+        }
 
-        // calculate the average of the 4 pixels
-        let avg_r = ((pixel1.r as u32 + pixel2.r as u32 + pixel3.r as u32 + pixel4.r as u32) / 4) as u16;
-        let avg_g = ((pixel1.g as u32 + pixel2.g as u32 + pixel3.g as u32 + pixel4.g as u32) / 4) as u16;
-        let avg_b = ((pixel1.b as u32 + pixel2.b as u32 + pixel3.b as u32 + pixel4.b as u32) / 4) as u16;
+        // calculate the average of the pixels
+        let avg_r = S::from_f32(sum_r / count);
+        let avg_g = S::from_f32(sum_g / count);
+        let avg_b = S::from_f32(sum_b / count);
 
         // create a new pixel
         let avg_pixel = Pixel {
@@ -115,9 +157,12 @@ pub fn half_size(image: PPM) -> PPM {
         };
 
         // assign new pixel to new image
-        *pixel = avg_pixel.clone();
+        *pixel = avg_pixel;
     });
 
+    // carry the alpha channel through the same transformation
+    half_image.alpha = transform_alpha(&image, half_size);
+
     half_image
 }
 
@@ -125,17 +170,18 @@ pub fn half_size(image: PPM) -> PPM {
 /**
 Doubles the size of an image by copying each pixel into a 2x2 square
 */
-pub fn double_size(image: PPM) -> PPM {
+pub fn double_size<S: Sample>(image: PPM<S>) -> PPM<S> {
     let mut double_image = PPM::new();
 
     // copy header info
     double_image.magic = image.magic.clone();
+    double_image.tupltype = image.tupltype.clone();
     double_image.maxc = image.maxc;
     double_image.width = image.width * 2;
     double_image.height = image.height * 2;
 
     // assign the pixel array for the new image
-    double_image.pixels = vec![Pixel::new(); double_image.pixel_count() as usize];
+    double_image.pixels = vec![Pixel::new(); double_image.width as usize * double_image.height as usize];
 
     // loop through each pixel in the new image
     double_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -149,17 +195,20 @@ pub fn double_size(image: PPM) -> PPM {
 
         // get the representative pixel
         let old_pixel = image.get_pixel(old_x, old_y).unwrap();
-        *pixel = old_pixel.clone();
+        *pixel = *old_pixel;
     });
 
-    return double_image;
+    // carry the alpha channel through the same transformation
+    double_image.alpha = transform_alpha(&image, double_size);
+
+    double_image
 }
 
 /**
 Calculates the value of an "in-between" pixel of an image using bilinear interpolation.
 Used in the double_bilinear function.
 */
-fn bilinear_interpolation(image: &PPM, x: f32, y: f32) -> Pixel {
+fn bilinear_interpolation<S: Sample>(image: &PPM<S>, x: f32, y: f32) -> Pixel<S> {
     // get the x and y values of the pixel on the original image
 
     // get the x and y values of the 4 pixels that will be used for interpolation
@@ -187,45 +236,110 @@ fn bilinear_interpolation(image: &PPM, x: f32, y: f32) -> Pixel {
     };
 
     // red channel
-    let r1_r = q11.r as f32 * (x2 - x) / (x2 - x1) + q21.r as f32 * (x - x1) / (x2 - x1);
-    let r2_r: f32 = q12.r as f32 * (x2 - x) / (x2 - x1) + q22.r as f32 * (x - x1) / (x2 - x1);
+    let r1_r = q11.r.to_f32() * (x2 - x) / (x2 - x1) + q21.r.to_f32() * (x - x1) / (x2 - x1);
+    let r2_r: f32 = q12.r.to_f32() * (x2 - x) / (x2 - x1) + q22.r.to_f32() * (x - x1) / (x2 - x1);
     let p_r = r1_r * (y2 - y) / (y2 - y1) + r2_r * (y - y1) / (y2 - y1);
     
     // green channel
-    let r1_g = q11.g as f32 * (x2 - x) / (x2 - x1) + q21.g as f32 * (x - x1) / (x2 - x1);
-    let r2_g: f32 = q12.g as f32 * (x2 - x) / (x2 - x1) + q22.g as f32 * (x - x1) / (x2 - x1);
+    let r1_g = q11.g.to_f32() * (x2 - x) / (x2 - x1) + q21.g.to_f32() * (x - x1) / (x2 - x1);
+    let r2_g: f32 = q12.g.to_f32() * (x2 - x) / (x2 - x1) + q22.g.to_f32() * (x - x1) / (x2 - x1);
     let p_g = r1_g * (y2 - y) / (y2 - y1) + r2_g * (y - y1) / (y2 - y1);
 
     // blue channel
-    let r1_b = q11.b as f32 * (x2 - x) / (x2 - x1) + q21.b as f32 * (x - x1) / (x2 - x1);
-    let r2_b: f32 = q12.b as f32 * (x2 - x) / (x2 - x1) + q22.b as f32 * (x - x1) / (x2 - x1);
+    let r1_b = q11.b.to_f32() * (x2 - x) / (x2 - x1) + q21.b.to_f32() * (x - x1) / (x2 - x1);
+    let r2_b: f32 = q12.b.to_f32() * (x2 - x) / (x2 - x1) + q22.b.to_f32() * (x - x1) / (x2 - x1);
     let p_b = r1_b * (y2 - y) / (y2 - y1) + r2_b * (y - y1) / (y2 - y1);
     
     // create new interpolated pixel
-    let interpolated_pixel = Pixel {
-        r: p_r as u16,
-        g: p_g as u16,
-        b: p_b as u16
-    };
+    Pixel {
+        r: S::from_f32(p_r),
+        g: S::from_f32(p_g),
+        b: S::from_f32(p_b)
+    }
 
-    interpolated_pixel
+}
 
+/**
+Calculates the value of an "in-between" pixel of an image using bicubic interpolation,
+which blends the 4x4 pixels around it. Pixels past the edge are replaced by the pixel on the edge.
+*/
+fn bicubic_interpolation<S: Sample>(image: &PPM<S>, x: f32, y: f32) -> Pixel<S> {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+
+    // add up the 4x4 pixels around the point, weighed by the same cubic used to resize images
+    for j in -1..=2 {
+        let sample_y = y0 + j as f32;
+        let weight_y = Filter::Bicubic.weight(y - sample_y);
+        let old_y = (sample_y.max(0.0) as u32).min(image.height - 1);
+        for i in -1..=2 {
+            let sample_x = x0 + i as f32;
+            let weight = weight_y * Filter::Bicubic.weight(x - sample_x);
+            let old_x = (sample_x.max(0.0) as u32).min(image.width - 1);
+
+            let old_pixel = image.get_pixel(old_x, old_y).unwrap(); // guaranteed to be in the image
+            r += old_pixel.r.to_f32() * weight;
+            g += old_pixel.g.to_f32() * weight;
+            b += old_pixel.b.to_f32() * weight;
+        }
+    }
+
+    // the cubic can overshoot, so the result is clamped to the max color value
+    Pixel {
+        r: S::from_f32_clamped(r, image.maxc),
+        g: S::from_f32_clamped(g, image.maxc),
+        b: S::from_f32_clamped(b, image.maxc)
+    }
+}
+
+/*
+The Interpolation type selects how the color of a point between pixels is found,
+when an image is rotated or warped.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+/**
+Returns the color of the image at a point, where each pixel is centered on whole numbers.
+Points outside of the image are the background color.
+*/
+pub(crate) fn sample<S: Sample>(image: &PPM<S>, x: f32, y: f32, interpolation: Interpolation, background: Pixel<S>) -> Pixel<S> {
+    // each pixel covers half a pixel around its center
+    let inside = x >= -0.5 && x < image.width as f32 - 0.5 && y >= -0.5 && y < image.height as f32 - 0.5;
+    if !inside {
+        return background;
+    }
+
+    // the half pixel around the edge is treated like the pixel on the edge
+    let x = x.clamp(0.0, (image.width - 1) as f32);
+    let y = y.clamp(0.0, (image.height - 1) as f32);
+    match interpolation {
+        Interpolation::Nearest => *image.get_pixel(x.round() as u32, y.round() as u32).unwrap(), // guaranteed to be in the image
+        Interpolation::Bilinear => bilinear_interpolation(image, x, y),
+        Interpolation::Bicubic => bicubic_interpolation(image, x, y),
+    }
 }
 
 /**
 Doubles the size of an image by using bilinear interpolation.
 */
-pub fn double_bilinear(image: PPM) -> PPM {
+pub fn double_bilinear<S: Sample>(image: PPM<S>) -> PPM<S> {
     let mut double_image = PPM::new();
 
     // copy header info
     double_image.magic = image.magic.clone();
+    double_image.tupltype = image.tupltype.clone();
     double_image.maxc = image.maxc;
     double_image.width = image.width * 2;
     double_image.height = image.height * 2;
 
     // assign the pixel array for the new image
-    double_image.pixels = vec![Pixel::new(); double_image.pixel_count() as usize];
+    double_image.pixels = vec![Pixel::new(); double_image.width as usize * double_image.height as usize];
 
     // loop through each pixel in the new image
     double_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -236,23 +350,379 @@ pub fn double_bilinear(image: PPM) -> PPM {
         *pixel = new_pixel;
     });
 
-    return double_image;
+    // carry the alpha channel through the same transformation
+    double_image.alpha = transform_alpha(&image, double_bilinear);
+
+    double_image
+}
+
+/*
+The Filter type selects how pixels are resampled when an image is resized.
+Nearest is the fastest and keeps hard edges, bilinear and bicubic are smooth,
+Lanczos3 is the sharpest, and box averages the area each new pixel covers,
+which is the best choice for shrinking.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos3,
+    Box,
+}
+
+impl Filter {
+    /**
+    Returns the distance from the center of a pixel at which the filter's weight becomes zero.
+    */
+    fn support(&self) -> f32 {
+        match self {
+            Filter::Nearest | Filter::Box => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    /**
+    Returns the weight of a pixel at distance `x` from the center of the new pixel.
+    */
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest | Filter::Box => if x < 0.5 { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            // Catmull-Rom spline, the cubic with a = -0.5
+            Filter::Bicubic => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x == 0.0 {
+                    1.0
+                } else if x < 3.0 {
+                    let pi_x = std::f32::consts::PI * x;
+                    3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/*
+The Contribution type holds the weights of the old pixels that are added up to
+make one new pixel along a single axis. The weights apply to consecutive pixels,
+starting at `start`, and add up to 1.
+*/
+struct Contribution {
+    start: usize, // the index of the first old pixel that is used
+    weights: Vec<f32>, // the weight of each old pixel, starting at start
+}
+
+/**
+Calculates the contributions that resample a row or column of `old_size` pixels to `new_size` pixels.
+Pixels past the edge of the image are replaced by the pixel on the edge.
+*/
+fn contributions(old_size: u32, new_size: u32, filter: Filter) -> Vec<Contribution> {
+    let scale = old_size as f32 / new_size as f32;
+
+    // when shrinking, the filter is stretched so that every old pixel is used
+    let filter_scale = scale.max(1.0);
+    let radius = filter.support() * filter_scale;
+
+    (0..new_size).map(|i| {
+        // an image with no pixels has nothing to resample
+        if old_size == 0 {
+            return Contribution { start: 0, weights: Vec::new() };
+        }
+
+        // the area of the old image covered by the new pixel, and its center
+        let left = i as f32 * scale;
+        let right = (i + 1) as f32 * scale;
+        let center = (left + right) / 2.0;
+        let last = old_size as i64 - 1;
+
+        // find the weight of each old pixel near the new one
+        let weighted: Vec<(usize, f32)> = match filter {
+            // nearest neighbor always uses exactly one pixel
+            Filter::Nearest => vec![((center.floor() as i64).clamp(0, last) as usize, 1.0)],
+
+            // box weighs each pixel by how much of it is covered by the new pixel
+            Filter::Box => (left.floor() as i64..right.ceil() as i64).map(|j| {
+                let overlap = right.min(j as f32 + 1.0) - left.max(j as f32);
+                (j.clamp(0, last) as usize, overlap.max(0.0))
+            }).collect(),
+
+            // the other filters weigh each pixel by its distance from the center
+            _ => ((center - radius).floor() as i64..=(center + radius).ceil() as i64).map(|j| {
+                let distance = (j as f32 + 0.5 - center) / filter_scale;
+                (j.clamp(0, last) as usize, filter.weight(distance))
+            }).collect(),
+        };
+
+        // collect the weights into consecutive pixels, pixels past the edge add to the edge pixel
+        let start = weighted.iter().map(|(j, _)| *j).min().unwrap_or(0);
+        let end = weighted.iter().map(|(j, _)| *j).max().unwrap_or(0);
+        let mut weights = vec![0.0; end - start + 1];
+        for (j, weight) in weighted {
+            weights[j - start] += weight;
+        }
+
+        // make the weights add up to 1, so the brightness of the image doesn't change
+        let total: f32 = weights.iter().sum();
+        if total != 0.0 {
+            weights.iter_mut().for_each(|weight| *weight /= total);
+        }
+
+        Contribution { start, weights }
+    }).collect()
+}
+
+/**
+Resizes an image to any width and height, resampling it with the given filter.
+The filter is applied to the rows first and then to the columns, which gives the
+same result as applying it in both directions at once but is much faster.
+*/
+pub fn resize<S: Sample>(image: PPM<S>, new_width: u32, new_height: u32, filter: Filter) -> PPM<S> {
+    let mut resized_image = PPM::new();
+
+    // copy header info
+    resized_image.magic = image.magic.clone();
+    resized_image.tupltype = image.tupltype.clone();
+    resized_image.maxc = image.maxc;
+    resized_image.width = new_width;
+    resized_image.height = new_height;
+
+    // calculate the weights once for every column and every row
+    let columns = contributions(image.width, new_width, filter);
+    let rows = contributions(image.height, new_height, filter);
+
+    // resample each row to the new width, keeping the results as floating point until the end
+    // the stretched rows can have more pixels than either image, so they're counted as usize
+    let mut stretched = vec![Pixel::<f32>::new(); new_width as usize * image.height as usize];
+    stretched.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = index % new_width as usize;
+        let y = index / new_width as usize;
+
+        let contribution = &columns[x];
+        for (i, weight) in contribution.weights.iter().enumerate() {
+            let old_pixel = image.get_pixel((contribution.start + i) as u32, y as u32).unwrap(); // guaranteed to be in the image
+            pixel.r += old_pixel.r.to_f32() * weight;
+            pixel.g += old_pixel.g.to_f32() * weight;
+            pixel.b += old_pixel.b.to_f32() * weight;
+        }
+    });
+
+    // assign the pixel array for the new image
+    resized_image.pixels = vec![Pixel::new(); resized_image.width as usize * resized_image.height as usize];
+
+    // resample each column of the stretched rows to the new height
+    let maxc = image.maxc;
+    resized_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = index % new_width as usize;
+        let y = index / new_width as usize;
+
+        let contribution = &rows[y];
+        let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+        for (i, weight) in contribution.weights.iter().enumerate() {
+            let old_pixel = &stretched[(contribution.start + i) * new_width as usize + x];
+            r += old_pixel.r * weight;
+            g += old_pixel.g * weight;
+            b += old_pixel.b * weight;
+        }
+
+        // filters with negative weights can overshoot, so the result is clamped to the max color value
+        *pixel = Pixel {
+            r: S::from_f32_clamped(r, maxc),
+            g: S::from_f32_clamped(g, maxc),
+            b: S::from_f32_clamped(b, maxc)
+        };
+    });
+
+    // carry the alpha channel through the same transformation
+    resized_image.alpha = transform_alpha(&image, |alpha| resize(alpha, new_width, new_height, filter));
+
+    resized_image
+}
+
+/*
+The Edge type selects which pixels are used past the edge of an image, such as
+when it is padded. Constant uses a single color, replicate repeats the pixel on
+the edge, reflect mirrors the image without repeating the edge pixel, and wrap
+tiles the image.
+*/
+#[derive(Copy, Clone, Debug)]
+pub enum Edge<S: Sample = u16> {
+    Constant(Pixel<S>),
+    Replicate,
+    Reflect,
+    Wrap,
+}
+
+impl<S: Sample> Edge<S> {
+    /**
+    Returns the index of the pixel used at position `i` of a row or column of `size` pixels,
+    or None if the constant color is used instead. `size` must not be 0.
+    */
+    pub(crate) fn index(&self, i: i64, size: u32) -> Option<u32> {
+        let size = size as i64;
+        if (0..size).contains(&i) {
+            return Some(i as u32);
+        }
+
+        match self {
+            Edge::Constant(_) => None,
+            Edge::Replicate => Some(i.clamp(0, size - 1) as u32),
+            Edge::Wrap => Some(i.rem_euclid(size) as u32),
+            Edge::Reflect => {
+                // the pattern repeats every 2 * (size - 1) pixels, going forward and then back
+                let period = (2 * (size - 1)).max(1);
+                let i = i.rem_euclid(period);
+                Some(if i < size { i } else { period - i } as u32)
+            }
+        }
+    }
+}
+
+/*
+The Anchor type selects where an image is placed when its canvas is extended.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+/**
+Moves the image to a new canvas of the given size, with the top left corner of the
+image at `left` and `top` on the canvas. Either can be negative, which cuts off that
+side of the image. Pixels of the canvas that aren't covered by the image are filled
+in using the edge mode, and the alpha channel is filled with opaque pixels when the
+edge is a constant color.
+*/
+fn move_to_canvas<S: Sample>(image: PPM<S>, width: u32, height: u32, left: i64, top: i64, edge: Edge<S>) -> PPM<S> {
+    let mut canvas_image = PPM::new();
+
+    // copy header info
+    canvas_image.magic = image.magic.clone();
+    canvas_image.tupltype = image.tupltype.clone();
+    canvas_image.maxc = image.maxc;
+    canvas_image.width = width;
+    canvas_image.height = height;
+
+    // assign the pixel array for the new image
+    canvas_image.pixels = vec![Pixel::new(); canvas_image.width as usize * canvas_image.height as usize];
+
+    // an image with no pixels has nothing to repeat, so everything is the constant color
+    let edge = match image.pixels.is_empty() {
+        true => Edge::Constant(match edge {
+            Edge::Constant(fill) => fill,
+            _ => Pixel::new(),
+        }),
+        false => edge,
+    };
+
+    // loop through each pixel in the new image
+    canvas_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = (index % width as usize) as u32;
+        let y = (index / width as usize) as u32;
+
+        // find the pixel of the old image at this position, or past its edge
+        let old_x = edge.index(x as i64 - left, image.width);
+        let old_y = edge.index(y as i64 - top, image.height);
+        *pixel = match (old_x, old_y, edge) {
+            (Some(old_x), Some(old_y), _) => *image.get_pixel(old_x, old_y).unwrap(), // guaranteed to be in the image
+            (_, _, Edge::Constant(fill)) => fill,
+            _ => unreachable!("only constant edges have positions without a pixel"),
+        };
+    });
+
+    // carry the alpha channel through the same transformation, a constant edge is opaque
+    let alpha_edge = alpha_edge(edge, image.maxc);
+    canvas_image.alpha = transform_alpha(&image, |alpha| move_to_canvas(alpha, width, height, left, top, alpha_edge));
+
+    canvas_image
+}
+
+/**
+Crops the image to the `width` by `height` region with its top left corner at `x` and `y`.
+Any part of the region outside of the image is left out, so the result may be smaller.
+*/
+pub fn crop<S: Sample>(image: PPM<S>, x: u32, y: u32, width: u32, height: u32) -> PPM<S> {
+    // keep the region inside the image
+    let x = x.min(image.width);
+    let y = y.min(image.height);
+    let width = width.min(image.width - x);
+    let height = height.min(image.height - y);
+
+    move_to_canvas(image, width, height, -(x as i64), -(y as i64), Edge::Replicate)
+}
+
+/**
+Adds the given number of pixels to each side of the image, filled in using the edge mode.
+Returns None if the padded image would have more pixels than fit in a u32.
+*/
+pub fn pad<S: Sample>(image: PPM<S>, top: u32, right: u32, bottom: u32, left: u32, edge: Edge<S>) -> Option<PPM<S>> {
+    let width = image.width.checked_add(left)?.checked_add(right)?;
+    let height = image.height.checked_add(top)?.checked_add(bottom)?;
+    width.checked_mul(height)?;
+    Some(move_to_canvas(image, width, height, left as i64, top as i64, edge))
+}
+
+/**
+Changes the size of the canvas without resizing the image, which is placed at the anchor.
+New parts of the canvas are filled in using the edge mode, and if the canvas is smaller
+than the image, the image is cropped on the sides away from the anchor.
+*/
+pub fn extend_canvas<S: Sample>(image: PPM<S>, width: u32, height: u32, anchor: Anchor, edge: Edge<S>) -> PPM<S> {
+    // the extra space on each axis, which is negative if the canvas is smaller
+    let extra_x = width as i64 - image.width as i64;
+    let extra_y = height as i64 - image.height as i64;
+
+    // place the image at the start, middle, or end of each axis
+    let left = match anchor {
+        Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0,
+        Anchor::Top | Anchor::Center | Anchor::Bottom => extra_x / 2,
+        Anchor::TopRight | Anchor::Right | Anchor::BottomRight => extra_x,
+    };
+    let top = match anchor {
+        Anchor::TopLeft | Anchor::Top | Anchor::TopRight => 0,
+        Anchor::Left | Anchor::Center | Anchor::Right => extra_y / 2,
+        Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => extra_y,
+    };
+
+    move_to_canvas(image, width, height, left, top, edge)
 }
 
 /**
 Flips an image in the horizontal direction.
 */
-pub fn flip_horizontal(image: PPM) -> PPM {
+pub fn flip_horizontal<S: Sample>(image: PPM<S>) -> PPM<S> {
     let mut flipped_image = PPM::new();
 
     // copy header info
     flipped_image.magic = image.magic.clone();
+    flipped_image.tupltype = image.tupltype.clone();
     flipped_image.maxc = image.maxc;
     flipped_image.width = image.width;
     flipped_image.height = image.height;
 
     // assign the pixel array for the new image
-    flipped_image.pixels = vec![Pixel::new(); flipped_image.pixel_count() as usize];
+    flipped_image.pixels = vec![Pixel::new(); flipped_image.width as usize * flipped_image.height as usize];
 
     // loop through each pixel in the new image
     flipped_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -260,26 +730,30 @@ pub fn flip_horizontal(image: PPM) -> PPM {
         let y = (index as u32) / flipped_image.width;
 
         let original_pixel = image.get_pixel(flipped_image.width - x -1, y).unwrap(); // guaranteed to be in the image
-        *pixel = original_pixel.clone();
+        *pixel = *original_pixel;
     });
 
+    // carry the alpha channel through the same transformation
+    flipped_image.alpha = transform_alpha(&image, flip_horizontal);
+
     flipped_image
 }
 
 /**
 Flips an image in the vertical direction.
 */
-pub fn flip_vertical(image: PPM) -> PPM {
+pub fn flip_vertical<S: Sample>(image: PPM<S>) -> PPM<S> {
     let mut flipped_image = PPM::new();
 
     // copy header info
     flipped_image.magic = image.magic.clone();
+    flipped_image.tupltype = image.tupltype.clone();
     flipped_image.maxc = image.maxc;
     flipped_image.width = image.width;
     flipped_image.height = image.height;
 
     // assign the pixel array for the new image
-    flipped_image.pixels = vec![Pixel::new(); flipped_image.pixel_count() as usize];
+    flipped_image.pixels = vec![Pixel::new(); flipped_image.width as usize * flipped_image.height as usize];
 
     // loop through each pixel in the new image
     flipped_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
@@ -287,8 +761,1210 @@ pub fn flip_vertical(image: PPM) -> PPM {
         let y = (index as u32) / flipped_image.width;
 
         let original_pixel = image.get_pixel(x, flipped_image.height - y - 1).unwrap(); // guaranteed to be in the image
-        *pixel = original_pixel.clone();
+        *pixel = *original_pixel;
     });
 
+    // carry the alpha channel through the same transformation
+    flipped_image.alpha = transform_alpha(&image, flip_vertical);
+
     flipped_image
-}
\ No newline at end of file
+}
+
+/**
+Creates an image of the given size where each pixel is copied from the coordinate
+of the old image given by `mapping`, which is used for exact rotations and flips.
+*/
+fn remap<S: Sample>(image: &PPM<S>, width: u32, height: u32, mapping: impl Fn(u32, u32) -> (u32, u32) + Sync) -> PPM<S> {
+    let mut remapped_image = PPM::new();
+
+    // copy header info
+    remapped_image.magic = image.magic.clone();
+    remapped_image.tupltype = image.tupltype.clone();
+    remapped_image.maxc = image.maxc;
+    remapped_image.width = width;
+    remapped_image.height = height;
+
+    // assign the pixel array for the new image
+    remapped_image.pixels = vec![Pixel::new(); remapped_image.width as usize * remapped_image.height as usize];
+
+    // loop through each pixel in the new image
+    remapped_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = (index as u32) % width;
+        let y = (index as u32) / width;
+
+        let (old_x, old_y) = mapping(x, y);
+        *pixel = *image.get_pixel(old_x, old_y).unwrap(); // guaranteed to be in the image
+    });
+
+    remapped_image
+}
+
+/**
+Rotates the image 180 degrees.
+*/
+pub fn rotate_180<S: Sample>(image: PPM<S>) -> PPM<S> {
+    let (width, height) = (image.width, image.height);
+    let mut rotated_image = remap(&image, width, height, |x, y| (width - x - 1, height - y - 1));
+
+    // carry the alpha channel through the same transformation
+    rotated_image.alpha = transform_alpha(&image, rotate_180);
+
+    rotated_image
+}
+
+/**
+Flips the image over the diagonal from the top left to the bottom right corner, swapping the rows and columns.
+*/
+pub fn transpose<S: Sample>(image: PPM<S>) -> PPM<S> {
+    let mut transposed_image = remap(&image, image.height, image.width, |x, y| (y, x));
+
+    // carry the alpha channel through the same transformation
+    transposed_image.alpha = transform_alpha(&image, transpose);
+
+    transposed_image
+}
+
+/**
+Flips the image over the diagonal from the top right to the bottom left corner.
+*/
+pub fn transverse<S: Sample>(image: PPM<S>) -> PPM<S> {
+    let (width, height) = (image.width, image.height);
+    let mut transversed_image = remap(&image, height, width, |x, y| (width - y - 1, height - x - 1));
+
+    // carry the alpha channel through the same transformation
+    transversed_image.alpha = transform_alpha(&image, transverse);
+
+    transversed_image
+}
+
+/**
+Rotates the image clockwise by any angle in degrees, using the interpolation to find the
+color of each rotated pixel. With `expand`, the canvas grows to fit the whole rotated image,
+otherwise it keeps its size and the corners are cut off. New parts of the canvas are the
+background color, which is opaque in the alpha channel. Multiples of 90 degrees use the
+exact rotations whenever the canvas allows it, so no quality is lost.
+*/
+pub fn rotate<S: Sample>(image: PPM<S>, degrees: f32, interpolation: Interpolation, background: Pixel<S>, expand: bool) -> PPM<S> {
+    // use the exact rotations for multiples of 90 degrees, unless the canvas would be cropped
+    let degrees = degrees.rem_euclid(360.0);
+    let fits = expand || image.width == image.height;
+    match degrees {
+        0.0 => return image,
+        180.0 => return rotate_180(image),
+        90.0 if fits => return rotate_right(image),
+        270.0 if fits => return rotate_left(image),
+        _ => {}
+    }
+
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (old_width, old_height) = (image.width as f32, image.height as f32);
+
+    // the size of the rotated image's bounding box, ignoring tiny rounding errors
+    let (width, height) = match expand {
+        true => (
+            (old_width * cos.abs() + old_height * sin.abs() - 1e-3).ceil().max(0.0) as u32,
+            (old_width * sin.abs() + old_height * cos.abs() - 1e-3).ceil().max(0.0) as u32,
+        ),
+        false => (image.width, image.height),
+    };
+
+    let mut rotated_image = PPM::new();
+
+    // copy header info
+    rotated_image.magic = image.magic.clone();
+    rotated_image.tupltype = image.tupltype.clone();
+    rotated_image.maxc = image.maxc;
+    rotated_image.width = width;
+    rotated_image.height = height;
+
+    // assign the pixel array for the rotated image
+    rotated_image.pixels = vec![Pixel::new(); rotated_image.width as usize * rotated_image.height as usize];
+
+    // loop through each pixel in the rotated image
+    // an expanded canvas can have more pixels than a u32 can count, so the indexes are usize
+    rotated_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = index % width as usize;
+        let y = index / width as usize;
+
+        // the position of the pixel's center relative to the center of the rotated image
+        let dx = x as f32 + 0.5 - width as f32 / 2.0;
+        let dy = y as f32 + 0.5 - height as f32 / 2.0;
+
+        // rotate it back counter-clockwise to find where it came from in the old image
+        let old_x = dx * cos + dy * sin + old_width / 2.0 - 0.5;
+        let old_y = -dx * sin + dy * cos + old_height / 2.0 - 0.5;
+
+        *pixel = sample(&image, old_x, old_y, interpolation, background);
+    });
+
+    // carry the alpha channel through the same transformation, the background is opaque
+    let opaque = S::from_f32(image.maxc as f32);
+    let alpha_background = Pixel { r: opaque, g: opaque, b: opaque };
+    rotated_image.alpha = transform_alpha(&image, |alpha| rotate(alpha, degrees, interpolation, alpha_background, expand));
+
+    rotated_image
+}
+
+/*
+A 3x3 matrix for projective transforms, stored as rows.
+*/
+pub type Matrix = [[f64; 3]; 3];
+
+/**
+Returns the inverse of a 3x3 matrix, or None if it can't be inverted.
+*/
+fn invert(matrix: &Matrix) -> Option<Matrix> {
+    let m = matrix;
+
+    // the cofactors of the first row give the determinant
+    let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    // the inverse is the adjugate divided by the determinant
+    let adjugate = [
+        [m[1][1] * m[2][2] - m[1][2] * m[2][1], m[0][2] * m[2][1] - m[0][1] * m[2][2], m[0][1] * m[1][2] - m[0][2] * m[1][1]],
+        [m[1][2] * m[2][0] - m[1][0] * m[2][2], m[0][0] * m[2][2] - m[0][2] * m[2][0], m[0][2] * m[1][0] - m[0][0] * m[1][2]],
+        [m[1][0] * m[2][1] - m[1][1] * m[2][0], m[0][1] * m[2][0] - m[0][0] * m[2][1], m[0][0] * m[1][1] - m[0][1] * m[1][0]],
+    ];
+    Some(adjugate.map(|row| row.map(|value| value / determinant)))
+}
+
+/**
+Calculates the projective transform (homography) that moves each of the four `from`
+points to the matching `to` point, as a 3x3 matrix that can be given to warp_perspective.
+Returns None if three of the points are on a line, so no transform exists.
+*/
+pub fn homography(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<Matrix> {
+    // three points on a line, in either quad, would squash the image onto a line, which the
+    // equations below can still be solved for, so they're checked first
+    let on_a_line = |points: &[(f64, f64); 4]| (0..4).any(|skip| {
+        let others = points.iter().enumerate().filter(|&(i, _)| i != skip).map(|(_, &point)| point).collect::<Vec<(f64, f64)>>();
+        let ((ax, ay), (bx, by), (cx, cy)) = (others[0], others[1], others[2]);
+        let cross = (bx - ax) * (cy - ay) - (by - ay) * (cx - ax);
+        cross.abs() <= 1e-9 * (bx - ax).hypot(by - ay) * (cx - ax).hypot(cy - ay)
+    });
+    if on_a_line(&from) || on_a_line(&to) {
+        return None;
+    }
+
+    // each pair of points gives two equations for the 8 unknown entries of the matrix,
+    // with the last entry fixed at 1
+    let mut system = [[0.0; 9]; 8];
+    for (i, (&(x, y), &(u, v))) in from.iter().zip(to.iter()).enumerate() {
+        system[i * 2] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, u];
+        system[i * 2 + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v, v];
+    }
+
+    // solve the equations with gaussian elimination, picking the largest pivot for accuracy
+    for column in 0..8 {
+        let pivot = (column..8).max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs())).unwrap();
+        if system[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        system.swap(column, pivot);
+
+        let pivot_row = system[column];
+        for (row, equation) in system.iter_mut().enumerate() {
+            if row != column {
+                let factor = equation[column] / pivot_row[column];
+                for (value, pivot_value) in equation.iter_mut().zip(pivot_row.iter()).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    let h = |i: usize| system[i][8] / system[i][i];
+
+    Some([
+        [h(0), h(1), h(2)],
+        [h(3), h(4), h(5)],
+        [h(6), h(7), 1.0],
+    ])
+}
+
+/**
+Warps the image with a projective transform, given as a 3x3 matrix that moves a point
+(x, y) of the old image to (x', y') = ((a x + b y + c) / w, (d x + e y + f) / w) in the new
+image, where w = g x + h y + i. Pixels are centered on whole numbers. The new image is
+`width` by `height` pixels, and the interpolation finds the color of each new pixel.
+Parts of the new image that don't come from the old one are the background color, which
+is opaque in the alpha channel. If the matrix can't be inverted, every pixel is the background.
+*/
+pub fn warp_perspective<S: Sample>(image: PPM<S>, matrix: Matrix, width: u32, height: u32, interpolation: Interpolation, background: Pixel<S>) -> PPM<S> {
+    let mut warped_image = PPM::new();
+
+    // copy header info
+    warped_image.magic = image.magic.clone();
+    warped_image.tupltype = image.tupltype.clone();
+    warped_image.maxc = image.maxc;
+    warped_image.width = width;
+    warped_image.height = height;
+
+    // assign the pixel array for the warped image
+    warped_image.pixels = vec![Pixel::new(); warped_image.width as usize * warped_image.height as usize];
+
+    // each new pixel is found by moving it back to the old image, with the inverse transform
+    let inverse = invert(&matrix);
+
+    // loop through each pixel in the warped image
+    warped_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = ((index as u32) % width) as f64;
+        let y = ((index as u32) / width) as f64;
+
+        *pixel = match inverse {
+            Some(m) => {
+                let w = m[2][0] * x + m[2][1] * y + m[2][2];
+                match w.abs() < 1e-12 {
+                    // the point is on the horizon, so it doesn't come from anywhere in the old image
+                    true => background,
+                    false => {
+                        let old_x = (m[0][0] * x + m[0][1] * y + m[0][2]) / w;
+                        let old_y = (m[1][0] * x + m[1][1] * y + m[1][2]) / w;
+                        sample(&image, old_x as f32, old_y as f32, interpolation, background)
+                    }
+                }
+            }
+            None => background,
+        };
+    });
+
+    // carry the alpha channel through the same transformation, the background is opaque
+    let opaque = S::from_f32(image.maxc as f32);
+    let alpha_background = Pixel { r: opaque, g: opaque, b: opaque };
+    warped_image.alpha = transform_alpha(&image, |alpha| warp_perspective(alpha, matrix, width, height, interpolation, alpha_background));
+
+    warped_image
+}
+
+/**
+Warps the image with an affine transform, such as a shear, scale, or translation, given as a
+2x3 matrix that moves a point (x, y) of the old image to (a x + b y + c, d x + e y + f).
+Works the same as warp_perspective otherwise.
+*/
+pub fn warp_affine<S: Sample>(image: PPM<S>, matrix: [[f64; 3]; 2], width: u32, height: u32, interpolation: Interpolation, background: Pixel<S>) -> PPM<S> {
+    warp_perspective(image, [matrix[0], matrix[1], [0.0, 0.0, 1.0]], width, height, interpolation, background)
+}
+
+/*
+The Kernel type is a grid of weights for convolve. Each new pixel is the sum of the pixels
+around it, multiplied by the weights at the same places in the kernel, which is centered on
+the pixel and isn't flipped. The offset is added to the sum as a fraction of the max color
+value, so that kernels which give negative results, like emboss, can be centered on gray.
+*/
+#[derive(Clone, Debug)]
+pub struct Kernel {
+    pub width: u32, // the number of columns, with the center column at width / 2
+    pub height: u32, // the number of rows, with the center row at height / 2
+    pub weights: Vec<f32>, // the weights, one row after another
+    pub offset: f32, // added to each result, from 0.0 to 1.0
+}
+
+impl Kernel {
+    // constructor
+    // returns None if either size is 0, or there aren't width * height weights
+    pub fn new(width: u32, height: u32, weights: Vec<f32>) -> Option<Self> {
+        match width > 0 && height > 0 && width.checked_mul(height).is_some_and(|count| weights.len() == count as usize) {
+            true => Some(Kernel { width, height, weights, offset: 0.0 }),
+            false => None,
+        }
+    }
+
+    /**
+    Returns the kernel with its weights divided by their sum, so that it keeps the brightness
+    of the image. Kernels with weights that add up to 0, such as edge detectors, are unchanged.
+    */
+    pub fn normalized(mut self) -> Self {
+        let sum = self.weights.iter().sum::<f32>();
+        if sum.abs() > f32::EPSILON {
+            self.weights.iter_mut().for_each(|weight| *weight /= sum);
+        }
+        self
+    }
+
+    /**
+    Returns a 3x3 kernel that sharpens the image by subtracting the pixels around each pixel.
+    */
+    pub fn sharpen() -> Self {
+        Kernel::new(3, 3, vec![0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]).unwrap()
+    }
+
+    /**
+    Returns a 3x3 kernel that makes the image look raised, lit from the top left, on a gray background.
+    */
+    pub fn emboss() -> Self {
+        let mut kernel = Kernel::new(3, 3, vec![-1.0, -1.0, 0.0, -1.0, 0.0, 1.0, 0.0, 1.0, 1.0]).unwrap();
+        kernel.offset = 0.5;
+        kernel
+    }
+
+    /**
+    Returns a 3x3 Laplacian kernel, which finds edges in every direction. Flat areas become gray,
+    and the sides of each edge become darker and lighter.
+    */
+    pub fn laplacian() -> Self {
+        let mut kernel = Kernel::new(3, 3, vec![0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0, 1.0, 0.0]).unwrap();
+        kernel.offset = 0.5;
+        kernel
+    }
+}
+
+// the largest radius of the square windows used by the filters, so that the pixels across a
+// window and the pixels in the whole window can both be counted with a u32
+pub const MAX_RADIUS: u32 = 32767;
+
+/**
+Returns the weights of a box blur that averages the `radius` pixels on each side of a pixel.
+Radii past MAX_RADIUS are treated as MAX_RADIUS. Used for both directions of convolve_separable.
+*/
+pub fn box_weights(radius: u32) -> Vec<f32> {
+    let size = 2 * radius.min(MAX_RADIUS) + 1;
+    vec![1.0 / size as f32; size as usize]
+}
+
+/**
+Returns the weights of a gaussian blur with the standard deviation `sigma`, in pixels,
+reaching 3 sigma to each side, but no more than MAX_RADIUS, and adding up to 1. Used for both
+directions of convolve_separable.
+*/
+pub fn gaussian_weights(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil().clamp(0.0, MAX_RADIUS as f32) as i32;
+    if radius == 0 {
+        // a blur smaller than a pixel doesn't change anything
+        return vec![1.0];
+    }
+    let weights = (-radius..=radius).map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp()).collect::<Vec<f32>>();
+    let sum = weights.iter().sum::<f32>();
+    weights.iter().map(|weight| weight / sum).collect()
+}
+
+/**
+Converts a pixel to floating point samples for calculations.
+*/
+fn float_pixel<S: Sample>(pixel: Pixel<S>) -> Pixel<f32> {
+    Pixel { r: pixel.r.to_f32(), g: pixel.g.to_f32(), b: pixel.b.to_f32() }
+}
+
+/**
+Returns the pixel at `x` and `y`, or the pixel the edge mode picks if the position is
+past the edge of the image.
+*/
+fn pixel_at<S: Sample>(image: &PPM<S>, x: i64, y: i64, edge: Edge<S>) -> Pixel<S> {
+    match (edge.index(x, image.width), edge.index(y, image.height), edge) {
+        (Some(x), Some(y), _) => *image.get_pixel(x, y).unwrap(), // guaranteed to be in the image
+        (_, _, Edge::Constant(fill)) => fill,
+        _ => unreachable!("only constant edges have positions without a pixel"),
+    }
+}
+
+/**
+Returns the pixel at `x` and `y` as floating point, like pixel_at.
+*/
+fn edge_pixel<S: Sample>(image: &PPM<S>, x: i64, y: i64, edge: Edge<S>) -> Pixel<f32> {
+    float_pixel(pixel_at(image, x, y, edge))
+}
+
+/**
+Returns the edge mode to use for the alpha channel, where a constant edge is opaque.
+*/
+fn alpha_edge<S: Sample>(edge: Edge<S>, maxc: u32) -> Edge<S> {
+    let opaque = S::from_f32(maxc as f32);
+    match edge {
+        Edge::Constant(_) => Edge::Constant(Pixel { r: opaque, g: opaque, b: opaque }),
+        _ => edge,
+    }
+}
+
+/**
+Convolves the image with the kernel. The edge mode picks the pixels used past the edges of
+the image, where constant black is a zero edge and replicate clamps to the edge pixel.
+Results are rounded and clamped to the max color value. The alpha channel is convolved too,
+with a constant edge being opaque.
+*/
+pub fn convolve<S: Sample>(image: PPM<S>, kernel: &Kernel, edge: Edge<S>) -> PPM<S> {
+    // an image with no pixels has nothing to convolve
+    if image.pixels.is_empty() {
+        return image;
+    }
+
+    let mut convolved_image = PPM::new();
+
+    // copy header info
+    convolved_image.magic = image.magic.clone();
+    convolved_image.tupltype = image.tupltype.clone();
+    convolved_image.maxc = image.maxc;
+    convolved_image.width = image.width;
+    convolved_image.height = image.height;
+
+    // assign the pixel array for the convolved image
+    convolved_image.pixels = vec![Pixel::new(); convolved_image.width as usize * convolved_image.height as usize];
+
+    let (center_x, center_y) = ((kernel.width / 2) as i64, (kernel.height / 2) as i64);
+    let maxc = image.maxc;
+    let offset = kernel.offset * maxc as f32;
+
+    // loop through each pixel, adding up the weighted pixels around it
+    convolved_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = ((index as u32) % image.width) as i64;
+        let y = ((index as u32) / image.width) as i64;
+
+        let (mut r, mut g, mut b) = (offset, offset, offset);
+        for (i, &weight) in kernel.weights.iter().enumerate() {
+            if weight == 0.0 {
+                continue;
+            }
+            let kernel_x = (i as u32 % kernel.width) as i64;
+            let kernel_y = (i as u32 / kernel.width) as i64;
+            let old_pixel = edge_pixel(&image, x + kernel_x - center_x, y + kernel_y - center_y, edge);
+            r += old_pixel.r * weight;
+            g += old_pixel.g * weight;
+            b += old_pixel.b * weight;
+        }
+
+        // kernels with negative weights can overshoot, so the result is clamped to the max color value
+        *pixel = Pixel {
+            r: S::from_f32_clamped(r, maxc),
+            g: S::from_f32_clamped(g, maxc),
+            b: S::from_f32_clamped(b, maxc)
+        };
+    });
+
+    // carry the alpha channel through the same transformation
+    let alpha_edge = alpha_edge(edge, image.maxc);
+    convolved_image.alpha = transform_alpha(&image, |alpha| convolve(alpha, kernel, alpha_edge));
+
+    convolved_image
+}
+
+/**
+Runs a separable convolution, first along each row with the `horizontal` weights and then
+along each column with the `vertical` weights. Each sum is passed to `finish` with the
+index of its pixel, without rounding, and the results are returned in order.
+The image must not be empty.
+*/
+fn separable_convolution<S: Sample, T: Send>(image: &PPM<S>, horizontal: &[f32], vertical: &[f32], edge: Edge<S>, finish: impl Fn(usize, Pixel<f32>) -> T + Sync) -> Vec<T> {
+    let width = image.width;
+    let (center_x, center_y) = ((horizontal.len() / 2) as i64, (vertical.len() / 2) as i64);
+
+    // convolve each row
+    let mut rows = vec![Pixel::<f32>::new(); image.pixels.len()];
+    rows.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = ((index as u32) % width) as i64;
+        let y = ((index as u32) / width) as i64;
+        let first = x - center_x;
+
+        let mut add = |old_pixel: Pixel<f32>, weight: f32| {
+            pixel.r += old_pixel.r * weight;
+            pixel.g += old_pixel.g * weight;
+            pixel.b += old_pixel.b * weight;
+        };
+        match first >= 0 && first + horizontal.len() as i64 <= width as i64 {
+            // the whole kernel is inside the row, so the pixels can be read directly
+            true => {
+                let row_start = index - center_x as usize;
+                for (old_pixel, &weight) in image.pixels[row_start..row_start + horizontal.len()].iter().zip(horizontal) {
+                    add(float_pixel(*old_pixel), weight);
+                }
+            }
+            false => {
+                for (i, &weight) in horizontal.iter().enumerate() {
+                    add(edge_pixel(image, first + i as i64, y, edge), weight);
+                }
+            }
+        }
+    });
+
+    // a row past a constant edge is all the constant color, which the row weights scale by their sum
+    let outside = match edge {
+        Edge::Constant(fill) => {
+            let sum = horizontal.iter().sum::<f32>();
+            let fill = float_pixel(fill);
+            Pixel { r: fill.r * sum, g: fill.g * sum, b: fill.b * sum }
+        }
+        _ => Pixel::new(),
+    };
+
+    // convolve each column of the convolved rows
+    (0..image.pixels.len()).into_par_iter().map(|index| {
+        let x = (index as u32) % width;
+        let y = ((index as u32) / width) as i64;
+
+        let mut sum = Pixel::<f32>::new();
+        for (i, weight) in vertical.iter().enumerate() {
+            let row_pixel = match edge.index(y + i as i64 - center_y, image.height) {
+                Some(row) => &rows[(row * width + x) as usize],
+                None => &outside,
+            };
+            sum.r += row_pixel.r * weight;
+            sum.g += row_pixel.g * weight;
+            sum.b += row_pixel.b * weight;
+        }
+        finish(index, sum)
+    }).collect()
+}
+
+/**
+Convolves the image with a separable kernel, which is the `horizontal` weights times the
+`vertical` weights. This gives the same result as convolve with the full kernel, but only
+takes the sum of their lengths for each pixel instead of the product, so it is much faster
+for big kernels like blurs. The center of each list is at its length / 2.
+*/
+pub fn convolve_separable<S: Sample>(image: PPM<S>, horizontal: &[f32], vertical: &[f32], edge: Edge<S>) -> PPM<S> {
+    // an image with no pixels, or an empty kernel, has nothing to convolve
+    if image.pixels.is_empty() || horizontal.is_empty() || vertical.is_empty() {
+        return image;
+    }
+
+    let mut convolved_image = PPM::new();
+
+    // copy header info
+    convolved_image.magic = image.magic.clone();
+    convolved_image.tupltype = image.tupltype.clone();
+    convolved_image.maxc = image.maxc;
+    convolved_image.width = image.width;
+    convolved_image.height = image.height;
+
+    // round and clamp the sums to the max color value
+    let maxc = image.maxc;
+    convolved_image.pixels = separable_convolution(&image, horizontal, vertical, edge, |_, sum| Pixel {
+        r: S::from_f32_clamped(sum.r, maxc),
+        g: S::from_f32_clamped(sum.g, maxc),
+        b: S::from_f32_clamped(sum.b, maxc),
+    });
+
+    // carry the alpha channel through the same transformation
+    let alpha_edge = alpha_edge(edge, image.maxc);
+    convolved_image.alpha = transform_alpha(&image, |alpha| convolve_separable(alpha, horizontal, vertical, alpha_edge));
+
+    convolved_image
+}
+
+/**
+Blurs the image by averaging the square of pixels reaching `radius` pixels around each pixel.
+*/
+pub fn box_blur<S: Sample>(image: PPM<S>, radius: u32, edge: Edge<S>) -> PPM<S> {
+    let weights = box_weights(radius);
+    convolve_separable(image, &weights, &weights, edge)
+}
+
+/**
+Blurs the image with a gaussian, where `sigma` is the standard deviation in pixels.
+*/
+pub fn gaussian_blur<S: Sample>(image: PPM<S>, sigma: f32, edge: Edge<S>) -> PPM<S> {
+    let weights = gaussian_weights(sigma);
+    convolve_separable(image, &weights, &weights, edge)
+}
+
+/**
+Sharpens the image with an unsharp mask, which adds back `amount` times the difference
+between the image and a gaussian blur of it with the standard deviation `sigma`. Samples
+that differ from the blur by less than `threshold`, a fraction of the max color value,
+are left alone so that noise in smooth areas isn't sharpened. The alpha channel is kept.
+*/
+pub fn unsharp_mask<S: Sample>(mut image: PPM<S>, amount: f32, sigma: f32, threshold: f32, edge: Edge<S>) -> PPM<S> {
+    if image.pixels.is_empty() {
+        return image;
+    }
+
+    // push each sample away from the blur, if it is far enough from it
+    let maxc = image.maxc;
+    let threshold = threshold * maxc as f32;
+    let sharpen = |sample: S, blurred: f32| {
+        let difference = sample.to_f32() - blurred;
+        match difference.abs() < threshold {
+            true => sample,
+            false => S::from_f32_clamped(sample.to_f32() + amount * difference, maxc),
+        }
+    };
+    let weights = gaussian_weights(sigma);
+    let pixels = separable_convolution(&image, &weights, &weights, edge, |index, blurred| {
+        let pixel = image.pixels[index];
+        Pixel { r: sharpen(pixel.r, blurred.r), g: sharpen(pixel.g, blurred.g), b: sharpen(pixel.b, blurred.b) }
+    });
+    image.pixels = pixels;
+
+    image
+}
+
+/*
+The LevelHistogram type counts the samples of one channel in a sliding window of a median
+filter. The samples are counted at every level, and in groups of neighboring levels, with
+about as many groups as there are levels in a group, so that the median can be found by going
+through the groups and then one group, instead of through every level.
+*/
+struct LevelHistogram {
+    counts: Vec<u32>, // the number of samples at each level
+    groups: Vec<u32>, // the number of samples in each group of levels
+    shift: u32, // the group of a level is found by shifting it right this many bits
+}
+
+impl LevelHistogram {
+    // constructor
+    fn new(maxc: u32) -> Self {
+        let shift = (32 - maxc.leading_zeros()) / 2;
+        LevelHistogram {
+            counts: vec![0; maxc as usize + 1],
+            groups: vec![0; (maxc >> shift) as usize + 1],
+            shift,
+        }
+    }
+
+    /**
+    Removes every sample.
+    */
+    fn clear(&mut self) {
+        self.counts.fill(0);
+        self.groups.fill(0);
+    }
+
+    /**
+    Adds a sample at the level, or removes one if `add` is false.
+    */
+    fn change(&mut self, level: usize, add: bool) {
+        match add {
+            true => {
+                self.counts[level] += 1;
+                self.groups[level >> self.shift] += 1;
+            }
+            false => {
+                self.counts[level] -= 1;
+                self.groups[level >> self.shift] -= 1;
+            }
+        }
+    }
+
+    /**
+    Returns the level of the sample with the given rank, counting from 0 at the darkest sample.
+    */
+    fn nth(&self, rank: u32) -> usize {
+        // find the group the sample is in, and then the level within the group
+        let mut passed = 0;
+        for (group, &count) in self.groups.iter().enumerate() {
+            if passed + count > rank {
+                let first = group << self.shift;
+                for (level, &count) in self.counts.iter().enumerate().skip(first) {
+                    passed += count;
+                    if passed > rank {
+                        return level;
+                    }
+                }
+            }
+            passed += count;
+        }
+        self.counts.len() - 1
+    }
+}
+
+/**
+Reduces noise by replacing each pixel with the median of each channel in the square reaching
+`radius` pixels around it, which removes specks without blurring edges. Integer images use a
+histogram of the window that slides along each row, so large radii stay fast. Radii past
+MAX_RADIUS are treated as MAX_RADIUS. The edge mode picks the pixels used past the edges of the
+image, and the alpha channel is kept.
+*/
+pub fn median_filter<S: Sample>(mut image: PPM<S>, radius: u32, edge: Edge<S>) -> PPM<S> {
+    if image.pixels.is_empty() || radius == 0 {
+        return image;
+    }
+
+    let width = image.width as usize;
+    let maxc = image.maxc;
+    let radius = radius.min(MAX_RADIUS) as i64;
+    let size = (2 * radius + 1) as usize;
+
+    // the median is the middle sample of the window
+    let rank = (size * size / 2) as u32;
+
+    let mut pixels = vec![Pixel::new(); image.pixels.len()];
+    match S::INTEGER {
+        true => {
+            let level = |value: S| (value.to_f32() as usize).min(maxc as usize);
+
+            // each row slides a window along, with one histogram for each channel
+            pixels.par_chunks_mut(width).enumerate().for_each_init(|| [LevelHistogram::new(maxc), LevelHistogram::new(maxc), LevelHistogram::new(maxc)], |histograms, (y, row)| {
+                let y = y as i64;
+                for histogram in histograms.iter_mut() {
+                    histogram.clear();
+                }
+
+                // adds or removes a column of the window
+                let column = |histograms: &mut [LevelHistogram; 3], x: i64, add: bool| {
+                    for window_y in y - radius..=y + radius {
+                        let pixel = pixel_at(&image, x, window_y, edge);
+                        histograms[0].change(level(pixel.r), add);
+                        histograms[1].change(level(pixel.g), add);
+                        histograms[2].change(level(pixel.b), add);
+                    }
+                };
+
+                // fill the window up to the left of the first pixel, then slide it one column at a time
+                for x in -radius..radius {
+                    column(histograms, x, true);
+                }
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let x = x as i64;
+                    column(histograms, x + radius, true);
+                    *pixel = Pixel {
+                        r: S::from_f32(histograms[0].nth(rank) as f32),
+                        g: S::from_f32(histograms[1].nth(rank) as f32),
+                        b: S::from_f32(histograms[2].nth(rank) as f32),
+                    };
+                    column(histograms, x - radius, false);
+                }
+            });
+        }
+
+        false => {
+            // floating point samples have no levels, so each window is partly sorted instead
+            pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+                let x = (index % width) as i64;
+                let y = (index / width) as i64;
+
+                let mut window = [Vec::with_capacity(size * size), Vec::with_capacity(size * size), Vec::with_capacity(size * size)];
+                for window_y in y - radius..=y + radius {
+                    for window_x in x - radius..=x + radius {
+                        let old_pixel = pixel_at(&image, window_x, window_y, edge);
+                        window[0].push(old_pixel.r.to_f32());
+                        window[1].push(old_pixel.g.to_f32());
+                        window[2].push(old_pixel.b.to_f32());
+                    }
+                }
+                let [r, g, b] = window.map(|mut samples| *samples.select_nth_unstable_by(rank as usize, f32::total_cmp).1);
+                *pixel = Pixel { r: S::from_f32(r), g: S::from_f32(g), b: S::from_f32(b) };
+            });
+        }
+    }
+    image.pixels = pixels;
+
+    image
+}
+
+/**
+Reduces noise while keeping edges sharp, by averaging each pixel with the pixels around it that
+have a similar color. `sigma_spatial` is the standard deviation of the distance in pixels, and
+`sigma_range` is the standard deviation of the color difference, as a fraction of the max color
+value, so colors that differ by much more than it aren't mixed. The window reaches 2 sigma, but
+no more than MAX_RADIUS. The alpha channel is kept.
+*/
+pub fn bilateral_filter<S: Sample>(mut image: PPM<S>, sigma_spatial: f32, sigma_range: f32, edge: Edge<S>) -> PPM<S> {
+    if image.pixels.is_empty() || sigma_spatial <= 0.0 || sigma_range <= 0.0 {
+        return image;
+    }
+
+    // the window reaches 2 sigma, and the distance weights are the same for every pixel
+    let radius = (2.0 * sigma_spatial).ceil().min(MAX_RADIUS as f32) as i64;
+    let size = 2 * radius + 1;
+    let spatial_weights = (0..size * size).map(|i| {
+        let (dx, dy) = ((i % size - radius) as f32, (i / size - radius) as f32);
+        (-(dx * dx + dy * dy) / (2.0 * sigma_spatial * sigma_spatial)).exp()
+    }).collect::<Vec<f32>>();
+
+    let width = image.width;
+    let maxc = image.maxc;
+    let range_scale = 1.0 / (2.0 * (sigma_range * maxc as f32).powi(2));
+
+    let mut pixels = vec![Pixel::new(); image.pixels.len()];
+    pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = ((index as u32) % width) as i64;
+        let y = ((index as u32) / width) as i64;
+        let center = float_pixel(image.pixels[index]);
+
+        let (mut r, mut g, mut b, mut total) = (0.0, 0.0, 0.0, 0.0);
+        for (i, spatial_weight) in spatial_weights.iter().enumerate() {
+            let old_pixel = edge_pixel(&image, x + i as i64 % size - radius, y + i as i64 / size - radius, edge);
+
+            // the color difference is averaged over the channels
+            let difference = ((old_pixel.r - center.r).powi(2) + (old_pixel.g - center.g).powi(2) + (old_pixel.b - center.b).powi(2)) / 3.0;
+            let weight = spatial_weight * (-difference * range_scale).exp();
+            r += old_pixel.r * weight;
+            g += old_pixel.g * weight;
+            b += old_pixel.b * weight;
+            total += weight;
+        }
+
+        // the center pixel always has a weight of 1, so the total is never 0
+        *pixel = Pixel {
+            r: S::from_f32_clamped(r / total, maxc),
+            g: S::from_f32_clamped(g / total, maxc),
+            b: S::from_f32_clamped(b / total, maxc),
+        };
+    });
+    image.pixels = pixels;
+
+    image
+}
+
+/**
+Smooths a `width` by `height` grid of values with the same weights along the rows and then
+the columns, such as box_weights or gaussian_weights, repeating the values on the edges past
+the edge of the grid. There must be an odd number of weights, centered on each value.
+*/
+pub(crate) fn blur_plane(values: &[f32], width: u32, height: u32, weights: &[f32]) -> Vec<f32> {
+    let (width, height, radius) = (width as i64, height as i64, (weights.len() / 2) as i64);
+
+    // smooth along the rows, and then along the columns
+    let mut rows = vec![0.0; values.len()];
+    rows.par_iter_mut().enumerate().for_each(|(index, sum)| {
+        let (x, y) = (index as i64 % width, index as i64 / width);
+        *sum = (x - radius..=x + radius).zip(weights).map(|(x, weight)| values[(y * width + x.clamp(0, width - 1)) as usize] * weight).sum::<f32>();
+    });
+    let mut sums = vec![0.0; values.len()];
+    sums.par_iter_mut().enumerate().for_each(|(index, sum)| {
+        let (x, y) = (index as i64 % width, index as i64 / width);
+        *sum = (y - radius..=y + radius).zip(weights).map(|(y, weight)| rows[(y.clamp(0, height - 1) * width + x) as usize] * weight).sum::<f32>();
+    });
+    sums
+}
+
+/**
+Reduces noise with non-local means, which averages each pixel with the pixels in the square
+reaching `search_radius` around it, weighted by how alike the patches reaching `patch_radius`
+around the two pixels are. Patterns that repeat across the image keep their detail, since
+they are only averaged with each other. `strength` is how different patches can be before
+they stop being mixed, as a fraction of the max color value, and should be about the amount
+of noise. The time taken grows with the square of the search radius, but not with the patch
+size. The edge mode picks the pixels used past the edges of the image, and the alpha channel is kept.
+*/
+pub fn non_local_means<S: Sample>(mut image: PPM<S>, strength: f32, patch_radius: u32, search_radius: u32, edge: Edge<S>) -> PPM<S> {
+    if image.pixels.is_empty() || strength <= 0.0 {
+        return image;
+    }
+
+    let width = image.width;
+    let maxc = image.maxc;
+    let scale = 1.0 / maxc as f32;
+    let strength_squared = strength * strength;
+    let search_radius = search_radius as i64;
+
+    // the weighted sums of the r, g, and b values of the pixels mixed into each pixel, and the sums of their weights
+    let mut totals = vec![[0.0f32; 4]; image.pixels.len()];
+    let mut differences = vec![0.0f32; image.pixels.len()];
+
+    // compare every pixel with the pixel at the same offset at once, for each offset in the search window
+    for offset_y in -search_radius..=search_radius {
+        for offset_x in -search_radius..=search_radius {
+            // the squared difference between each pixel and the pixel at the offset, averaged over the channels
+            differences.par_iter_mut().enumerate().for_each(|(index, difference)| {
+                let x = ((index as u32) % width) as i64;
+                let y = ((index as u32) / width) as i64;
+                let (a, b) = (float_pixel(image.pixels[index]), edge_pixel(&image, x + offset_x, y + offset_y, edge));
+                *difference = ((a.r - b.r).powi(2) + (a.g - b.g).powi(2) + (a.b - b.b).powi(2)) * scale * scale / 3.0;
+            });
+
+            // averaging the differences over each patch gives the distance between the patches around the two pixels
+            let distances = blur_plane(&differences, width, image.height, &box_weights(patch_radius));
+
+            totals.par_iter_mut().zip(distances.par_iter()).enumerate().for_each(|(index, (total, distance))| {
+                let x = ((index as u32) % width) as i64;
+                let y = ((index as u32) / width) as i64;
+                let weight = (-distance / strength_squared).exp();
+                let other = edge_pixel(&image, x + offset_x, y + offset_y, edge);
+                total[0] += other.r * weight;
+                total[1] += other.g * weight;
+                total[2] += other.b * weight;
+                total[3] += weight;
+            });
+        }
+    }
+
+    // the pixel itself is always mixed in with a weight of 1, so the total weight is never 0
+    image.pixels.par_iter_mut().zip(totals.par_iter()).for_each(|(pixel, total)| {
+        *pixel = Pixel {
+            r: S::from_f32_clamped(total[0] / total[3], maxc),
+            g: S::from_f32_clamped(total[1] / total[3], maxc),
+            b: S::from_f32_clamped(total[2] / total[3], maxc),
+        };
+    });
+
+    image
+}
+
+/*
+The GradientOperator type selects the 3x3 kernels used to find the gradient of an image.
+Each one takes the difference of the pixels on either side, smoothed across the gradient.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GradientOperator {
+    Sobel, // smooths with weights of 1, 2, 1
+    Prewitt, // smooths with weights of 1, 1, 1
+    Scharr, // smooths with weights of 3, 10, 3, which gives the most accurate directions
+}
+
+impl GradientOperator {
+    /**
+    Returns the smoothing weights, scaled so they add up to 1.
+    */
+    fn smoothing(&self) -> [f32; 3] {
+        match self {
+            GradientOperator::Sobel => [0.25, 0.5, 0.25],
+            GradientOperator::Prewitt => [1.0 / 3.0; 3],
+            GradientOperator::Scharr => [3.0 / 16.0, 10.0 / 16.0, 3.0 / 16.0],
+        }
+    }
+}
+
+/**
+Returns the luma of a pixel, with the Rec. 709 weights.
+*/
+fn luma<S: Sample>(pixel: Pixel<S>) -> f32 {
+    0.2126 * pixel.r.to_f32() + 0.7152 * pixel.g.to_f32() + 0.0722 * pixel.b.to_f32()
+}
+
+/**
+Returns the horizontal and vertical gradient of the luma at each pixel, found with the operator.
+A step from black to white gives a gradient of the max color value, and the gradients point
+towards the brighter side, with y going down the image. The image must not be empty.
+*/
+fn luma_gradients<S: Sample>(image: &PPM<S>, operator: GradientOperator, edge: Edge<S>) -> Vec<(f32, f32)> {
+    let levels = image.pixels.par_iter().map(|&pixel| luma(pixel)).collect::<Vec<f32>>();
+    let fill = match edge {
+        Edge::Constant(fill) => luma(fill),
+        _ => 0.0,
+    };
+    let level_at = |x: i64, y: i64| match (edge.index(x, image.width), edge.index(y, image.height)) {
+        (Some(x), Some(y)) => levels[(y * image.width + x) as usize],
+        _ => fill,
+    };
+
+    // each gradient is the difference of the pixels on either side, with the three rows or columns smoothed
+    let smoothing = operator.smoothing();
+    let width = image.width;
+    let mut gradients = vec![(0.0, 0.0); levels.len()];
+    gradients.par_iter_mut().enumerate().for_each(|(index, gradient)| {
+        let x = ((index as u32) % width) as i64;
+        let y = ((index as u32) / width) as i64;
+
+        let (mut horizontal, mut vertical) = (0.0, 0.0);
+        for (offset, weight) in (-1..=1).zip(smoothing) {
+            horizontal += weight * (level_at(x + 1, y + offset) - level_at(x - 1, y + offset));
+            vertical += weight * (level_at(x + offset, y + 1) - level_at(x + offset, y - 1));
+        }
+        *gradient = (horizontal, vertical);
+    });
+    gradients
+}
+
+/**
+Builds a gray image with the header of `image`, with each level clamped to the max color value.
+*/
+pub(crate) fn gray_image<S: Sample>(image: &PPM<S>, levels: impl IndexedParallelIterator<Item = f32>) -> PPM<S> {
+    let mut gray_image = PPM::new();
+
+    // copy header info
+    gray_image.magic = image.magic.clone();
+    gray_image.tupltype = image.tupltype.clone();
+    gray_image.maxc = image.maxc;
+    gray_image.width = image.width;
+    gray_image.height = image.height;
+
+    let maxc = image.maxc;
+    gray_image.pixels = levels.map(|level| {
+        let level = S::from_f32_clamped(level, maxc);
+        Pixel { r: level, g: level, b: level }
+    }).collect();
+
+    gray_image
+}
+
+/**
+Finds the edges in the image with a gradient operator, and returns two gray images. The first
+is the magnitude of the gradient, where a step from black to white is white, and the second is
+the direction it points in, towards the brighter side, going counter-clockwise from black
+pointing right to white after a full turn. The gradient is taken of the luma, so it is the
+same for every channel, and the edge mode picks the pixels used past the edges of the image.
+Neither image has an alpha channel.
+*/
+pub fn gradient<S: Sample>(image: PPM<S>, operator: GradientOperator, edge: Edge<S>) -> (PPM<S>, PPM<S>) {
+    if image.pixels.is_empty() {
+        return (gray_image(&image, Vec::new().into_par_iter()), gray_image(&image, Vec::new().into_par_iter()));
+    }
+
+    let gradients = luma_gradients(&image, operator, edge);
+    let maxc = image.maxc as f32;
+    let magnitude = gray_image(&image, gradients.par_iter().map(|(x, y)| x.hypot(*y)));
+
+    // y goes down the image, so it is flipped to go counter-clockwise
+    let direction = gray_image(&image, gradients.par_iter().map(|(x, y)| (-y).atan2(*x).to_degrees().rem_euclid(360.0) / 360.0 * maxc));
+
+    (magnitude, direction)
+}
+
+/**
+Finds the edges in the image with the Canny detector, and returns an image where the edges are
+white lines one pixel wide on a black background. The image is smoothed with a gaussian of
+`sigma` first, so noise isn't found as edges. Then the Sobel gradient is thinned to the pixels
+where it is strongest across the edge, and edges are kept where the gradient is above `high`,
+along with the parts connected to them where it is above `low`. Both thresholds are fractions
+of the max color value, where a step from black to white is 1. The gradient is taken of the
+luma, and the edge mode picks the pixels used past the edges of the image. The result has no
+alpha channel, use to_bitmap on it to save it as a bitmap.
+*/
+pub fn canny<S: Sample>(image: PPM<S>, sigma: f32, low: f32, high: f32, edge: Edge<S>) -> PPM<S> {
+    if image.pixels.is_empty() {
+        return gray_image(&image, Vec::new().into_par_iter());
+    }
+
+    let image = match sigma > 0.0 {
+        true => gaussian_blur(image, sigma, edge),
+        false => image,
+    };
+    let gradients = luma_gradients(&image, GradientOperator::Sobel, edge);
+    let magnitudes = gradients.par_iter().map(|(x, y)| x.hypot(*y)).collect::<Vec<f32>>();
+    let (width, height) = (image.width as i64, image.height as i64);
+    let magnitude_at = |x: i64, y: i64| match (0..width).contains(&x) && (0..height).contains(&y) {
+        true => magnitudes[(y * width + x) as usize],
+        false => 0.0,
+    };
+
+    // thin the edges by keeping the pixels that are stronger than both neighbors along the gradient, where a tie
+    // with the neighbor ahead keeps this pixel so a step only gives one, and mark them as weak or strong edges
+    let (low, high) = (low * image.maxc as f32, high * image.maxc as f32);
+    let mut strengths = vec![0u8; magnitudes.len()]; // 0 for no edge, 1 for a weak edge, and 2 for a strong edge
+    strengths.par_iter_mut().enumerate().for_each(|(index, strength)| {
+        let x = index as i64 % width;
+        let y = index as i64 / width;
+        let magnitude = magnitudes[index];
+        if magnitude <= low {
+            return;
+        }
+
+        // round the direction of the gradient to the nearest of the four lines through the neighbors
+        let (gradient_x, gradient_y) = gradients[index];
+        let (offset_x, offset_y) = match (gradient_y.atan2(gradient_x).to_degrees().rem_euclid(180.0) / 45.0).round() as u32 {
+            1 => (1, 1),
+            2 => (0, 1),
+            3 => (-1, 1),
+            _ => (1, 0),
+        };
+        if magnitude >= magnitude_at(x + offset_x, y + offset_y) && magnitude > magnitude_at(x - offset_x, y - offset_y) {
+            *strength = match magnitude > high {
+                true => 2,
+                false => 1,
+            };
+        }
+    });
+
+    // follow the weak edges out from the strong ones, through all 8 neighbors
+    let mut edges = vec![false; strengths.len()];
+    let mut stack = (0..strengths.len()).filter(|&index| strengths[index] == 2).collect::<Vec<usize>>();
+    for &index in &stack {
+        edges[index] = true;
+    }
+    while let Some(index) = stack.pop() {
+        let x = index as i64 % width;
+        let y = index as i64 / width;
+        for neighbor_y in (y - 1).max(0)..=(y + 1).min(height - 1) {
+            for neighbor_x in (x - 1).max(0)..=(x + 1).min(width - 1) {
+                let neighbor = (neighbor_y * width + neighbor_x) as usize;
+                if strengths[neighbor] == 1 && !edges[neighbor] {
+                    edges[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+
+    let maxc = image.maxc as f32;
+    gray_image(&image, edges.par_iter().map(|&edge| match edge {
+        true => maxc,
+        false => 0.0,
+    }))
+}
+
+/**
+Builds `rows` rows of a transformed mapped image, starting at `first_row`.
+The transformed image is `width` pixels wide, and `mapping` gives the coordinate
+in the mapped image of each pixel in the transformed image. The samples must be
+able to hold the max color value of the mapped image.
+*/
+#[cfg(feature = "mmap")]
+fn mapped_rows<S: IntegerSample>(image: &MappedImage, width: u32, first_row: u32, rows: u32, mapping: impl Fn(u32, u32) -> (u32, u32) + Sync) -> PPM<S> {
+    let header = image.header();
+    let mut transformed_image = PPM::new();
+
+    // copy header info
+    transformed_image.magic = header.magic.clone();
+    transformed_image.tupltype = header.tupltype.clone();
+    transformed_image.maxc = header.maxc;
+    transformed_image.width = width;
+    transformed_image.height = rows;
+
+    // assign the pixel array for the new rows
+    transformed_image.pixels = vec![Pixel::new(); transformed_image.width as usize * transformed_image.height as usize];
+
+    // loop through each pixel in the new rows
+    transformed_image.pixels.par_iter_mut().enumerate().for_each(|(index, pixel)| {
+        let x = (index as u32) % width;
+        let y = first_row + (index as u32) / width;
+
+        let (old_x, old_y) = mapping(x, y);
+        *pixel = image.get_pixel(old_x, old_y).unwrap(); // guaranteed to be in the image
+    });
+
+    // carry the alpha channel through the same transformation
+    if header.alpha.is_some() {
+        transformed_image.alpha = Some((0..width * rows).into_par_iter().map(|index| {
+            let (old_x, old_y) = mapping(index % width, first_row + index / width);
+            image.get_alpha(old_x, old_y).unwrap()
+        }).collect());
+    }
+
+    transformed_image
+}
+
+/**
+Returns `rows` rows of the mapped image rotated 90 degrees counter-clockwise, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn rotate_left_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let (width, height) = (image.header().width, image.header().height);
+    mapped_rows(image, height, first_row, rows, |x, y| (width - y - 1, x))
+}
+
+/**
+Returns `rows` rows of the mapped image rotated 90 degrees clockwise, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn rotate_right_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let height = image.header().height;
+    mapped_rows(image, height, first_row, rows, |x, y| (y, height - x - 1))
+}
+
+/**
+Returns `rows` rows of the mapped image flipped in the vertical direction, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn flip_vertical_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let (width, height) = (image.header().width, image.header().height);
+    mapped_rows(image, width, first_row, rows, |x, y| (x, height - y - 1))
+}
+
+/**
+Returns `rows` rows of the mapped image rotated 180 degrees, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn rotate_180_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let (width, height) = (image.header().width, image.header().height);
+    mapped_rows(image, width, first_row, rows, |x, y| (width - x - 1, height - y - 1))
+}
+
+/**
+Returns `rows` rows of the mapped image flipped over its main diagonal, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn transpose_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let height = image.header().height;
+    mapped_rows(image, height, first_row, rows, |x, y| (y, x))
+}
+
+/**
+Returns `rows` rows of the mapped image flipped over its anti-diagonal, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+pub fn transverse_mapped<S: IntegerSample>(image: &MappedImage, first_row: u32, rows: u32) -> PPM<S> {
+    let (width, height) = (image.header().width, image.header().height);
+    mapped_rows(image, height, first_row, rows, |x, y| (width - y - 1, height - x - 1))
+}
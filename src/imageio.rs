@@ -1,243 +1,1381 @@
+use crate::ppm::{PPM, Pixel, Sample, IntegerSample, Image};
+use crate::error::PpmError;
+use std::io::{BufRead, Read, BufWriter, Write};
+use rayon::prelude::*;
 
-use crate::ppm::{PPM, Pixel};
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, BufWriter, Write};
-use std::path::Path;
-use std::process;
+#[cfg(feature = "mmap")]
+use std::{fs::File, path::Path};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+
+/**
+Returns true if the magic number is one of the Netpbm formats this tool understands (P1 to P7).
+*/
+pub fn is_supported_magic(magic: &str) -> bool {
+    matches!(magic, "P1" | "P2" | "P3" | "P4" | "P5" | "P6" | "P7")
+}
+
+/**
+Returns true if the max color value is allowed by the Netpbm spec (1 to 65535).
+Samples take one byte when the max color value is below 256, and two bytes otherwise.
+*/
+pub fn is_supported_maxc(maxc: u32) -> bool {
+    (1..=65535).contains(&maxc)
+}
+
+/**
+Returns true if the magic number belongs to a bitmap (PBM) format.
+Bitmaps have no max color value in their header.
+*/
+fn is_bitmap(magic: &str) -> bool {
+    magic == "P1" || magic == "P4"
+}
+
+/**
+Returns the number of samples in each PAM tuple, and whether the last sample is alpha.
+The tuple type must already have been checked by parse_header.
+*/
+fn pam_layout(tupltype: &str) -> (usize, bool) {
+    match tupltype {
+        "GRAYSCALE" => (1, false),
+        "GRAYSCALE_ALPHA" => (2, true),
+        "RGB_ALPHA" => (4, true),
+        _ => (3, false),
+    }
+}
+
+/*
+The HeaderTokenizer reads the blocks of a Netpbm header one byte at a time.
+Blocks are separated by any amount of whitespace (spaces, tabs, CR, LF, ...),
+and a '#' anywhere starts a comment that runs to the end of the line.
+It keeps track of how many bytes have been read, so that the exact position
+of the pixel data is known once the header is finished.
+*/
+struct HeaderTokenizer<'a, R: BufRead> {
+    reader: &'a mut R, // the reader the header is read from
+    offset: u64, // the number of bytes read so far
+}
+
+impl<'a, R: BufRead> HeaderTokenizer<'a, R> {
+    // constructor
+    fn new(reader: &'a mut R) -> Self {
+        HeaderTokenizer {
+            reader,
+            offset: 0,
+        }
+    }
+
+    /**
+    Reads a single byte, or returns None at the end of the file.
+    */
+    fn next_byte(&mut self) -> Result<Option<u8>, PpmError> {
+        let mut buffer = [0u8; 1];
+        match self.reader.read(&mut buffer)? {
+            0 => Ok(None),
+            _ => {
+                self.offset += 1;
+                Ok(Some(buffer[0]))
+            }
+        }
+    }
+
+    /**
+    Skips the rest of a comment, up to and including the end of the line.
+    */
+    fn skip_comment(&mut self) -> Result<(), PpmError> {
+        while let Some(byte) = self.next_byte()? {
+            if byte == b'\n' || byte == b'\r' {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+    Returns the next block of the header.
+    The single byte that ends the block is consumed, so after the last block of the
+    header the reader is positioned at the first byte of the pixel data.
+    */
+    fn next_token(&mut self) -> Result<String, PpmError> {
+        let mut token = Vec::new();
+
+        loop {
+            let byte = match self.next_byte()? {
+                Some(byte) => byte,
+                None if token.is_empty() => return Err(PpmError::TruncatedHeader),
+                None => break, // the file ends right after the block
+            };
+
+            match byte {
+                b'#' => {
+                    // a comment, which also ends the current block
+                    self.skip_comment()?;
+                    if !token.is_empty() {
+                        break;
+                    }
+                }
+
+                // whitespace separates blocks, so skip it before a block and stop after one
+                _ if byte.is_ascii_whitespace() => {
+                    if !token.is_empty() {
+                        break;
+                    }
+                }
+
+                _ => token.push(byte),
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&token).to_string())
+    }
+}
 
 /* File Operations */
-pub fn parse_header(reader: &mut BufReader<File>, image: &mut PPM) -> usize {
+pub fn parse_header<R: BufRead, S: Sample>(reader: &mut R, image: &mut PPM<S>) -> Result<u64, PpmError> {
     /*
 	parseHeader()
 
-	This function takes in an input file and a PPM object.
-	It then reads the header information from the file and assigns it to the PPM object.
+	This function takes in a reader and a PPM object.
+	It then reads the header information from the reader and assigns it to the PPM object.
+	On success, the byte offset where the pixel data starts is returned, and the reader
+	is positioned at that offset.
+
+	Bitmaps (P1 and P4) do not store a max color value, so their pixels are
+	stored as black (0) and white (255) and the max color value is set to 255.
 	*/
 
-    let mut info_block = 0; // keeps track of which part of the header is being read
+    // 1. Read the magic number, which must be the first two bytes of the file
+    // 2. Read the width, height, and (except for bitmaps) max color blocks, skipping whitespace and comments
+    // 3. Assign the data to the PPM object
+    // 4. If the file runs out, return an error
 
-    // 1. Loop through all lines until all the required data is read (info_block == 3)
-    // 2. If the line is a comment, skip it
-    // 3. Otherwise, split the line into blocks by spaces
-    // 4. For each line, loop over each block until the end of the line, then go to 2
-    // 5. Block 1 is the magic number, block 2 is the width, block 3 is the height, block 4 is the max color value
-    // 6. Assign the data to the PPM object
-    // 7. If the lines run out, exit with an error message
+    let mut tokenizer = HeaderTokenizer::new(reader);
 
-    // loop through each line in the file
-    for (index, line) in reader.lines().enumerate() {
+    // Block 1: Magic Number
+    let mut magic = [0u8; 2];
+    for byte in magic.iter_mut() {
+        *byte = match tokenizer.next_byte()? {
+            Some(byte) => byte,
+            None => return Err(PpmError::TruncatedHeader),
+        };
+    }
+    let magic = String::from_utf8_lossy(&magic).to_string();
+    if !is_supported_magic(&magic) {
+        return Err(PpmError::UnsupportedMagic(magic));
+    }
+    image.magic = magic;
 
-        // if all required data has been read, exit the loop
-        if info_block >= 4 {
-            return index; // return the line number where the header ends
-        }
-        
-        // get the current line, or exit with error if lines run out
-        let line = match line {
-            Ok(line) => line,
-            Err(_) => {
-                eprintln!("End of file reached before all header information was read.");
-                process::exit(1);
-            }
+    // PAM images have a header made of KEY value lines instead of a fixed order of blocks
+    if image.magic == "P7" {
+        parse_pam_header(&mut tokenizer, image)?;
+        return Ok(tokenizer.offset);
+    }
+
+    // remember what kind of pixels the image has, which is used when saving as PAM
+    image.tupltype = match image.magic.as_str() {
+        "P3" | "P6" => String::from("RGB"),
+        _ => String::from("GRAYSCALE"),
+    };
+
+    // Block 2: Width
+    let block = tokenizer.next_token()?;
+    image.width = match block.parse::<u32>() {
+        Ok(num) => num,
+        Err(_) => return Err(PpmError::InvalidWidth(block)),
+    };
+
+    // Block 3: Height
+    let block = tokenizer.next_token()?;
+    image.height = match block.parse::<u32>() {
+        Ok(num) => num,
+        Err(_) => return Err(PpmError::InvalidHeight(block)),
+    };
+
+    // make sure the pixels of the image can be counted
+    image.pixel_count()?;
+
+    // Block 4: Max Color, which bitmaps don't have
+    if is_bitmap(&image.magic) {
+        image.maxc = 255;
+    } else {
+        let block = tokenizer.next_token()?;
+        image.maxc = match block.parse::<u32>() {
+            Ok(maxc) => maxc,
+            Err(_) => return Err(PpmError::InvalidMaxColor(block)),
         };
 
-        // if the line is a comment, skip it
-        if line.starts_with("#") {
-            continue;
+        // the Netpbm spec allows any max color value from 1 to 65535
+        if !is_supported_maxc(image.maxc) {
+            return Err(PpmError::UnsupportedMaxColor(image.maxc));
         }
+    }
 
-        // split the line into blocks by spaces
-        let blocks: Vec<&str> = line.split(' ').collect();
+    // all data has been read, and the reader is now at the start of the pixel data
+    Ok(tokenizer.offset)
+}
 
-        // loop through each block in the line
-        for block in blocks {
+fn parse_pam_header<R: BufRead, S: Sample>(tokenizer: &mut HeaderTokenizer<R>, image: &mut PPM<S>) -> Result<(), PpmError> {
+    /*
+	parsePamHeader()
 
-            // save the current block as a property of the PPM object depending on which block it is
-            match info_block {
-                0 => {
-                    // Block 1: Magic Number
-                    image.magic = block.to_string();
-                    info_block += 1;
-                }
+	This function reads the rest of a PAM (P7) header after the magic number.
+	The header is a list of KEY value lines in any order, ending with ENDHDR.
+	WIDTH, HEIGHT, DEPTH, and MAXVAL are required. If TUPLTYPE is missing, it is
+	guessed from the depth. BLACKANDWHITE tuples are read as grayscale, since they
+	are simply grayscale images with a max color value of 1.
+	*/
 
-                1 => {
-                    // Block 2: Width
-                    image.width = match block.parse::<u32>() {
-                        Ok(num) => num,
-                        Err(_) => {
-                            eprintln!("Header block for width failed to parse. Possibly invalid file type.");
-                            process::exit(1);
-                        }
-                    };
-                    info_block += 1;
-                }
+    let mut width = None;
+    let mut height = None;
+    let mut depth = None;
+    let mut maxc = None;
+    let mut tupltype = String::new();
 
-                2 => {
-                    // Block 3: Height
-                    image.height = match block.parse::<u32>() {
-                        Ok(num) => num,
-                        Err(_) => {
-                            eprintln!("Header block for height failed to parse. Possibly invalid file type.");
-                            process::exit(1);
-                        }
-                    };
-                    info_block += 1;
-                }
+    // read each line of the header until ENDHDR
+    loop {
+        let key = tokenizer.next_token()?;
+        match key.as_str() {
+            "ENDHDR" => break,
 
-                3 => {
-                    // Block 4: Max Color
-                    image.maxc = match block.parse::<u32>() {
-                        Ok(maxc) => maxc,
-                        Err(_) => {
-                            eprintln!("Header block for max color failed to parse. Possibly invalid file type.");
-                            process::exit(1);
-                        }
-                    };
-                    info_block += 1;
-                }
+            "WIDTH" => {
+                let value = tokenizer.next_token()?;
+                width = match value.parse::<u32>() {
+                    Ok(num) => Some(num),
+                    Err(_) => return Err(PpmError::InvalidWidth(value)),
+                };
+            }
 
-                _ => {
-                    // info_block is none of the above, which means we've read all required blocks
-                    break;
-                }
+            "HEIGHT" => {
+                let value = tokenizer.next_token()?;
+                height = match value.parse::<u32>() {
+                    Ok(num) => Some(num),
+                    Err(_) => return Err(PpmError::InvalidHeight(value)),
+                };
+            }
+
+            "DEPTH" => {
+                let value = tokenizer.next_token()?;
+                depth = match value.parse::<u32>() {
+                    Ok(num) => Some(num),
+                    Err(_) => return Err(PpmError::InvalidPamHeader(format!("DEPTH {}", value))),
+                };
+            }
+
+            "MAXVAL" => {
+                let value = tokenizer.next_token()?;
+                maxc = match value.parse::<u32>() {
+                    Ok(num) => Some(num),
+                    Err(_) => return Err(PpmError::InvalidMaxColor(value)),
+                };
+            }
+
+            "TUPLTYPE" => {
+                tupltype = tokenizer.next_token()?;
             }
+
+            _ => return Err(PpmError::InvalidPamHeader(key)),
         }
     }
 
-    return 0;
+    // every field except TUPLTYPE is required
+    image.width = width.ok_or_else(|| PpmError::InvalidPamHeader(String::from("missing WIDTH")))?;
+    image.height = height.ok_or_else(|| PpmError::InvalidPamHeader(String::from("missing HEIGHT")))?;
+    let depth = depth.ok_or_else(|| PpmError::InvalidPamHeader(String::from("missing DEPTH")))?;
+    image.maxc = maxc.ok_or_else(|| PpmError::InvalidPamHeader(String::from("missing MAXVAL")))?;
+
+    // make sure the pixels of the image can be counted
+    image.pixel_count()?;
+
+    // the Netpbm spec allows any max color value from 1 to 65535
+    if !is_supported_maxc(image.maxc) {
+        return Err(PpmError::UnsupportedMaxColor(image.maxc));
+    }
+
+    // guess the tuple type from the depth if it's missing
+    if tupltype.is_empty() {
+        tupltype = match depth {
+            1 => String::from("GRAYSCALE"),
+            2 => String::from("GRAYSCALE_ALPHA"),
+            3 => String::from("RGB"),
+            4 => String::from("RGB_ALPHA"),
+            _ => return Err(PpmError::UnsupportedTupleType(format!("depth {}", depth))),
+        };
+    }
+
+    // black and white tuples are grayscale with a max color value of 1
+    let tupltype = match tupltype.as_str() {
+        "BLACKANDWHITE" => String::from("GRAYSCALE"),
+        "BLACKANDWHITE_ALPHA" => String::from("GRAYSCALE_ALPHA"),
+        _ => tupltype,
+    };
+
+    // make sure the tuple type is one we can read, and that it matches the depth
+    let expected_depth = match tupltype.as_str() {
+        "GRAYSCALE" => 1,
+        "GRAYSCALE_ALPHA" => 2,
+        "RGB" => 3,
+        "RGB_ALPHA" => 4,
+        _ => return Err(PpmError::UnsupportedTupleType(tupltype)),
+    };
+    if depth != expected_depth {
+        return Err(PpmError::UnsupportedTupleType(format!("{} with depth {}", tupltype, depth)));
+    }
 
-    // all data has been read and the image has been modified
-    // in addition, the reader is now at the start of the pixel data
+    image.tupltype = tupltype;
+
+    Ok(())
 }
 
 
-pub fn read_image_data(reader: &mut BufReader<File>, image: &mut PPM, header_length: usize) {
+pub fn read_image_data<R: Read, S: IntegerSample>(reader: &mut R, image: &mut PPM<S>) -> Result<(), PpmError> {
     /*
 	readImageData()
 
 	This function takes reader and a PPM object as arguments.
-	It reads the binary data from the file and stores it as pixels in the PPM object.
-	This function requires that parseHeader was run previously, or else the file stream
-	cursor will in the wrong place.
+	It reads the pixel data from the file and stores it as pixels in the PPM object.
+	Grayscale images are stored with equal r, g, and b values, and bitmaps are
+	stored as black and white pixels with a max color value of 255. The alpha
+	channel of PAM images is stored separately from the pixels.
+	This function requires that parseHeader was run previously on the same reader,
+	which leaves the reader at the start of the pixel data. No seeking is done, so
+	the reader can be a pipe such as stdin.
+	The samples of the PPM object must be able to hold the max color value, so
+	8-bit images can be read into either type, and 16-bit images need u16 samples.
 	*/
 
-    // Reset the seeker and move it to the start of the pixel data
-    reader.seek(SeekFrom::Start(0)).expect("Failed to seek to start of file.");
-    let mut tmp_str = String::new();
-    for _ in 0..header_length {
-        reader.read_line(&mut tmp_str).expect("Failed to skip line of the header.");
-    }
-
-    // initialize the image pixel data
-    image.pixels = Vec::with_capacity(image.pixel_count() as usize);
-
-    // loop through each pixel in the image
-    for _ in 0..image.pixel_count() {
-        // Process 8 bit data
-        match image.maxc {
-            255 => {
-                // Process 8 bit color data
-                let mut buffer = [0u8; 3]; // create a temporary buffer to store the pixel data
-                match reader.read_exact(&mut buffer) { // read the data into the buffer
-                    Ok(_) => {
-                        // store the pixel info in the PPM object
-                        let pixel = Pixel {
-                            r: buffer[0] as u16,
-                            g: buffer[1] as u16,
-                            b: buffer[2] as u16,
-                        };
-                        image.pixels.push(pixel);
-                    }
-                    Err(error) => {
-                        eprintln!("{:?}", error);
-                        eprintln!("End of file reached before all pixel data was read. File may be corrupted.");
-                        process::exit(1);
-                    }
-                };
+    // verify that the bit depth is supported before reading anything
+    if !is_supported_maxc(image.maxc) || image.maxc > S::MAX {
+        return Err(PpmError::UnsupportedMaxColor(image.maxc));
+    }
+
+    // initialize the image pixel data, which grows as it's read so a header that lies about
+    // the size of the image can't reserve more memory than the data it's followed by
+    let count = image.pixel_count()?;
+    image.pixels = Vec::new();
+    image.alpha = None;
+
+    match image.magic.as_str() {
+        "P1" => {
+            // Plain bitmap: 1 is black and 0 is white
+            for value in read_plain_samples(reader, count, 1, true)? {
+                let level = S::from_u16(if value == 1 { 0 } else { 255 });
+                image.pixels.push(Pixel { r: level, g: level, b: level });
             }
+        }
 
-            65535 => {
-                // Process 16 bit color data
-                let mut buffer = [0u8; 6]; // create a temporary buffer to store the pixel data
-                match reader.read_exact(&mut buffer) { // read the data into the buffer
-                    Ok(_) => {
-                        // store the pixel info in the PPM object
-                        let pixel = Pixel {
-                            r: u16::from_be_bytes([buffer[0], buffer[1]]),
-                            g: u16::from_be_bytes([buffer[2], buffer[3]]),
-                            b: u16::from_be_bytes([buffer[4], buffer[5]])
-                        };
-                        image.pixels.push(pixel);
-                    }
-                    Err(_) => {
-                        eprintln!("End of file reached before all pixel data was read. File may be corrupted.");
-                        process::exit(1);
-                    }
-                };
+        "P2" => {
+            // Plain graymap: one sample per pixel
+            for value in read_plain_samples(reader, count, image.maxc, false)? {
+                let value = S::from_u16(value);
+                image.pixels.push(Pixel { r: value, g: value, b: value });
             }
+        }
 
-            _ => {
-                // Invalid bit depth
-                eprintln!("Cannot parse pixel data for image with max color value of {}.", image.maxc);
-                process::exit(1);
+        "P3" => {
+            // Plain pixmap: three samples per pixel
+            let samples = read_plain_samples(reader, count * 3, image.maxc, false)?;
+            for chunk in samples.chunks_exact(3) {
+                image.pixels.push(Pixel { r: S::from_u16(chunk[0]), g: S::from_u16(chunk[1]), b: S::from_u16(chunk[2]) });
+            }
+        }
+
+        "P4" => read_raw_bitmap(reader, image)?,
+
+        "P5" => read_raw_pixels(reader, image, 1, false)?,
+
+        "P6" => read_raw_pixels(reader, image, 3, false)?,
+
+        "P7" => {
+            // PAM: each tuple has 1 to 4 samples, with alpha as the last sample
+            let (depth, has_alpha) = pam_layout(&image.tupltype);
+            read_raw_pixels(reader, image, depth, has_alpha)?;
+        }
+
+        _ => return Err(PpmError::UnsupportedMagic(image.magic.clone())),
+    }
+
+    Ok(())
+}
+
+/**
+The number of bytes of raw pixel data that are read or written at once.
+Pixel data is handled in blocks of whole rows of about this size, so that
+I/O is done in large chunks while the extra memory used stays bounded.
+*/
+const BLOCK_SIZE: usize = 1 << 20;
+
+/**
+Returns the number of rows that fit in one block, which is always at least one.
+*/
+fn rows_per_block(row_bytes: usize) -> usize {
+    (BLOCK_SIZE / row_bytes.max(1)).max(1)
+}
+
+/**
+Reads exactly `length` bytes of pixel data into the buffer, which grows as the bytes arrive
+instead of all at once, so that a header that promises more data than the file has can't
+make it reserve memory for all of it.
+*/
+fn read_block<R: Read>(reader: &mut R, buffer: &mut Vec<u8>, length: usize) -> Result<(), PpmError> {
+    buffer.clear();
+    match reader.take(length as u64).read_to_end(buffer) {
+        Ok(read) if read == length => Ok(()),
+        _ => Err(PpmError::ShortPixelData),
+    }
+}
+
+/**
+Decodes one row of raw tuples into pixels, using `sample` to read each sample of a tuple.
+Tuples with a depth of 1 or 2 are grayscale, and set every channel to the same level.
+*/
+fn decode_row<S: IntegerSample>(row: &mut [Pixel<S>], data: &[u8], depth: usize, sample: impl Fn(&[u8], usize) -> u16) {
+    let tuple_bytes = data.len() / row.len();
+    let sample = |tuple: &[u8], index: usize| S::from_u16(sample(tuple, index));
+    for (pixel, tuple) in row.iter_mut().zip(data.chunks_exact(tuple_bytes)) {
+        *pixel = match depth {
+            1 | 2 => {
+                let value = sample(tuple, 0);
+                Pixel { r: value, g: value, b: value }
             }
+            _ => Pixel { r: sample(tuple, 0), g: sample(tuple, 1), b: sample(tuple, 2) },
         };
     }
 }
 
-pub fn write_image(output_file_path: &Path, image: &PPM) {
+/**
+Decodes the alpha samples of one row of raw tuples, which are always the last sample of a tuple.
+*/
+fn decode_alpha_row<S: IntegerSample>(row: &mut [S], data: &[u8], depth: usize, sample: impl Fn(&[u8], usize) -> u16) {
+    let tuple_bytes = data.len() / row.len();
+    for (value, tuple) in row.iter_mut().zip(data.chunks_exact(tuple_bytes)) {
+        *value = S::from_u16(sample(tuple, depth - 1));
+    }
+}
+
+/**
+Reads sample `index` of an 8-bit tuple.
+*/
+fn sample_8(tuple: &[u8], index: usize) -> u16 {
+    tuple[index] as u16
+}
+
+/**
+Reads sample `index` of a 16-bit tuple, which is stored big endian.
+*/
+fn sample_16(tuple: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([tuple[index * 2], tuple[index * 2 + 1]])
+}
+
+fn read_raw_pixels<R: Read, S: IntegerSample>(reader: &mut R, image: &mut PPM<S>, depth: usize, has_alpha: bool) -> Result<(), PpmError> {
     /*
-	writeImageToFile()
+	readRawPixels()
 
-	This function takes a filestream pointer and a PPM object pointer as arguments.
-	It writes a new P6 PPM file to the filestream pointer using the data in the PPM object.
-	The filestream pointer must reference an opened filestream that is in write mode.
+	This function reads the raw pixel data of P5, P6, and P7 images.
+	Each pixel is a tuple of `depth` samples: 1 for grayscale and 3 for color,
+	plus one more if the last sample is alpha. The data is read a block of rows
+	at a time, and the rows of each block are decoded in parallel.
 	*/
 
-    // open file for writing
-    let out_file = match File::create(output_file_path) {
-        Ok(file) => file,
-        Err(_) => {
-            eprintln!("Error writing to output file.");
-            process::exit(1);
+    let bytes_per_sample = if image.maxc < 256 { 1 } else { 2 };
+    let tuple_bytes = depth * bytes_per_sample;
+    let width = (image.width as usize).max(1);
+    let block_pixels = rows_per_block(width * tuple_bytes) * width;
+
+    // the pixels grow one block at a time as the data is read, so a header that promises more
+    // data than the file has can't make it reserve memory for all of it
+    let count = image.pixel_count()?;
+    image.pixels = Vec::new();
+    let mut alpha = Vec::new();
+    let mut buffer = Vec::new();
+
+    while image.pixels.len() < count {
+        // read the whole block at once
+        let start = image.pixels.len();
+        let length = block_pixels.min(count - start);
+        read_block(reader, &mut buffer, length * tuple_bytes)?;
+        let bytes = &buffer[..];
+
+        // decode each row in parallel, 8-bit and 16-bit samples are read by different functions
+        image.pixels.resize(start + length, Pixel::new());
+        let rows = image.pixels[start..].par_chunks_mut(width).zip(bytes.par_chunks_exact(width * tuple_bytes));
+        match bytes_per_sample {
+            1 => rows.for_each(|(row, data)| decode_row(row, data, depth, sample_8)),
+            _ => rows.for_each(|(row, data)| decode_row(row, data, depth, sample_16)),
+        }
+
+        if has_alpha {
+            alpha.resize(start + length, S::default());
+            let rows = alpha[start..].par_chunks_mut(width).zip(bytes.par_chunks_exact(width * tuple_bytes));
+            match bytes_per_sample {
+                1 => rows.for_each(|(row, data)| decode_alpha_row(row, data, depth, sample_8)),
+                _ => rows.for_each(|(row, data)| decode_alpha_row(row, data, depth, sample_16)),
+            }
+        }
+    }
+
+    if has_alpha {
+        image.alpha = Some(alpha);
+    }
+
+    Ok(())
+}
+
+fn read_raw_bitmap<R: Read, S: IntegerSample>(reader: &mut R, image: &mut PPM<S>) -> Result<(), PpmError> {
+    /*
+	readRawBitmap()
+
+	This function reads the pixel data of P4 images, where each row is packed
+	8 pixels per byte and padded to a whole byte. A set bit is black and a clear
+	bit is white. Blocks of rows are read at once and unpacked in parallel.
+	*/
+
+    let width = (image.width as usize).max(1);
+    let row_bytes = width.div_ceil(8);
+    let block_rows = rows_per_block(row_bytes);
+
+    // the pixels grow one block at a time as the data is read, like read_raw_pixels
+    let count = image.pixel_count()?;
+    image.pixels = Vec::new();
+    let mut buffer = Vec::new();
+
+    while image.pixels.len() < count {
+        // read the whole block at once
+        let start = image.pixels.len();
+        let rows = block_rows.min((count - start) / width);
+        read_block(reader, &mut buffer, rows * row_bytes)?;
+        let bytes = &buffer[..];
+
+        // unpack each row in parallel
+        image.pixels.resize(start + rows * width, Pixel::new());
+        image.pixels[start..].par_chunks_mut(width).zip(bytes.par_chunks_exact(row_bytes)).for_each(|(row, packed)| {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                // the most significant bit is the leftmost pixel
+                let bit = (packed[x / 8] >> (7 - (x % 8))) & 1;
+                let level = S::from_u16(if bit == 1 { 0 } else { 255 });
+                *pixel = Pixel { r: level, g: level, b: level };
+            }
+        });
+    }
+
+    Ok(())
+}
+
+pub fn read_image<R: BufRead>(reader: &mut R) -> Result<Image, PpmError> {
+    /*
+	readImage()
+
+	This function reads a complete Netpbm image from the reader, choosing the type of
+	samples from the max color value in the header. Images with a max color value
+	below 256 use 8-bit samples, and all others use 16-bit samples.
+	*/
+
+    // the header doesn't have any samples yet, so it can be read into either type
+    let mut header: PPM<u8> = PPM::new();
+    parse_header(reader, &mut header)?;
+
+    match header.maxc <= u8::MAX as u32 {
+        true => {
+            read_image_data(reader, &mut header)?;
+            Ok(Image::U8(header))
+        }
+        false => {
+            let mut image = copy_header(&header, header.height);
+            read_image_data(reader, &mut image)?;
+            Ok(Image::U16(image))
+        }
+    }
+}
+
+/**
+Skips any whitespace before the next image of a stream.
+Returns false if the end of the stream was reached instead.
+*/
+pub fn skip_to_next_image<R: BufRead>(reader: &mut R) -> Result<bool, PpmError> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(false);
+        }
+
+        // count the whitespace at the start of the buffer
+        let whitespace = buffer.iter().take_while(|byte| byte.is_ascii_whitespace()).count();
+        let at_image = whitespace < buffer.len();
+        reader.consume(whitespace);
+
+        if at_image {
+            return Ok(true);
+        }
+    }
+}
+
+/*
+The ImageStream type reads successive images from a single stream, such as the
+output of `ffmpeg -f image2pipe -vcodec ppm`. The Netpbm spec allows any number of
+images to be stored back to back, optionally separated by whitespace. Each frame
+is yielded as an Image, and the stream ends at the end of the reader, or after the
+first error.
+*/
+pub struct ImageStream<R: BufRead> {
+    reader: R, // the reader the frames are read from
+    finished: bool, // true once the end of the stream or an error has been reached
+}
+
+impl<R: BufRead> ImageStream<R> {
+    // constructor
+    pub fn new(reader: R) -> Self {
+        ImageStream {
+            reader,
+            finished: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for ImageStream<R> {
+    type Item = Result<Image, PpmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        // stop at the end of the stream
+        match skip_to_next_image(&mut self.reader) {
+            Ok(true) => {}
+            Ok(false) => {
+                self.finished = true;
+                return None;
+            }
+            Err(error) => {
+                self.finished = true;
+                return Some(Err(error));
+            }
+        }
+
+        // read the next frame
+        match read_image(&mut self.reader) {
+            Ok(image) => Some(Ok(image)),
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/**
+Creates an image with the same header information as `image`, and `height` rows of no pixels.
+The copy can have a different type of samples.
+*/
+fn copy_header<S: Sample, T: Sample>(image: &PPM<S>, height: u32) -> PPM<T> {
+    let mut copy = PPM::new();
+    copy.magic = image.magic.clone();
+    copy.tupltype = image.tupltype.clone();
+    copy.maxc = image.maxc;
+    copy.width = image.width;
+    copy.height = height;
+    copy.alpha = image.alpha.as_ref().map(|_| Vec::new());
+    copy
+}
+
+/*
+The RowReader type reads a single image a few rows at a time, instead of all at
+once, so that huge images can be processed in bounded memory. The header is read
+when the RowReader is created, and each call to read_rows returns the next rows as
+an image with the same header information and a smaller height. Iterating over a
+RowReader yields one row at a time. Rows are read with 8-bit samples when the max
+color value is below 256, and 16-bit samples otherwise.
+*/
+pub struct RowReader<R: BufRead> {
+    reader: R, // the reader the rows are read from
+    header: PPM, // the header information of the image, without any pixels
+    rows_read: u32, // the number of rows that have been read so far
+}
+
+impl<R: BufRead> RowReader<R> {
+    // constructor
+    pub fn new(mut reader: R) -> Result<Self, PpmError> {
+        let mut header = PPM::new();
+        parse_header(&mut reader, &mut header)?;
+
+        // the header of a PAM image with an alpha channel has an empty alpha channel
+        if header.magic == "P7" && pam_layout(&header.tupltype).1 {
+            header.alpha = Some(Vec::new());
+        }
+
+        Ok(RowReader {
+            reader,
+            header,
+            rows_read: 0,
+        })
+    }
+
+    /**
+    Returns the header information of the image, which has no pixels.
+    */
+    pub fn header(&self) -> &PPM {
+        &self.header
+    }
+
+    /**
+    Returns the number of rows that haven't been read yet.
+    */
+    pub fn rows_remaining(&self) -> u32 {
+        self.header.height - self.rows_read
+    }
+
+    /**
+    Reads the next `count` rows as an image. Fewer rows are returned at the end of the
+    image, and an image with no rows is returned once every row has been read.
+    */
+    pub fn read_rows(&mut self, count: u32) -> Result<Image, PpmError> {
+        let height = count.min(self.rows_remaining());
+        let rows = match self.header.maxc <= u8::MAX as u32 {
+            true => {
+                let mut rows = copy_header(&self.header, height);
+                read_image_data(&mut self.reader, &mut rows)?;
+                Image::U8(rows)
+            }
+            false => {
+                let mut rows = copy_header(&self.header, height);
+                read_image_data(&mut self.reader, &mut rows)?;
+                Image::U16(rows)
+            }
+        };
+        self.rows_read += height;
+        Ok(rows)
+    }
+}
+
+impl<R: BufRead> Iterator for RowReader<R> {
+    type Item = Result<Image, PpmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rows_remaining() {
+            0 => None,
+            _ => Some(self.read_rows(1)),
+        }
+    }
+}
+
+/*
+The RowWriter type writes a single image a few rows at a time. The header is
+written when the RowWriter is created, using the width, height, and format of the
+given header image, which doesn't need to have any pixels. PAM images are written
+with an alpha channel if the header image has one, even if it is empty. The rows
+passed to write_rows must match the header, and every row has to be written
+before calling finish.
+*/
+pub struct RowWriter<W: Write> {
+    writer: BufWriter<W>, // the buffered writer the rows are written to
+    header: PPM, // the header information of the image, without any pixels
+    rows_written: u32, // the number of rows that have been written so far
+}
+
+impl<W: Write> RowWriter<W> {
+    // constructor
+    pub fn new<S: Sample>(output: W, header: &PPM<S>) -> Result<Self, PpmError> {
+        let mut writer = BufWriter::new(output);
+        write_header(&mut writer, header)?;
+
+        Ok(RowWriter {
+            writer,
+            header: copy_header(header, header.height),
+            rows_written: 0,
+        })
+    }
+
+    /**
+    Writes the rows of an image, which must have the same width and format as the header.
+    */
+    pub fn write_rows<S: IntegerSample>(&mut self, rows: &PPM<S>) -> Result<(), PpmError> {
+        // make sure the rows will be written the way the header says they are
+        if rows.magic != self.header.magic || rows.maxc != self.header.maxc || rows.width != self.header.width {
+            return Err(PpmError::RowMismatch(format!("rows are {} {} pixels wide with max color value {}, but the image is {} {} pixels wide with max color value {}", rows.magic, rows.width, rows.maxc, self.header.magic, self.header.width, self.header.maxc)));
+        }
+        if self.rows_written + rows.height > self.header.height {
+            return Err(PpmError::RowMismatch(format!("more than {} rows were written", self.header.height)));
+        }
+
+        write_pixel_data(&mut self.writer, rows, self.header.alpha.is_some())?;
+        self.rows_written += rows.height;
+
+        Ok(())
+    }
+
+    /**
+    Checks that every row was written, and flushes the rows to the output.
+    */
+    pub fn finish(mut self) -> Result<(), PpmError> {
+        if self.rows_written != self.header.height {
+            return Err(PpmError::RowMismatch(format!("only {} of {} rows were written", self.rows_written, self.header.height)));
+        }
+
+        // make sure everything in the buffer actually reaches the output
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/*
+The MappedImage type gives random access to the pixels of a raw (P5, P6, or P7)
+image file without reading the whole file into memory. The file is memory mapped,
+so the operating system only loads the parts that are used, and each pixel is
+decoded when it is read. This lets operations like rotations, which need a pixel
+from every row to produce a single row of output, work on images that are bigger
+than the available memory.
+*/
+#[cfg(feature = "mmap")]
+pub struct MappedImage {
+    map: Mmap, // the memory mapped file
+    header: PPM, // the header information of the image, without any pixels
+    data_offset: usize, // the byte offset where the pixel data starts
+    depth: usize, // the number of samples in each tuple
+    bytes_per_sample: usize, // 1 for 8-bit samples and 2 for 16-bit samples
+}
+
+#[cfg(feature = "mmap")]
+impl MappedImage {
+    /**
+    Maps the image file at the given path and reads its header.
+    Only raw grayscale, color, and PAM images can be mapped.
+    */
+    pub fn open(path: &Path) -> Result<Self, PpmError> {
+        let file = File::open(path)?;
+
+        // SAFETY: the map is only ever read. If another process changes the file while it
+        // is mapped the pixels read may be wrong, but every read is still bounds checked.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let mut header = PPM::new();
+        let data_offset = parse_header(&mut &map[..], &mut header)? as usize;
+
+        // only raw formats have a fixed number of bytes per pixel
+        let (depth, has_alpha) = match header.magic.as_str() {
+            "P5" => (1, false),
+            "P6" => (3, false),
+            "P7" => pam_layout(&header.tupltype),
+            _ => return Err(PpmError::UnsupportedMagic(header.magic.clone())),
+        };
+        if has_alpha {
+            header.alpha = Some(Vec::new());
+        }
+        let bytes_per_sample = if header.maxc < 256 { 1 } else { 2 };
+
+        // make sure all of the pixel data is in the file
+        let image = MappedImage {
+            map,
+            header,
+            data_offset,
+            depth,
+            bytes_per_sample,
+        };
+        if image.map.len() < image.data_end() {
+            return Err(PpmError::ShortPixelData);
+        }
+
+        Ok(image)
+    }
+
+    /**
+    Returns the header information of the image, which has no pixels.
+    */
+    pub fn header(&self) -> &PPM {
+        &self.header
+    }
+
+    /**
+    Returns true if there is nothing but whitespace after the pixel data, so the
+    file doesn't hold a stream of several images.
+    */
+    pub fn is_single_image(&self) -> bool {
+        self.map[self.data_end()..].iter().all(|byte| byte.is_ascii_whitespace())
+    }
+
+    /**
+    Returns the byte offset where the pixel data ends.
+    */
+    fn data_end(&self) -> usize {
+        self.data_offset + self.header.width as usize * self.header.height as usize * self.depth * self.bytes_per_sample
+    }
+
+    /**
+    Returns the raw bytes of the tuple at the given x and y coordinate.
+    */
+    fn tuple(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if (x >= self.header.width) || (y >= self.header.height) {
+            return None;
+        }
+        let tuple_bytes = self.depth * self.bytes_per_sample;
+        let start = self.data_offset + (y as usize * self.header.width as usize + x as usize) * tuple_bytes;
+        Some(&self.map[start..start + tuple_bytes])
+    }
+
+    /**
+    Decodes the pixel at the given x and y coordinate, into samples that must be
+    able to hold the max color value.
+    */
+    pub fn get_pixel<S: IntegerSample>(&self, x: u32, y: u32) -> Option<Pixel<S>> {
+        let tuple = self.tuple(x, y)?;
+        let mut pixel = Pixel::new();
+        match self.bytes_per_sample {
+            1 => decode_row(std::slice::from_mut(&mut pixel), tuple, self.depth, sample_8),
+            _ => decode_row(std::slice::from_mut(&mut pixel), tuple, self.depth, sample_16),
+        }
+        Some(pixel)
+    }
+
+    /**
+    Decodes the alpha value at the given x and y coordinate.
+    Images without an alpha channel are fully opaque, like in PPM::get_alpha.
+    */
+    pub fn get_alpha<S: IntegerSample>(&self, x: u32, y: u32) -> Option<S> {
+        let tuple = self.tuple(x, y)?;
+        let value = match (self.header.alpha.is_some(), self.bytes_per_sample) {
+            (false, _) => self.header.maxc as u16,
+            (true, 1) => sample_8(tuple, self.depth - 1),
+            (true, _) => sample_16(tuple, self.depth - 1),
+        };
+        Some(S::from_u16(value))
+    }
+}
+
+/**
+Reads `count` ASCII samples from the reader for the plain (P1, P2, P3) formats.
+Samples are separated by whitespace, except in plain bitmaps where every 0 or 1
+character is its own sample. Any sample above maxc is rejected. Nothing after the
+last sample is read, so that another image can follow in the same stream.
+*/
+fn read_plain_samples(reader: &mut impl Read, count: usize, maxc: u32, bitmap: bool) -> Result<Vec<u16>, PpmError> {
+    let mut samples = Vec::new(); // grown as samples are read, since count comes from the header
+    let mut token = Vec::new(); // the digits of the sample being read
+
+    // parse a token as a sample no larger than the max color value
+    let parse_token = |token: &[u8]| -> Result<u16, PpmError> {
+        match std::str::from_utf8(token).ok().and_then(|token| token.parse::<u32>().ok()) {
+            Some(value) if value <= maxc => Ok(value as u16),
+            _ => Err(PpmError::InvalidPixelData(String::from_utf8_lossy(token).to_string())),
         }
     };
 
-    // Create a new filestream writer
-    let mut writer = BufWriter::new(out_file);
+    let mut byte = [0u8; 1];
+    while samples.len() < count {
+        // read the next character, the last sample may end at the end of the file
+        if reader.read(&mut byte)? == 0 {
+            if !token.is_empty() {
+                samples.push(parse_token(&token)?);
+            }
+            break;
+        }
 
-    // Create the new header text
-    let header = format!("{}\n# Modified with David Bootle's PPM Image Tool\n{} {}\n{}\n", image.magic, image.width, image.height, image.maxc);
+        if byte[0].is_ascii_whitespace() {
+            // whitespace ends the current sample
+            if !token.is_empty() {
+                samples.push(parse_token(&token)?);
+                token.clear();
+            }
+        } else if bitmap {
+            // bitmap digits don't need to be separated, so every character is a sample
+            match byte[0] {
+                b'0' => samples.push(0),
+                b'1' => samples.push(1),
+                _ => return Err(PpmError::InvalidPixelData(String::from_utf8_lossy(&byte).to_string())),
+            }
+        } else {
+            token.push(byte[0]);
+        }
+    }
 
-    // Write the header to the file
-    writer.write(header.as_bytes()).expect("Failed to write header to file.");
+    if samples.len() < count {
+        return Err(PpmError::ShortPixelData);
+    }
 
-    // Write the pixel data to the file
-    for pixel in &image.pixels {
-        match image.maxc {
-            255 => {
-                // Write 8 bit color data
-                let mut buffer = [0u8; 3];
-                buffer[0] = pixel.r as u8;
-                buffer[1] = pixel.g as u8;
-                buffer[2] = pixel.b as u8;
-                writer.write(&buffer).expect("Failed to write pixel data to file.");
+    Ok(samples)
+}
+
+/**
+Writes one row of ASCII samples for the plain formats.
+Lines are wrapped so that none is longer than 70 characters, as required by the Netpbm spec.
+*/
+fn write_plain_row(writer: &mut impl Write, samples: &[u16]) -> Result<(), PpmError> {
+    let mut line = String::new();
+    for sample in samples {
+        let text = sample.to_string();
+
+        // start a new line if this sample would make the current one too long
+        if !line.is_empty() && line.len() + 1 + text.len() > 70 {
+            line.push('\n');
+            writer.write_all(line.as_bytes())?;
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&text);
+    }
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+/**
+Returns true if the pixel should be black when written to a bitmap.
+*/
+fn is_black<S: IntegerSample>(pixel: &Pixel<S>, maxc: u32) -> bool {
+    (pixel.average().to_u16() as u32) * 2 < maxc
+}
+
+/**
+Encodes one row of pixels into raw tuples, using `put` to write each sample of a tuple.
+Tuples with a depth of 1 or 2 are grayscale, and hold the average of the pixel.
+The alpha samples are written last in each tuple when given.
+*/
+fn encode_row<S: IntegerSample>(data: &mut [u8], row: &[Pixel<S>], alpha: Option<&[S]>, depth: usize, put: impl Fn(&mut [u8], usize, u16)) {
+    let tuple_bytes = data.len() / row.len();
+    for (x, (tuple, pixel)) in data.chunks_exact_mut(tuple_bytes).zip(row).enumerate() {
+        match depth {
+            1 | 2 => put(tuple, 0, pixel.average().to_u16()),
+            _ => {
+                put(tuple, 0, pixel.r.to_u16());
+                put(tuple, 1, pixel.g.to_u16());
+                put(tuple, 2, pixel.b.to_u16());
             }
+        }
+        if let Some(alpha) = alpha {
+            put(tuple, depth - 1, alpha[x].to_u16());
+        }
+    }
+}
+
+/**
+Writes sample `index` of an 8-bit tuple.
+*/
+fn put_8(tuple: &mut [u8], index: usize, value: u16) {
+    tuple[index] = value as u8;
+}
+
+/**
+Writes sample `index` of a 16-bit tuple, which is stored big endian.
+*/
+fn put_16(tuple: &mut [u8], index: usize, value: u16) {
+    tuple[index * 2..index * 2 + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn write_raw_pixels<W: Write, S: IntegerSample>(writer: &mut W, image: &PPM<S>, depth: usize, alpha: Option<&[S]>) -> Result<(), PpmError> {
+    /*
+	writeRawPixels()
+
+	This function writes the raw pixel data of P5, P6, and P7 images.
+	Each pixel is written as a tuple of `depth` samples. Depths of 1 and 2 write the
+	average of the pixel, depths of 3 and 4 write the color, and the alpha samples are
+	written last when given. The rows of each block are encoded in parallel into a
+	buffer, which is then written at once.
+	*/
 
-            65535 => {
-                // Write 16 bit color data
-                let mut buffer = [0u8; 6];
-                buffer[0..2].copy_from_slice(&pixel.r.to_be_bytes());
-                buffer[2..4].copy_from_slice(&pixel.g.to_be_bytes());
-                buffer[4..6].copy_from_slice(&pixel.b.to_be_bytes());
-                writer.write(&buffer).expect("Failed to write pixel data to file.");
+    let bytes_per_sample = if image.maxc < 256 { 1 } else { 2 };
+    let tuple_bytes = depth * bytes_per_sample;
+    let width = (image.width as usize).max(1);
+    let block_pixels = rows_per_block(width * tuple_bytes) * width;
+    let mut buffer = vec![0u8; block_pixels.min(image.pixels.len()) * tuple_bytes];
+
+    for (block_index, pixels) in image.pixels.chunks(block_pixels).enumerate() {
+        let bytes = &mut buffer[..pixels.len() * tuple_bytes];
+
+        // encode each row in parallel, 8-bit and 16-bit samples are written by different functions
+        let start = block_index * block_pixels;
+        let alpha_row = |y: usize| alpha.map(|alpha| &alpha[start + y * width..start + (y + 1) * width]);
+        let rows = bytes.par_chunks_exact_mut(width * tuple_bytes).zip(pixels.par_chunks(width)).enumerate();
+        match bytes_per_sample {
+            1 => rows.for_each(|(y, (data, row))| encode_row(data, row, alpha_row(y), depth, put_8)),
+            _ => rows.for_each(|(y, (data, row))| encode_row(data, row, alpha_row(y), depth, put_16)),
+        }
+
+        writer.write_all(bytes)?;
+    }
+
+    Ok(())
+}
+
+fn write_raw_bitmap<W: Write, S: IntegerSample>(writer: &mut W, image: &PPM<S>) -> Result<(), PpmError> {
+    /*
+	writeRawBitmap()
+
+	This function writes the pixel data of P4 images. Each row is packed 8 pixels
+	per byte, with the leftmost pixel in the most significant bit, and padded to a
+	whole byte. Each block of rows is packed in parallel and then written at once.
+	*/
+
+    let width = (image.width as usize).max(1);
+    let row_bytes = width.div_ceil(8);
+    let block_rows = rows_per_block(row_bytes);
+    let mut buffer = vec![0u8; block_rows.min(image.height as usize) * row_bytes];
+
+    for pixels in image.pixels.chunks(block_rows * width) {
+        let bytes = &mut buffer[..(pixels.len() / width) * row_bytes];
+
+        // pack each row in parallel
+        bytes.par_chunks_exact_mut(row_bytes).zip(pixels.par_chunks(width)).for_each(|(packed, row)| {
+            packed.fill(0);
+            for (x, pixel) in row.iter().enumerate() {
+                if is_black(pixel, image.maxc) {
+                    packed[x / 8] |= 1 << (7 - (x % 8));
+                }
             }
+        });
+
+        writer.write_all(bytes)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_image<W: Write, S: IntegerSample>(output: &mut W, image: &PPM<S>) -> Result<(), PpmError> {
+    /*
+	writeImage()
+
+	This function takes a writer and a PPM object as arguments.
+	It writes a new Netpbm file to the writer using the data in the PPM object.
+	The subtype (P1 to P6) is chosen by the magic number of the PPM object. Color pixels
+	are averaged when written as grayscale, and thresholded at half of the max color value
+	when written as a bitmap. The alpha channel is only written to PAM (P7) images,
+	and is dropped for every other subtype.
+	The writer can be anything, such as a file or stdout, and is buffered internally.
+	*/
+
+    // Create a new buffered writer
+    let mut writer = BufWriter::new(output);
+
+    // Write the header, then the pixel data
+    write_header(&mut writer, image)?;
+    write_pixel_data(&mut writer, image, image.alpha.is_some())?;
+
+    // make sure everything in the buffer actually reaches the output
+    writer.flush()?;
+
+    Ok(())
+}
+
+/**
+Writes the header of a Netpbm file for the image. PAM images get an alpha channel
+in their tuple type if the image has one, even if it has no samples yet.
+*/
+fn write_header<W: Write, S: Sample>(writer: &mut W, image: &PPM<S>) -> Result<(), PpmError> {
+    // verify that the format is supported before writing anything
+    if !is_supported_magic(&image.magic) {
+        return Err(PpmError::UnsupportedMagic(image.magic.clone()));
+    }
+
+    // verify that the bit depth is supported before writing anything
+    if !is_supported_maxc(image.maxc) {
+        return Err(PpmError::UnsupportedMaxColor(image.maxc));
+    }
+
+    // the PAM tuple type only depends on whether the image is grayscale and has alpha
+    let pam_tupltype = pam_tupltype(image, image.alpha.is_some());
+    let (pam_depth, _) = pam_layout(pam_tupltype);
+
+    // Create the new header text, bitmaps don't have a max color value and PAM images have their own header format
+    let header = match image.magic.as_str() {
+        "P1" | "P4" => format!("{}\n# Modified with David Bootle's PPM Image Tool\n{} {}\n", image.magic, image.width, image.height),
+        "P7" => format!("P7\n# Modified with David Bootle's PPM Image Tool\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n", image.width, image.height, pam_depth, image.maxc, pam_tupltype),
+        _ => format!("{}\n# Modified with David Bootle's PPM Image Tool\n{} {}\n{}\n", image.magic, image.width, image.height, image.maxc),
+    };
+
+    // Write the header to the file
+    writer.write_all(header.as_bytes())?;
+
+    Ok(())
+}
+
+/**
+Returns the PAM tuple type to write for an image, which only depends on whether
+the image is grayscale and whether the alpha channel is written.
+*/
+fn pam_tupltype<S: Sample>(image: &PPM<S>, has_alpha: bool) -> &'static str {
+    match (image.tupltype.starts_with("GRAYSCALE"), has_alpha) {
+        (true, false) => "GRAYSCALE",
+        (true, true) => "GRAYSCALE_ALPHA",
+        (false, false) => "RGB",
+        (false, true) => "RGB_ALPHA",
+    }
+}
+
+/**
+Writes the pixel data of the image, in the format given by its magic number.
+When `has_alpha` is true, PAM images are written with an alpha channel, which is
+fully opaque if the image doesn't have one.
+*/
+fn write_pixel_data<W: Write, S: IntegerSample>(writer: &mut W, image: &PPM<S>, has_alpha: bool) -> Result<(), PpmError> {
+    // Write the pixel data to the file, plain formats one row at a time and raw formats one block at a time
+    match image.magic.as_str() {
+        "P1" | "P2" | "P3" => {
+            for row in image.pixels.chunks(image.width.max(1) as usize) {
+                let samples: Vec<u16> = match image.magic.as_str() {
+                    // Plain bitmap: 1 is black and 0 is white
+                    "P1" => row.iter().map(|pixel| is_black(pixel, image.maxc) as u16).collect(),
+                    // Plain graymap
+                    "P2" => row.iter().map(|pixel| pixel.average().to_u16()).collect(),
+                    // Plain pixmap
+                    _ => row.iter().flat_map(|pixel| [pixel.r.to_u16(), pixel.g.to_u16(), pixel.b.to_u16()]).collect(),
+                };
+                write_plain_row(writer, &samples)?;
+            }
+        }
+
+        "P4" => write_raw_bitmap(writer, image)?,
 
+        "P5" => write_raw_pixels(writer, image, 1, None)?,
+
+        "P7" => {
+            // PAM: grayscale or color samples, followed by alpha if the image has it
+            let (pam_depth, _) = pam_layout(pam_tupltype(image, has_alpha));
+            let opaque;
+            let alpha = match (has_alpha, &image.alpha) {
+                (false, _) => None,
+                (true, Some(alpha)) => Some(alpha.as_slice()),
+                (true, None) => {
+                    opaque = vec![S::from_u16(image.maxc as u16); image.pixels.len()];
+                    Some(opaque.as_slice())
+                }
+            };
+            write_raw_pixels(writer, image, pam_depth, alpha)?;
+        }
+
+        // Raw pixmap (P6)
+        _ => write_raw_pixels(writer, image, 3, None)?,
+    }
+
+    Ok(())
+}
+
+pub fn read_pfm<R: BufRead>(reader: &mut R) -> Result<PPM<f32>, PpmError> {
+    /*
+	readPfm()
+
+	This function reads a complete PFM (Portable Float Map) image from the reader.
+	The header is "PF" for color or "Pf" for grayscale, followed by the width,
+	height, and scale. A negative scale means the samples are little endian, and a
+	positive scale means they are big endian. Rows are stored from bottom to top,
+	so they are flipped while reading to match the other formats.
+	*/
+
+    let mut image: PPM<f32> = PPM::new();
+    let mut tokenizer = HeaderTokenizer::new(reader);
+
+    // Block 1: Magic Number
+    let mut magic = [0u8; 2];
+    for byte in magic.iter_mut() {
+        *byte = match tokenizer.next_byte()? {
+            Some(byte) => byte,
+            None => return Err(PpmError::TruncatedHeader),
+        };
+    }
+    image.magic = String::from_utf8_lossy(&magic).to_string();
+    let channels = match image.magic.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(PpmError::UnsupportedMagic(image.magic)),
+    };
+
+    // Block 2: Width
+    let block = tokenizer.next_token()?;
+    image.width = match block.parse::<u32>() {
+        Ok(num) => num,
+        Err(_) => return Err(PpmError::InvalidWidth(block)),
+    };
+
+    // Block 3: Height
+    let block = tokenizer.next_token()?;
+    image.height = match block.parse::<u32>() {
+        Ok(num) => num,
+        Err(_) => return Err(PpmError::InvalidHeight(block)),
+    };
+
+    // make sure the pixels of the image can be counted
+    let count = image.pixel_count()?;
+
+    // Block 4: Scale, where the sign gives the byte order
+    let block = tokenizer.next_token()?;
+    let little_endian = match block.parse::<f32>() {
+        Ok(scale) if scale.is_finite() && scale != 0.0 => scale < 0.0,
+        _ => return Err(PpmError::InvalidScale(block)),
+    };
+
+    image.maxc = 1;
+    image.tupltype = String::from(if channels == 3 { "RGB" } else { "GRAYSCALE" });
+
+    // decode one sample
+    let sample = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match little_endian {
+            true => f32::from_le_bytes(bytes),
+            false => f32::from_be_bytes(bytes),
+        }
+    };
+
+    // read each row, and add its pixels once the whole row has been read, so the pixels grow
+    // with the data instead of reserving the size given in the header
+    let width = image.width as usize;
+    let mut row = Vec::new();
+    let mut pixels = Vec::new();
+    while pixels.len() < count {
+        read_block(reader, &mut row, width * channels * 4)?;
+
+        // grayscale images set every channel to the same level
+        pixels.extend(row.chunks_exact(channels * 4).map(|tuple| match channels {
+            3 => Pixel { r: sample(&tuple[0..4]), g: sample(&tuple[4..8]), b: sample(&tuple[8..12]) },
             _ => {
-                // Invalid bit depth
-                eprintln!("Cannot write pixel data for image with max color value of {}.", image.maxc);
-                process::exit(1);
+                let level = sample(tuple);
+                Pixel { r: level, g: level, b: level }
+            }
+        }));
+    }
+
+    // the rows are stored from the bottom of the image to the top
+    image.pixels = pixels.chunks(width.max(1)).rev().flatten().copied().collect();
+
+    Ok(image)
+}
+
+pub fn write_pfm<W: Write>(output: &mut W, image: &PPM<f32>) -> Result<(), PpmError> {
+    /*
+	writePfm()
+
+	This function writes a PFM image to the writer using the data in the PPM object.
+	Images with the "Pf" magic number are written as grayscale by averaging the
+	channels, and everything else is written in color. Samples are always written
+	little endian (with a scale of -1.0), from the bottom row to the top.
+	PFM headers can't contain comments, so no comment is added.
+	*/
+
+    let grayscale = image.magic == "Pf";
+
+    // Create a new buffered writer
+    let mut writer = BufWriter::new(output);
+
+    // Write the header to the file
+    let header = format!("{}\n{} {}\n-1.0\n", if grayscale { "Pf" } else { "PF" }, image.width, image.height);
+    writer.write_all(header.as_bytes())?;
+
+    // Write the pixel data to the file, one row at a time from the bottom
+    for row in image.pixels.chunks(image.width.max(1) as usize).rev() {
+        let mut buffer = Vec::with_capacity(row.len() * 12);
+        for pixel in row {
+            match grayscale {
+                true => buffer.extend_from_slice(&pixel.average().to_le_bytes()),
+                false => {
+                    buffer.extend_from_slice(&pixel.r.to_le_bytes());
+                    buffer.extend_from_slice(&pixel.g.to_le_bytes());
+                    buffer.extend_from_slice(&pixel.b.to_le_bytes());
+                }
             }
         }
+        writer.write_all(&buffer)?;
     }
-}
\ No newline at end of file
+
+    // make sure everything in the buffer actually reaches the output
+    writer.flush()?;
+
+    Ok(())
+}
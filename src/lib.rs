@@ -0,0 +1,19 @@
+/*
+David Bootle
+PPM Parser (Rust)
+
+Library interface for the PPM image tool. The binary in main.rs is a thin
+command line wrapper around the modules exposed here.
+*/
+
+pub mod error;
+pub mod ppm;
+pub mod color;
+pub mod lut;
+pub mod imageio;
+pub mod imageactions;
+pub mod morphology;
+pub mod threshold;
+pub mod histogram;
+
+pub use error::PpmError;
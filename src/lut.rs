@@ -0,0 +1,280 @@
+/*
+Contains color lookup tables (LUTs), which map every color to a new one for color grading.
+
+LUTs are read from the .cube files made by Adobe and Resolve, or from a HALD image, which is
+an identity image with every color in order that has been graded in another program.
+*/
+
+use std::io::BufRead;
+
+use crate::error::PpmError;
+use crate::ppm::{PPM, Pixel, Sample};
+
+/*
+The LutTable type is a single 1D or 3D table of a LUT. A 1D table has `size` entries,
+one for each level, with a separate curve for each channel. A 3D table has `size`
+entries along each axis of the color cube, stored with red changing fastest, then
+green, then blue, which is the order of .cube files and HALD images.
+*/
+#[derive(Clone, Debug)]
+pub struct LutTable {
+    pub size: usize, // the number of entries along each axis
+    pub domain_min: [f32; 3], // the input levels of the first entry on each axis
+    pub domain_max: [f32; 3], // the input levels of the last entry on each axis
+    pub entries: Vec<[f32; 3]>, // the output rgb values
+}
+
+impl LutTable {
+    /**
+    Returns the position of each channel of an rgb value along the axes of the table,
+    from 0.0 to size - 1.
+    */
+    fn position(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        [0, 1, 2].map(|channel| {
+            let range = self.domain_max[channel] - self.domain_min[channel];
+            ((rgb[channel] - self.domain_min[channel]) / range * last).clamp(0.0, last)
+        })
+    }
+
+    /**
+    Looks up an rgb value in a 1D table, interpolating between the two nearest levels of each channel.
+    */
+    fn apply_1d(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let position = self.position(rgb);
+        [0, 1, 2].map(|channel| {
+            let low = (position[channel] as usize).min(self.size - 2);
+            let fraction = position[channel] - low as f32;
+            self.entries[low][channel] * (1.0 - fraction) + self.entries[low + 1][channel] * fraction
+        })
+    }
+
+    /**
+    Looks up an rgb value in a 3D table, interpolating between the corners of the cube it falls in.
+    */
+    fn apply_3d(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let position = self.position(rgb);
+        let low = position.map(|position| (position as usize).min(self.size - 2));
+        let [fr, fg, fb] = [0, 1, 2].map(|axis| position[axis] - low[axis] as f32);
+
+        // returns the entry at a corner of the cube, given as an offset of 0 or 1 on each axis
+        let corner = |r: usize, g: usize, b: usize| self.entries[(low[0] + r) + (low[1] + g) * self.size + (low[2] + b) * self.size * self.size];
+
+        // adds the corners together with their weights
+        let mix = |corners: &[([f32; 3], f32)]| {
+            corners.iter().fold([0.0; 3], |sum, (entry, weight)| [0, 1, 2].map(|channel| sum[channel] + entry[channel] * weight))
+        };
+
+        match interpolation {
+            LutInterpolation::Trilinear => mix(&[
+                (corner(0, 0, 0), (1.0 - fr) * (1.0 - fg) * (1.0 - fb)),
+                (corner(1, 0, 0), fr * (1.0 - fg) * (1.0 - fb)),
+                (corner(0, 1, 0), (1.0 - fr) * fg * (1.0 - fb)),
+                (corner(1, 1, 0), fr * fg * (1.0 - fb)),
+                (corner(0, 0, 1), (1.0 - fr) * (1.0 - fg) * fb),
+                (corner(1, 0, 1), fr * (1.0 - fg) * fb),
+                (corner(0, 1, 1), (1.0 - fr) * fg * fb),
+                (corner(1, 1, 1), fr * fg * fb),
+            ]),
+
+            // the cube is split into 6 tetrahedra along its gray diagonal, and only the 4 corners
+            // of the one the color falls in are used, picked by the order of the fractions
+            LutInterpolation::Tetrahedral => {
+                let (first, second) = if fr > fg {
+                    if fg > fb {
+                        ((corner(1, 0, 0), fr - fg), (corner(1, 1, 0), fg - fb))
+                    } else if fr > fb {
+                        ((corner(1, 0, 0), fr - fb), (corner(1, 0, 1), fb - fg))
+                    } else {
+                        ((corner(0, 0, 1), fb - fr), (corner(1, 0, 1), fr - fg))
+                    }
+                } else if fb > fg {
+                    ((corner(0, 0, 1), fb - fg), (corner(0, 1, 1), fg - fr))
+                } else if fb > fr {
+                    ((corner(0, 1, 0), fg - fb), (corner(0, 1, 1), fb - fr))
+                } else {
+                    ((corner(0, 1, 0), fg - fr), (corner(1, 1, 0), fr - fb))
+                };
+                let largest = fr.max(fg).max(fb);
+                let smallest = fr.min(fg).min(fb);
+                mix(&[(corner(0, 0, 0), 1.0 - largest), first, second, (corner(1, 1, 1), smallest)])
+            }
+        }
+    }
+}
+
+/*
+The LutInterpolation type picks how colors between the entries of a 3D table are found.
+Tetrahedral is closer to what the LUT was made from, especially along the grays.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LutInterpolation {
+    Trilinear, // blend the 8 corners of the cube around the color
+    Tetrahedral, // blend the 4 corners of the tetrahedron around the color
+}
+
+/*
+The Lut type is a color lookup table. It may have a 1D shaper table that is applied first,
+a 3D table, or both, like the .cube files from Resolve.
+*/
+#[derive(Clone, Debug)]
+pub struct Lut {
+    pub title: String, // the title from the file, or empty if it didn't have one
+    pub shaper: Option<LutTable>, // the 1D table
+    pub cube: Option<LutTable>, // the 3D table
+}
+
+impl Lut {
+    /**
+    Reads a LUT from a .cube file.
+    */
+    pub fn from_cube<R: BufRead>(reader: R) -> Result<Lut, PpmError> {
+        /*
+        fromCube()
+
+        This function reads a .cube file, which is a list of keyword lines followed by one line
+        of three numbers for each entry. LUT_1D_SIZE and LUT_3D_SIZE give the size of the tables,
+        and the entries of the 1D table come first when there are both. DOMAIN_MIN and DOMAIN_MAX
+        give the input range of every channel, and LUT_1D_INPUT_RANGE and LUT_3D_INPUT_RANGE give
+        one range for a single table. Comments start with '#', and unknown keywords are skipped.
+        */
+
+        let mut title = String::new();
+        let (mut size_1d, mut size_3d) = (None, None);
+        let (mut domain_min, mut domain_max) = ([0.0; 3], [1.0; 3]);
+        let (mut range_1d, mut range_3d) = (None, None);
+        let mut entries = Vec::new();
+
+        let invalid = |line: &str| PpmError::InvalidLut(format!("the line '{}' could not be parsed", line));
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            let numbers = |count: usize| -> Result<Vec<f32>, PpmError> {
+                let numbers = line.split_whitespace().skip(1).map(|number| number.parse::<f32>().ok().filter(|number| number.is_finite())).collect::<Option<Vec<f32>>>();
+                numbers.filter(|numbers| numbers.len() == count).ok_or_else(|| invalid(line))
+            };
+
+            match keyword {
+                "TITLE" => title = line["TITLE".len()..].trim().trim_matches('"').to_string(),
+                "LUT_1D_SIZE" => size_1d = Some(numbers(1)?[0]),
+                "LUT_3D_SIZE" => size_3d = Some(numbers(1)?[0]),
+                "DOMAIN_MIN" => domain_min = numbers(3)?.try_into().unwrap(),
+                "DOMAIN_MAX" => domain_max = numbers(3)?.try_into().unwrap(),
+                "LUT_1D_INPUT_RANGE" => range_1d = Some(numbers(2)?),
+                "LUT_3D_INPUT_RANGE" => range_3d = Some(numbers(2)?),
+
+                // lines that start with a number are entries
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.') => {
+                    let entry = line.split_whitespace().map(|number| number.parse::<f32>().ok()).collect::<Option<Vec<f32>>>();
+                    match entry.as_deref() {
+                        Some(&[r, g, b]) => entries.push([r, g, b]),
+                        _ => return Err(invalid(line)),
+                    }
+                }
+
+                // any other keywords don't change the colors
+                _ => {}
+            }
+        }
+
+        // the sizes must be whole numbers, with at least 2 entries along each axis
+        let size = |size: Option<f32>, largest: f32, name: &str| -> Result<Option<usize>, PpmError> {
+            match size {
+                None => Ok(None),
+                Some(size) if size.fract() == 0.0 && (2.0..=largest).contains(&size) => Ok(Some(size as usize)),
+                Some(size) => Err(PpmError::InvalidLut(format!("{} {} must be a whole number from 2 to {}", name, size, largest))),
+            }
+        };
+        let size_1d = size(size_1d, 65536.0, "LUT_1D_SIZE")?;
+        let size_3d = size(size_3d, 256.0, "LUT_3D_SIZE")?;
+
+        let expected = size_1d.unwrap_or(0) + size_3d.map_or(0, |size| size * size * size);
+        if expected == 0 {
+            return Err(PpmError::InvalidLut(String::from("there is no LUT_1D_SIZE or LUT_3D_SIZE")));
+        }
+        if entries.len() != expected {
+            return Err(PpmError::InvalidLut(format!("expected {} entries, but found {}", expected, entries.len())));
+        }
+
+        // a table's own input range replaces the domain
+        let domain = |range: Option<Vec<f32>>| match range {
+            Some(range) => ([range[0]; 3], [range[1]; 3]),
+            None => (domain_min, domain_max),
+        };
+        let (min_1d, max_1d) = domain(range_1d);
+        let (min_3d, max_3d) = domain(range_3d);
+        if (0..3).any(|channel| min_1d[channel] >= max_1d[channel] || min_3d[channel] >= max_3d[channel]) {
+            return Err(PpmError::InvalidLut(String::from("the domain minimum must be below the maximum")));
+        }
+
+        let cube_entries = entries.split_off(size_1d.unwrap_or(0));
+        Ok(Lut {
+            title,
+            shaper: size_1d.map(|size| LutTable { size, domain_min: min_1d, domain_max: max_1d, entries }),
+            cube: size_3d.map(|size| LutTable { size, domain_min: min_3d, domain_max: max_3d, entries: cube_entries }),
+        })
+    }
+
+    /**
+    Creates a 3D LUT from a HALD image, which is an identity image made by hald_identity
+    that has been graded in another program. The image must be square, with a side that
+    is the cube of the HALD level, such as 512x512 for level 8.
+    */
+    pub fn from_hald<S: Sample>(image: &PPM<S>) -> Result<Lut, PpmError> {
+        let level = (image.width as f64).cbrt().round() as u32;
+        if image.width != image.height || level < 2 || level.pow(3) != image.width {
+            return Err(PpmError::InvalidLut(format!("a HALD image must be square with a side that is a cube, such as 512x512, not {}x{}", image.width, image.height)));
+        }
+
+        // the pixels are already in the same order as the table
+        let entries = image.pixels.iter().map(|pixel| pixel.to_rgb(image.maxc)).collect();
+        Ok(Lut {
+            title: String::new(),
+            shaper: None,
+            cube: Some(LutTable { size: (level * level) as usize, domain_min: [0.0; 3], domain_max: [1.0; 3], entries }),
+        })
+    }
+
+    /**
+    Looks up the new color for rgb values from 0.0 to 1.0.
+    */
+    pub fn apply(&self, rgb: [f32; 3], interpolation: LutInterpolation) -> [f32; 3] {
+        let rgb = match &self.shaper {
+            Some(shaper) => shaper.apply_1d(rgb),
+            None => rgb,
+        };
+        match &self.cube {
+            Some(cube) => cube.apply_3d(rgb, interpolation),
+            None => rgb,
+        }
+    }
+}
+
+/**
+Creates the identity HALD image for a HALD level from 2 to 16, which holds every color of a
+level² sized LUT. The image is level³ pixels square, such as 512x512 for level 8. Grading it
+in another program and reading it back with Lut::from_hald gives a LUT of that grade.
+*/
+pub fn hald_identity<S: Sample>(level: u32, maxc: u32) -> PPM<S> {
+    let size = level * level;
+    let mut image = PPM::new();
+    image.magic = String::from("P6");
+    image.maxc = maxc;
+    image.width = size * level;
+    image.height = size * level;
+
+    // each pixel is the next color of the cube, with red changing fastest
+    let last = (size - 1) as f32;
+    image.pixels = (0..size * size * size).map(|index| {
+        let rgb = [index % size, index / size % size, index / (size * size)].map(|level| level as f32 / last);
+        Pixel::from_rgb(rgb, maxc)
+    }).collect();
+
+    image
+}
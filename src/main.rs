@@ -6,46 +6,1895 @@ PPM Parser (Rust)
 // standard imports
 use std::env;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
-use std::io::{BufReader};
+use std::io::{self, BufRead, BufReader, Write};
 use std::time::Instant;
 
 // custom
-mod ppm;
-mod imageio;
-mod imageactions;
+use ppm_parser::imageactions::{self, Anchor, Edge, Filter, GradientOperator, Interpolation, Kernel, Matrix, MAX_RADIUS};
+use ppm_parser::ppm::{PPM, Pixel, Channel, Curve, GrayscaleMode, Image, IntegerSample, Levels, Sample};
+use ppm_parser::PpmError;
+use ppm_parser::lut::{self, Lut, LutInterpolation};
+use ppm_parser::morphology::{self, StructuringElement};
+use ppm_parser::threshold::{self, ThresholdMethod};
+use ppm_parser::histogram::{self, Histogram};
+use ppm_parser::imageio::{is_supported_magic, is_supported_maxc, parse_header, read_image, read_pfm, write_image, write_pfm, skip_to_next_image, ImageStream, RowReader, RowWriter};
+#[cfg(feature = "mmap")]
+use ppm_parser::imageio::MappedImage;
 
-use ppm::{PPM, Channel};
-use imageio::{parse_header, read_image_data, write_image};
+/**
+Replaces the image with the result of a function that takes and returns an image,
+whichever type of samples it has. Functions that need more arguments can be given
+as a closure-like expression, such as `|inner| resize(inner, 10, 10, filter)`.
+*/
+macro_rules! transform {
+    ($image:ident, $function:path) => {
+        transform!($image, |inner| $function(inner))
+    };
+    ($image:ident, |$inner:ident| $body:expr) => {
+        $image = match $image {
+            Image::U8($inner) => Image::U8($body),
+            Image::U16($inner) => Image::U16($body),
+            Image::F32($inner) => Image::F32($body),
+        }
+    };
+}
+
+/**
+Calls a method that modifies the image in place, whichever type of samples it has.
+*/
+macro_rules! modify {
+    ($image:ident, $method:ident $(, $arg:expr)*) => {
+        match &mut $image {
+            Image::U8(inner) => inner.$method($($arg),*),
+            Image::U16(inner) => inner.$method($($arg),*),
+            Image::F32(inner) => inner.$method($($arg),*),
+        }
+    };
+}
+
+/**
+Returns true if the output path means the image should be written to stdout.
+*/
+fn write_to_stdout(output_file_path: &Path) -> bool {
+    output_file_path.as_os_str() == "-"
+}
+
+fn print_help_text() {
+    let executable_name = env::args().next().unwrap();
+
+    println!("PPM IMAGE TOOL\n");
+    println!("SYNTAX: {} <file> [options]\n", executable_name);
+    println!("Use '-' as the file to read the image from stdin. Images read from stdin are written to stdout unless -o is used.\n");
+    println!("If run with no options, the tool will output the width and height of the image.\n");
+
+    println!("-h, --help\t\tPrint this help text.");
+    println!("-c, --copy\t\tCreate an exact copy of the image.");
+    println!("-o [file_path], --output [file_path]\t\tSpecify where to save the image. Use '-' to write to stdout.");
+    println!("-f [format], --format [format]\t\tSpecify the Netpbm subtype (P1 to P7, PF or Pf) to save the image as. Defaults to the input subtype.");
+    println!("-t --time\t\tPrint the time it took to run the program.");
+    println!("-m [maxc], --max-color [maxc]\t\tRescale the image to a new max color value (1 to 65535), such as 255 for 8-bit or 65535 for 16-bit.");
+    println!("\nImage Effects");
+    println!("-n, --negative\t\tConvert the image to a negative.");
+    println!("-g, --grayscale\t\tConvert the image to grayscale by averaging the channels.");
+    println!("--grayscale=[mode]\t\tConvert the image to grayscale with a mode: average, 601 or 709 (luma), luminance (linear light), lightness, red, green or blue.");
+    println!("--single-channel\t\tSave grayscale images with one sample per pixel, as P2, P5, Pf or a GRAYSCALE PAM.");
+    println!("--hue [degrees]\t\tRotate the hue of every color around the color wheel, such as +30 or -120.");
+    println!("--saturation [factor]\t\tMultiply the saturation of every color, such as 1.2 for stronger colors or 0 for gray.");
+    println!("--lightness [amount]\t\tAdd to the lightness of every color, from -100 for black to +100 for white.");
+    println!("--brightness [percent]\t\tBrighten the image by a percentage of white, such as +10 or -10.");
+    println!("--contrast [factor]\t\tMultiply the contrast of the image, such as 1.5 for more or 0.5 for less.");
+    println!("--gamma [gamma]\t\tApply gamma correction, where above 1 brightens the middle levels and below 1 darkens them.");
+    println!("--levels [levels]\t\tStretch the levels from black to white, given as 'black,white', 'black,white,gamma' or 'black,white,gamma,output_black,output_white', from 0 to 255.");
+    println!("--auto-levels [percent]\t\tStretch each channel to fill black to white, ignoring a percentage of the darkest and brightest samples, such as 0.5.");
+    println!("--auto-contrast [percent]\t\tStretch all of the channels together to fill black to white, keeping the colors, and ignoring a percentage like --auto-levels.");
+    println!("--curves [points]\t\tApply a smooth tone curve through input:output points from 0 to 255, such as 0:0,64:48,192:208,255:255.");
+    println!("--lut [file]\t\tGrade the colors with a 1D or 3D .cube LUT, or a graded HALD image.");
+    println!("--lut-interpolation [mode]\t\tHow --lut finds colors between the entries of a 3D LUT: trilinear (default) or tetrahedral.");
+    println!("--hald [level]\t\tReplace the image with the identity HALD image of a level from 2 to 16, such as 8 for 512x512, to grade in another program and use with --lut.");
+    println!("-rl, --rotate-left\t\tRotate the image 90 degrees counter-clockwise.");
+    println!("-rr, --rotate-right\t\tRotate the image 90 degrees clockwise.");
+    println!("-r180, --rotate-180\t\tRotate the image 180 degrees.");
+    println!("-ra [degrees], --rotate [degrees]\t\tRotate the image clockwise by any angle, such as 1.7 or -90. The corners are filled with the fill color.");
+    println!("--interpolation [mode]\t\tHow --rotate, --affine and --perspective find the color between pixels: nearest, bilinear (default) or bicubic.");
+    println!("--expand\t\tGrow the canvas to fit the whole image when using --rotate, instead of keeping its size.");
+    println!("-tp, --transpose\t\tFlip the image over the diagonal from the top left corner, swapping rows and columns.");
+    println!("-tv, --transverse\t\tFlip the image over the diagonal from the top right corner.");
+    println!("-af [matrix], --affine [matrix]\t\tWarp the image with an affine matrix a,b,c,d,e,f that moves each point (x, y) to (ax+by+c, dx+ey+f), such as 1,0.3,0,0,1,0 for a shear.");
+    println!("-ps [corners], --perspective [corners]\t\tCorrect the perspective by moving the corners x1,y1,...,x4,y4 (top left, top right, bottom right, bottom left) to the corners of the image. Give 8 more numbers to move them to those points instead.");
+    println!("--warp-size [size]\t\tThe WxH size of the image made by --affine and --perspective. Defaults to the input size, or the size of the corners for --perspective.");
+    println!("-s, --shrink\t\tShrink the image by 2x.");
+    println!("-d, --double\t\tDouble the size of the image by turning each pixel into a 2x2 square.");
+    println!("-db --double-bilinear\t\tDouble the size of the image using bilinear interpolation.");
+    println!("-rs [size], --resize [size]\t\tResize the image to WxH pixels. Use Wx or xH to set one side and keep the aspect ratio.");
+    println!("-sc [scale], --scale [scale]\t\tScale the image by a percentage such as 37%, or a factor such as 0.37.");
+    println!("--filter [filter]\t\tThe filter used to resize the image: nearest, bilinear, bicubic (default), lanczos3 or box.");
+    println!("--aspect [mode]\t\tHow WxH sizes treat the aspect ratio: stretch to exactly WxH (default), fit inside WxH, or fill WxH.");
+    println!("-cr [region], --crop [region]\t\tCrop the image to a WxH+X+Y region, such as 640x480+10+20.");
+    println!("-pd [sizes], --pad [sizes]\t\tPad the sides of the image, given as 'all', 'vertical,horizontal', or 'top,right,bottom,left' in pixels.");
+    println!("-bd [sizes], --border [sizes]\t\tAdd a border of the fill color, with sizes given like --pad.");
+    println!("-cv [size], --canvas [size]\t\tChange the canvas to WxH pixels without resizing the image, which is placed at the anchor.");
+    println!("--edge [mode]\t\tHow --pad and --canvas fill new pixels, and which pixels filters use past the edges: constant (the fill color, default for --pad), replicate or clamp (default for filters), reflect, wrap or zero.");
+    println!("--fill [color]\t\tThe fill color as R,G,B or a gray level, from 0 to 255 whatever the max color value. Defaults to black.");
+    println!("--anchor [position]\t\tWhere --canvas places the image: center (default), top-left, top, top-right, left, right, bottom-left, bottom or bottom-right.");
+    println!("--blur [radius]\t\tBlur the image by averaging the pixels up to a radius away, such as 2.");
+    println!("--gaussian [sigma]\t\tBlur the image with a gaussian, where sigma is its size in pixels, such as 1.5.");
+    println!("--sharpen\t\tSharpen the image with a 3x3 kernel.");
+    println!("--unsharp [mask]\t\tSharpen the image with an unsharp mask, given as amount,sigma or amount,sigma,threshold with the threshold from 0 to 255, such as 1.5,2,4.");
+    println!("--emboss\t\tMake the image look raised, on a gray background.");
+    println!("--laplacian\t\tFind the edges in the image with a Laplacian kernel, on a gray background.");
+    println!("--median [radius]\t\tReduce noise by taking the median of the pixels within the radius, such as 2.");
+    println!("--bilateral [sigmas]\t\tReduce noise while keeping edges, given as spatial,range with the range from 0 to 255, such as 3,25.");
+    println!("--nlmeans [settings]\t\tReduce noise with non-local means, given as strength or strength,patch,search with the strength from 0 to 255, such as 10 or 10,1,5.");
+    println!("--edges [detector]\t\tFind the edges in the image: sobel, prewitt or scharr for the strength of the edges, or canny for thin black and white edges saved as a bitmap.");
+    println!("--canny-thresholds [low,high]\t\tThe edge strengths from 0 to 255 used by --edges canny. Edges stronger than high are kept, along with the parts connected to them stronger than low. Defaults to 20,50.");
+    println!("--erode [radius]\t\tShrink the bright shapes in the image by taking the darkest pixel under the --element shape, or under the 1s in a file with a row of 1s and 0s on each line.");
+    println!("--dilate [radius]\t\tGrow the bright shapes in the image by taking the brightest pixel, with a radius or a file like --erode.");
+    println!("--open [radius]\t\tErode and then dilate, removing bright specks smaller than the element.");
+    println!("--close [radius]\t\tDilate and then erode, filling dark specks and gaps smaller than the element.");
+    println!("--top-hat [radius]\t\tKeep only the bright details smaller than the element, the image minus its opening.");
+    println!("--black-hat [radius]\t\tKeep only the dark details smaller than the element, the closing minus the image.");
+    println!("--morph-gradient [radius]\t\tOutline the shapes in the image, the dilation minus the erosion.");
+    println!("--element [shape]\t\tThe shape used by the morphological operations: square (default), disk or cross.");
+    println!("--threshold [method]\t\tTurn the image black and white: a level from 0 to 255, otsu, triangle, or a local threshold for uneven lighting: mean,radius,offset or gaussian,sigma,offset with the offset from 0 to 255, niblack,radius,k or sauvola,radius,k. The settings are optional, such as sauvola or mean,15,5.");
+    println!("--pbm\t\tSave the result of --threshold as a packed P4 bitmap.");
+    println!("--histogram [mode]\t\tPrint the number of samples of each channel and the luma at each level to stderr with print, or replace the image with a chart of them with chart. The number of bins can follow, such as print,16 or chart,64.");
+    println!("--equalize\t\tSpread the tones of the image evenly over the whole range, keeping the colors.");
+    println!("--match-histogram [file]\t\tChange the tones of each channel to match the histogram of another image.");
+    println!("--clahe [settings]\t\tEqualize each tile of the image separately with limited contrast, given as the tile size in pixels and optionally the clip limit, such as 64 or 64,2.");
+    println!("--kernel [file]\t\tConvolve the image with a kernel from a file, which has a row of weights on each line.");
+    println!("--normalize\t\tDivide the --kernel weights by their sum, so the brightness is kept.");
+    println!("-fh --flip-horizontal\t\tFlip the image horizontally.");
+    println!("-fv --flip-vertical\t\tFlip the image vertically.");
+    println!("-ir --isolate-red\t\tIsolate the red channel of the image.");
+    println!("-ig --isolate-green\t\tIsolate the green channel of the image.");
+    println!("-ib --isolate-blue\t\tIsolate the blue channel of the image.");
+    println!("-Dr --delete-red\t\tDelete the red channel of the image.");
+    println!("-Dg --delete-green\t\tDelete the green channel of the image.");
+    println!("-Db --delete-blue\t\tDelete the blue channel of the image.");
+}
+
+/*
+The output that processed frames are written to. It is opened when the first frame
+is written, since the default extension depends on the output format.
+*/
+struct Output {
+    path: Option<PathBuf>, // the output path given with -o, if any
+    input_file_path: PathBuf, // the input path, which the default output path is next to
+    read_from_stdin: bool, // images read from stdin are written to stdout by default
+    opened: Option<(PathBuf, Box<dyn Write>)>, // the path and writer of the output once it is opened
+    frames_written: u32, // the number of frames that have been written
+}
+
+impl Output {
+    /**
+    Returns the writer for the output, opening it first if needed.
+    `magic` is the format of the first frame, which sets the extension of the default output path.
+    */
+    fn writer(&mut self, magic: &str) -> &mut Box<dyn Write> {
+        if self.opened.is_none() {
+            // create a default output path next to the input file if none was given
+            // images read from stdin are written to stdout by default
+            let path = match &self.path {
+                Some(path) => path.clone(),
+                None if self.read_from_stdin => PathBuf::from("-"),
+                None => {
+                    let extension = match magic {
+                        "P1" | "P4" => "pbm",
+                        "P2" | "P5" => "pgm",
+                        "P7" => "pam",
+                        "PF" | "Pf" => "pfm",
+                        _ => "ppm",
+                    };
+                    let filename_no_extension = self.input_file_path.file_stem().unwrap_or("output".as_ref()).to_str().unwrap_or("output");
+                    let filename = format!("{}_modified.{}", filename_no_extension, extension);
+                    self.input_file_path.parent().unwrap().join(filename)
+                }
+            };
+
+            // open the output file, or stdout if the path is '-'
+            let writer: Box<dyn Write> = match write_to_stdout(&path) {
+                true => Box::new(io::stdout().lock()),
+                false => match File::create(&path) {
+                    Ok(file) => Box::new(file),
+                    Err(error) => {
+                        eprintln!("Error writing to output file. {}", error);
+                        process::exit(1);
+                    }
+                },
+            };
+            self.opened = Some((path, writer));
+        }
+
+        &mut self.opened.as_mut().unwrap().1
+    }
+}
+
+/**
+The number of pixels that are processed at once when an image is streamed.
+*/
+const STRIP_PIXELS: u32 = 1 << 20;
+
+/**
+The most times an image can be shrunk and still be streamed.
+*/
+const MAX_STREAMED_HALVINGS: u32 = 8;
+
+/**
+Returns the number of times the options shrink the image by 2x.
+*/
+fn count_halvings(args: &[String]) -> u32 {
+    args.iter().skip(2).filter(|arg| *arg == "-s" || *arg == "--shrink").count() as u32
+}
+
+/**
+Returns true if every option only changes single pixels or single rows, so the image
+can be processed a few rows at a time without changing the result. Shrinking averages
+pairs of rows, which still works as long as the rows are kept in pairs.
+*/
+fn is_row_local(args: &[String]) -> bool {
+    let mut skip_next = false;
+    for i in 2..args.len() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match args[i].as_ref() {
+            // floating point output needs the whole image
+            "-f" | "--format" if matches!(args.get(i + 1).map(String::as_str), Some("PF" | "Pf")) => return false,
+            "-f" | "--format" | "-m" | "--max-color" | "-o" | "--output" => skip_next = true,
+            "--hue" | "--saturation" | "--lightness" | "--brightness" | "--contrast" | "--gamma" | "--levels" | "--curves" => skip_next = true,
+            "--filter" | "--aspect" | "--edge" | "--fill" | "--anchor" | "--interpolation" | "--warp-size" | "--lut-interpolation" | "--canny-thresholds" | "--element" => skip_next = true,
+            "-h" | "--help" | "-t" | "--time" | "-c" | "--copy" | "--expand" | "--single-channel" => {}
+            arg if is_grayscale_option(arg) => {}
+            "-n" | "--negative" | "-fh" | "--flip-horizontal" | "-s" | "--shrink" => {}
+            "-ir" | "--isolate-red" | "-ig" | "--isolate-green" | "-ib" | "--isolate-blue" => {}
+            "-Dr" | "--delete-red" | "-Dg" | "--delete-green" | "-Db" | "--delete-blue" => {}
+            // everything else, including unknown options, is handled on the whole image
+            _ => return false,
+        }
+    }
+    count_halvings(args) <= MAX_STREAMED_HALVINGS
+}
+
+/**
+Returns the number of rows to process at once when streaming an image `width` pixels
+wide. It is a multiple of 2 for each time the image is shrunk, so rows stay in pairs
+until the last strip, which holds the odd last row of an odd sized image.
+*/
+fn strip_height(width: u32, halvings: u32) -> u32 {
+    let multiple = 1 << halvings;
+    let rows = (STRIP_PIXELS / width.max(1)).max(1);
+    rows.div_ceil(multiple) * multiple
+}
+
+/**
+Applies the effects to each strip of rows, and writes them to the output as one frame.
+`height` is the height of the frame before the effects are applied.
+*/
+fn write_strips(strips: impl Iterator<Item = Result<Image, PpmError>>, height: u32, args: &[String], report_unknown: bool, output: &mut Output) -> Result<(), PpmError> {
+    // apply the effect chain to each strip of rows, which never turns them into floating point
+    let mut processed = strips.enumerate().map(|(strip_index, strip)| -> Result<Option<Image>, PpmError> {
+        let (rows, write_image_on_completion) = apply_effects(strip?, args, report_unknown && strip_index == 0);
+        Ok(write_image_on_completion.then_some(rows))
+    });
+
+    // the first strip decides if anything is written, but the rest still has to be read
+    let Some(first) = processed.next().transpose()?.flatten() else {
+        for rows in processed {
+            rows?;
+        }
+        return Ok(());
+    };
+
+    // the header matches the first rows, with the height of the whole frame after shrinking, which rounds odd heights up
+    let height = (0..count_halvings(args)).fold(height, |height, _| height.div_ceil(2));
+    let mut writer = match &first {
+        Image::U8(rows) => RowWriter::new(output.writer(&rows.magic), &frame_header(rows, height))?,
+        Image::U16(rows) => RowWriter::new(output.writer(&rows.magic), &frame_header(rows, height))?,
+        Image::F32(_) => unreachable!("streamed effects never convert to floating point"),
+    };
+    for rows in std::iter::once(Ok(Some(first))).chain(processed) {
+        match rows? {
+            Some(Image::U8(rows)) => writer.write_rows(&rows)?,
+            Some(Image::U16(rows)) => writer.write_rows(&rows)?,
+            Some(Image::F32(_)) => unreachable!("streamed effects never convert to floating point"),
+            None => {}
+        }
+    }
+    writer.finish()?;
+    output.frames_written += 1;
+
+    Ok(())
+}
+
+/**
+Returns the header information of the rows, with the height of the whole frame.
+*/
+fn frame_header<S: Sample>(rows: &PPM<S>, height: u32) -> PPM<S> {
+    let mut header = PPM::new();
+    header.magic = rows.magic.clone();
+    header.tupltype = rows.tupltype.clone();
+    header.maxc = rows.maxc;
+    header.width = rows.width;
+    header.height = height;
+    header.alpha = rows.alpha.as_ref().map(|_| Vec::new());
+    header
+}
+
+/**
+Streams every frame of the input through the effects, a few rows at a time.
+*/
+fn stream_frames<R: BufRead>(reader: &mut R, args: &[String], output: &mut Output) -> Result<(), PpmError> {
+    let mut frame_index = 0;
+    while skip_to_next_image(reader)? {
+        let mut rows = RowReader::new(&mut *reader)?;
+        let height = rows.header().height;
+        let strip_rows = strip_height(rows.header().width, count_halvings(args));
+
+        // read strips until every row is read, always reading at least one so that empty images are still written
+        let mut finished = false;
+        let strips = std::iter::from_fn(|| {
+            if finished {
+                return None;
+            }
+            let strip = rows.read_rows(strip_rows);
+            finished = strip.is_err() || rows.rows_remaining() == 0;
+            Some(strip)
+        });
+
+        write_strips(strips, height, args, frame_index == 0, output)?;
+        frame_index += 1;
+    }
+
+    Ok(())
+}
+
+/**
+Processes the input through a memory map if the first effect that moves pixels between
+rows is an exact rotation or flip, and every other effect can be streamed. The
+rotated or flipped rows are built straight from the mapped file a few at a time.
+Returns None if the input can't be processed this way.
+*/
+#[cfg(feature = "mmap")]
+fn process_mapped(args: &[String], input_file_path: &Path, output: &mut Output) -> Option<Result<(), PpmError>> {
+    // find the effect, which can only be moved to the start if everything before it only changes single pixels
+    let index = (2..args.len()).find(|&i| matches!(args[i].as_str(),
+        "-rl" | "--rotate-left" | "-rr" | "--rotate-right" | "-fv" | "--flip-vertical" |
+        "-r180" | "--rotate-180" | "-tp" | "--transpose" | "-tv" | "--transverse"))?;
+    if args[2..index].iter().any(|arg| matches!(arg.as_str(), "-fh" | "--flip-horizontal" | "-s" | "--shrink")) {
+        return None;
+    }
+    // the effect is replaced with a copy, so the image is still written when there are no other effects
+    let mut remaining = args.to_vec();
+    let effect = std::mem::replace(&mut remaining[index], String::from("-c"));
+    if !is_row_local(&remaining) {
+        return None;
+    }
+
+    // only single raw images can be mapped, anything else is read normally
+    let image = MappedImage::open(input_file_path).ok()?;
+    if !image.is_single_image() {
+        return None;
+    }
+
+    // quarter turns and diagonal flips swap the width and height
+    let header = image.header();
+    let (width, height) = match effect.as_str() {
+        "-fv" | "--flip-vertical" | "-r180" | "--rotate-180" => (header.width, header.height),
+        _ => (header.height, header.width),
+    };
+    let strip_rows = strip_height(width, count_halvings(&remaining));
+
+    // build the rows a strip at a time, always building at least one so that empty images are still written
+    let eight_bit = header.maxc <= u8::MAX as u32;
+    let strips = (0..height.div_ceil(strip_rows).max(1)).map(|strip_index| {
+        let first_row = strip_index * strip_rows;
+        let rows = strip_rows.min(height - first_row);
+        Ok(match eight_bit {
+            true => Image::U8(mapped_strip(&image, &effect, first_row, rows)),
+            false => Image::U16(mapped_strip(&image, &effect, first_row, rows)),
+        })
+    });
+
+    Some(write_strips(strips, height, &remaining, true, output))
+}
+
+/**
+Builds `rows` rows of the mapped image with the exact rotation or flip applied, starting at `first_row`.
+*/
+#[cfg(feature = "mmap")]
+fn mapped_strip<S: IntegerSample>(image: &MappedImage, effect: &str, first_row: u32, rows: u32) -> PPM<S> {
+    match effect {
+        "-rl" | "--rotate-left" => imageactions::rotate_left_mapped(image, first_row, rows),
+        "-rr" | "--rotate-right" => imageactions::rotate_right_mapped(image, first_row, rows),
+        "-r180" | "--rotate-180" => imageactions::rotate_180_mapped(image, first_row, rows),
+        "-tp" | "--transpose" => imageactions::transpose_mapped(image, first_row, rows),
+        "-tv" | "--transverse" => imageactions::transverse_mapped(image, first_row, rows),
+        _ => imageactions::flip_vertical_mapped(image, first_row, rows),
+    }
+}
+
+/*
+The Aspect type selects how a resize to a width and height treats the aspect ratio of the image.
+*/
+#[derive(Copy, Clone)]
+enum Aspect {
+    Stretch, // resize to exactly the width and height
+    Fit, // the largest size that fits inside the width and height
+    Fill, // the smallest size that covers the width and height
+}
+
+/**
+Returns the value of the last use of an option, since options like --filter apply to every resize.
+*/
+fn last_value<'a>(args: &'a [String], names: &[&str]) -> Option<&'a String> {
+    (2..args.len()).rev().find(|&i| names.contains(&args[i].as_str())).and_then(|i| args.get(i + 1))
+}
+
+/**
+Parses the size given to --resize, which is WxH, Wx, or xH, and returns the new width and height.
+A missing side is calculated from the other one to keep the aspect ratio of the image.
+Returns None if the size can't be parsed, or if it's too large.
+*/
+fn parse_size(size: &str, width: u32, height: u32, aspect: Aspect) -> Option<(u32, u32)> {
+    let (new_width, new_height) = size.split_once('x').unwrap_or((size, ""));
+    let parse_side = |side: &str| -> Option<Option<u32>> {
+        match side {
+            "" => Some(None),
+            _ => side.parse::<u32>().ok().filter(|&side| side > 0).map(Some),
+        }
+    };
+
+    // scale the other side by the same amount, never going below 1 pixel
+    let scaled = |side: u32, from: u32, to: u32| ((side as f64 * to as f64 / from.max(1) as f64).round() as u32).max(1);
+
+    let size = match (parse_side(new_width)?, parse_side(new_height)?) {
+        (Some(new_width), Some(new_height)) => {
+            // compare the aspect ratios to find which side limits the size
+            let wider = new_width as u64 * height as u64 > new_height as u64 * width as u64;
+            match (aspect, wider) {
+                (Aspect::Stretch, _) => (new_width, new_height),
+                (Aspect::Fit, true) | (Aspect::Fill, false) => (scaled(width, height, new_height), new_height),
+                (Aspect::Fit, false) | (Aspect::Fill, true) => (new_width, scaled(height, width, new_width)),
+            }
+        }
+        (Some(new_width), None) => (new_width, scaled(height, width, new_width)),
+        (None, Some(new_height)) => (scaled(width, height, new_height), new_height),
+        (None, None) => return None,
+    };
+    checked_size(size)
+}
+
+/**
+Returns the size if an image of that size can be made, which needs its number of pixels to fit
+in a u32, the same as an image read from a file. Returns None if it's too large.
+*/
+fn checked_size((width, height): (u32, u32)) -> Option<(u32, u32)> {
+    width.checked_mul(height).map(|_| (width, height))
+}
+
+/**
+Parses the scale given to --scale, which is a percentage such as 37% or a factor such as 0.37,
+and returns the new width and height. Returns None if the scale can't be parsed, or if it makes
+the image too large.
+*/
+fn parse_scale(scale: &str, width: u32, height: u32) -> Option<(u32, u32)> {
+    let factor = match scale.strip_suffix('%') {
+        Some(percent) => percent.parse::<f64>().ok()? / 100.0,
+        None => scale.parse::<f64>().ok()?,
+    };
+    if !factor.is_finite() || factor <= 0.0 {
+        return None;
+    }
+
+    // never go below 1 pixel
+    let scaled = |side: u32| ((side as f64 * factor).round() as u32).max(1);
+    checked_size((scaled(width), scaled(height)))
+}
+
+/**
+Parses the sides given to --pad and --border, which are one size for every side, two sizes
+for the vertical and horizontal sides, or four sizes for the top, right, bottom and left.
+Returns the top, right, bottom and left sizes, or None if they can't be parsed.
+*/
+fn parse_sides(sides: &str) -> Option<[u32; 4]> {
+    let sizes = sides.split(',').map(|size| size.parse::<u32>().ok()).collect::<Option<Vec<u32>>>()?;
+    match sizes[..] {
+        [all] => Some([all; 4]),
+        [vertical, horizontal] => Some([vertical, horizontal, vertical, horizontal]),
+        [top, right, bottom, left] => Some([top, right, bottom, left]),
+        _ => None,
+    }
+}
+
+/**
+Parses the region given to --crop, which is WxH+X+Y or just WxH for the top left corner.
+Returns the x, y, width and height of the region, or None if it can't be parsed.
+*/
+fn parse_region(region: &str) -> Option<(u32, u32, u32, u32)> {
+    let mut parts = region.split('+');
+    let (width, height) = parts.next()?.split_once('x')?;
+    let x = parts.next().map_or(Some(0), |x| x.parse::<u32>().ok())?;
+    let y = parts.next().map_or(Some(0), |y| y.parse::<u32>().ok())?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((x, y, width.parse().ok()?, height.parse().ok()?))
+}
+
+/**
+Parses the color given to --fill, which is R,G,B or a single gray level from 0 to 255.
+Returns each channel from 0.0 to 1.0, so it can be scaled to any max color value,
+or None if it can't be parsed.
+*/
+fn parse_fill(fill: &str) -> Option<[f32; 3]> {
+    let levels = fill.split(',').map(|level| level.parse::<u8>().ok()).collect::<Option<Vec<u8>>>()?;
+    let level = |index: usize| levels[index] as f32 / 255.0;
+    match levels.len() {
+        1 => Some([level(0); 3]),
+        3 => Some([level(0), level(1), level(2)]),
+        _ => None,
+    }
+}
+
+/**
+Parses a comma separated list of exactly `count` numbers, such as the matrix given to --affine.
+Returns None if the list can't be parsed or has the wrong length.
+*/
+fn parse_numbers(list: &str, count: usize) -> Option<Vec<f64>> {
+    let numbers = list.split(',').map(|number| number.parse::<f64>().ok().filter(|number| number.is_finite())).collect::<Option<Vec<f64>>>()?;
+    (numbers.len() == count).then_some(numbers)
+}
+
+/**
+Parses the levels given to --levels, which are black,white, then optionally the gamma,
+and then optionally the output black and white. The levels are from 0 to 255, like --fill.
+Returns None if the levels can't be parsed.
+*/
+fn parse_levels(levels: &str) -> Option<Levels> {
+    let numbers = [2, 3, 5].iter().find_map(|&count| parse_numbers(levels, count))?;
+    let level = |index: usize| numbers[index] as f32 / 255.0;
+    let gamma = numbers.get(2).map_or(1.0, |&gamma| gamma as f32);
+    if level(0) >= level(1) || gamma <= 0.0 {
+        return None;
+    }
+    let (output_black, output_white) = match numbers.len() {
+        5 => (level(3), level(4)),
+        _ => (0.0, 1.0),
+    };
+    Some(Levels { input_black: level(0), input_white: level(1), gamma, output_black, output_white })
+}
+
+/**
+Parses the method given to --threshold, which is a level from 0 to 255, otsu, triangle, or a local
+method with optional settings after commas: mean,radius,offset and gaussian,sigma,offset with the
+offset from 0 to 255, niblack,radius,k, and sauvola,radius,k.
+*/
+fn parse_threshold(method: &str) -> Option<ThresholdMethod> {
+    if let Ok(level) = method.parse::<f32>() {
+        return (0.0..=255.0).contains(&level).then_some(ThresholdMethod::Fixed(level / 255.0));
+    }
+
+    let (name, settings) = method.split_once(',').unwrap_or((method, ""));
+    let settings = match settings.is_empty() {
+        true => Vec::new(),
+        false => settings.split(',').map(|setting| setting.trim().parse::<f32>().ok().filter(|setting| setting.is_finite())).collect::<Option<Vec<f32>>>()?,
+    };
+    let setting = |index: usize, default: f32| settings.get(index).copied().unwrap_or(default);
+    let radius = setting(0, 15.0);
+    if settings.len() > 2 || radius < 0.0 || (name != "gaussian" && radius.fract() != 0.0) {
+        return None;
+    }
+
+    let method = match name {
+        "otsu" if settings.is_empty() => Some(ThresholdMethod::Otsu),
+        "triangle" if settings.is_empty() => Some(ThresholdMethod::Triangle),
+        "mean" => Some(ThresholdMethod::AdaptiveMean { radius: radius as u32, offset: setting(1, 5.0) / 255.0 }),
+        "gaussian" => Some(ThresholdMethod::AdaptiveGaussian { sigma: setting(0, 5.0), offset: setting(1, 5.0) / 255.0 }),
+        "niblack" => Some(ThresholdMethod::Niblack { radius: radius as u32, k: setting(1, -0.2) }),
+        "sauvola" => Some(ThresholdMethod::Sauvola { radius: radius as u32, k: setting(1, 0.2), range: 0.5 }),
+        _ => None,
+    };
+    method.filter(|method| method.is_valid())
+}
+
+/**
+Parses the points given to --curves, which are input:output pairs from 0 to 255 separated by commas.
+Returns None if the points can't be parsed, or the inputs aren't in increasing order.
+*/
+fn parse_curve(points: &str) -> Option<Curve> {
+    let points = points.split(',').map(|point| {
+        let (input, output) = point.split_once(':')?;
+        Some((input.parse::<f32>().ok()? / 255.0, output.parse::<f32>().ok()? / 255.0))
+    }).collect::<Option<Vec<(f32, f32)>>>()?;
+    Curve::new(&points)
+}
+
+/**
+Reads the kernel file given to --kernel, which has one row of weights on each line, separated by
+spaces or commas. Lines starting with '#' are comments. Returns None if the rows can't be
+parsed or aren't all the same length.
+*/
+fn read_kernel(path: &Path) -> io::Result<Option<Kernel>> {
+    let mut rows = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.split(|c: char| c == ',' || c.is_whitespace()).filter(|weight| !weight.is_empty()).map(|weight| weight.parse::<f32>().ok().filter(|weight| weight.is_finite())).collect::<Option<Vec<f32>>>() {
+            Some(row) => rows.push(row),
+            None => return Ok(None),
+        }
+    }
+
+    let width = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != width) {
+        return Ok(None);
+    }
+    Ok(Kernel::new(width as u32, rows.len() as u32, rows.concat()))
+}
+
+/**
+Reads a structuring element file given to a morphological operation, which has one row of 1s and
+0s on each line, optionally separated by spaces or commas. Lines starting with '#' are comments.
+Returns None if the rows can't be parsed, aren't all the same length, or have no 1s.
+*/
+fn read_element(path: &Path) -> io::Result<Option<StructuringElement>> {
+    let mut rows = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        match line.chars().filter(|&c| c != ',' && !c.is_whitespace()).map(|c| match c {
+            '1' => Some(true),
+            '0' => Some(false),
+            _ => None,
+        }).collect::<Option<Vec<bool>>>() {
+            Some(row) => rows.push(row),
+            None => return Ok(None),
+        }
+    }
+
+    let width = rows.first().map_or(0, |row| row.len());
+    if rows.iter().any(|row| row.len() != width) {
+        return Ok(None);
+    }
+    Ok(StructuringElement::new(width as u32, rows.len() as u32, rows.concat()))
+}
+
+/**
+Reads the LUT given to --lut, which is a .cube file, or otherwise a graded HALD image.
+*/
+fn read_lut(path: &Path) -> Result<Lut, PpmError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("cube")) {
+        return Lut::from_cube(reader);
+    }
+
+    // the HALD image can be any format the tool reads
+    match read_any_image(&mut reader)? {
+        Image::U8(image) => Lut::from_hald(&image),
+        Image::U16(image) => Lut::from_hald(&image),
+        Image::F32(image) => Lut::from_hald(&image),
+    }
+}
+
+/**
+Reads an image in any of the formats the tool reads, including PFM, such as a HALD image or the reference for --match-histogram.
+*/
+fn read_any_image<R: BufRead>(reader: &mut R) -> Result<Image, PpmError> {
+    let bytes = reader.fill_buf()?;
+    let is_pfm = bytes.starts_with(b"PF") || bytes.starts_with(b"Pf");
+    match is_pfm {
+        true => Ok(Image::F32(read_pfm(reader)?)),
+        false => read_image(reader),
+    }
+}
+
+/**
+Parses the corners given to --perspective, and returns the homography that moves them to where
+they go in the new image, along with the size of the new image. With 8 numbers the corners go to
+the corners of the new image, which is `warp_size` or else as big as the sides between the corners.
+With 16 numbers they go to the last 4 points instead, and the size is `warp_size` or the old size.
+Returns None if the corners can't be parsed, or three of them are on a line.
+*/
+fn parse_perspective(corners: &str, warp_size: Option<(u32, u32)>, width: u32, height: u32) -> Option<(Matrix, (u32, u32))> {
+    let numbers = parse_numbers(corners, 8).or_else(|| parse_numbers(corners, 16))?;
+    let point = |index: usize| (numbers[index * 2], numbers[index * 2 + 1]);
+    let from = [point(0), point(1), point(2), point(3)];
+
+    let (to, size) = match numbers.len() {
+        16 => ([point(4), point(5), point(6), point(7)], warp_size.unwrap_or((width, height))),
+        _ => {
+            // pixels are centered on whole numbers, so a side between two corners is one pixel longer than its length
+            let length = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| (x2 - x1).hypot(y2 - y1);
+            let side = |a: f64, b: f64| (a.max(b).round() as u32 + 1).max(1);
+            let (new_width, new_height) = warp_size.unwrap_or((
+                side(length(from[0], from[1]), length(from[3], from[2])),
+                side(length(from[0], from[3]), length(from[1], from[2])),
+            ));
+            let (right, bottom) = ((new_width - 1) as f64, (new_height - 1) as f64);
+            ([(0.0, 0.0), (right, 0.0), (right, bottom), (0.0, bottom)], (new_width, new_height))
+        }
+    };
+    Some((imageactions::homography(from, to)?, size))
+}
+
+/**
+Scales the fill color to an image with the given max color value.
+*/
+fn fill_color<S: Sample>(fill: [f32; 3], maxc: u32) -> Pixel<S> {
+    let level = |channel: f32| S::from_f32_clamped(channel * maxc as f32, maxc);
+    Pixel { r: level(fill[0]), g: level(fill[1]), b: level(fill[2]) }
+}
+
+/**
+Creates the edge mode named by --edge for an image with the given max color value.
+The constant edge uses the fill color.
+*/
+fn edge_mode<S: Sample>(mode: &str, fill: [f32; 3], maxc: u32) -> Edge<S> {
+    match mode {
+        "replicate" | "clamp" => Edge::Replicate,
+        "zero" => Edge::Constant(Pixel::new()),
+        "reflect" => Edge::Reflect,
+        "wrap" => Edge::Wrap,
+        _ => Edge::Constant(fill_color(fill, maxc)),
+    }
+}
+
+/**
+Returns true if the argument is -g or --grayscale, with or without a =mode.
+*/
+fn is_grayscale_option(arg: &str) -> bool {
+    matches!(arg.split_once('=').map_or(arg, |(name, _)| name), "-g" | "--grayscale")
+}
+
+/**
+Returns the grayscale mode with the given name, or None if there isn't one.
+*/
+fn grayscale_mode(mode: &str) -> Option<GrayscaleMode> {
+    match mode {
+        "average" => Some(GrayscaleMode::Average),
+        "601" => Some(GrayscaleMode::Rec601),
+        "709" => Some(GrayscaleMode::Rec709),
+        "luminance" => Some(GrayscaleMode::Luminance),
+        "lightness" => Some(GrayscaleMode::Lightness),
+        "red" => Some(GrayscaleMode::Channel(Channel::Red)),
+        "green" => Some(GrayscaleMode::Channel(Channel::Green)),
+        "blue" => Some(GrayscaleMode::Channel(Channel::Blue)),
+        _ => None,
+    }
+}
+
+/**
+Applies every effect in the arguments to the image, in order.
+Returns the modified image, and whether it should be written on completion.
+Unknown options are only reported when `report_unknown` is true, so that they
+are reported once for a stream of frames instead of once per frame.
+*/
+fn apply_effects(mut image: Image, args: &[String], report_unknown: bool) -> (Image, bool) {
+    // for each additional argument after the input file, parse the argument and perform the specified operation
+    let mut skip_next = false;
+    let mut write_image_on_completion = false;
+
+    // the resize filter and aspect ratio mode apply to every resize, wherever they are given
+    let filter = match last_value(args, &["--filter"]).map(String::as_str) {
+        None | Some("bicubic") => Filter::Bicubic,
+        Some("nearest") => Filter::Nearest,
+        Some("bilinear") => Filter::Bilinear,
+        Some("lanczos3") => Filter::Lanczos3,
+        Some("box") => Filter::Box,
+        Some(filter) => {
+            eprintln!("Unknown filter '{}'. Supported filters are nearest, bilinear, bicubic, lanczos3 and box.", filter);
+            process::exit(1);
+        }
+    };
+    let aspect = match last_value(args, &["--aspect"]).map(String::as_str) {
+        None | Some("stretch") => Aspect::Stretch,
+        Some("fit") => Aspect::Fit,
+        Some("fill") => Aspect::Fill,
+        Some(aspect) => {
+            eprintln!("Unknown aspect mode '{}'. Supported modes are stretch, fit and fill.", aspect);
+            process::exit(1);
+        }
+    };
+
+    // the padding options also apply to every use, wherever they are given
+    let edge = match last_value(args, &["--edge"]).map(String::as_str) {
+        None => "constant",
+        Some(edge @ ("constant" | "replicate" | "clamp" | "reflect" | "wrap" | "zero")) => edge,
+        Some(edge) => {
+            eprintln!("Unknown edge mode '{}'. Supported modes are constant, replicate (or clamp), reflect, wrap and zero.", edge);
+            process::exit(1);
+        }
+    };
+    let fill = match last_value(args, &["--fill"]).map(|fill| parse_fill(fill)) {
+        None => [0.0; 3],
+        Some(Some(fill)) => fill,
+        Some(None) => {
+            eprintln!("Invalid fill color '{}'. Use R,G,B or a gray level, from 0 to 255.", last_value(args, &["--fill"]).unwrap());
+            process::exit(1);
+        }
+    };
+    let interpolation = match last_value(args, &["--interpolation"]).map(String::as_str) {
+        None | Some("bilinear") => Interpolation::Bilinear,
+        Some("nearest") => Interpolation::Nearest,
+        Some("bicubic") => Interpolation::Bicubic,
+        Some(interpolation) => {
+            eprintln!("Unknown interpolation '{}'. Supported modes are nearest, bilinear and bicubic.", interpolation);
+            process::exit(1);
+        }
+    };
+    let expand = args[2..].iter().any(|arg| arg == "--expand");
+    let single_channel = args[2..].iter().any(|arg| arg == "--single-channel");
+    let normalize = args[2..].iter().any(|arg| arg == "--normalize");
+    let pbm = args[2..].iter().any(|arg| arg == "--pbm");
+
+    // filters that look at the pixels around each pixel clamp to the edge pixel, unless --edge is given
+    let filter_edge = last_value(args, &["--edge"]).map_or("replicate", |_| edge);
+    let lut_interpolation = match last_value(args, &["--lut-interpolation"]).map(String::as_str) {
+        None | Some("trilinear") => LutInterpolation::Trilinear,
+        Some("tetrahedral") => LutInterpolation::Tetrahedral,
+        Some(interpolation) => {
+            eprintln!("Unknown LUT interpolation '{}'. Supported modes are trilinear and tetrahedral.", interpolation);
+            process::exit(1);
+        }
+    };
+    let warp_size = match last_value(args, &["--warp-size"]).map(|size| size.split_once('x').and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))) {
+        None => None,
+        Some(Some((width, height))) if width > 0 && height > 0 => Some((width, height)),
+        Some(_) => {
+            eprintln!("Invalid warp size '{}'. It must be WxH, such as 640x480.", last_value(args, &["--warp-size"]).unwrap());
+            process::exit(1);
+        }
+    };
+    let canny_thresholds = match last_value(args, &["--canny-thresholds"]).map(|thresholds| parse_numbers(thresholds, 2)) {
+        None => (20.0 / 255.0, 50.0 / 255.0),
+        Some(Some(thresholds)) if 0.0 <= thresholds[0] && thresholds[0] <= thresholds[1] => (thresholds[0] as f32 / 255.0, thresholds[1] as f32 / 255.0),
+        Some(_) => {
+            eprintln!("Invalid Canny thresholds '{}'. Use low,high from 0 to 255 with low below high, such as 20,50.", last_value(args, &["--canny-thresholds"]).unwrap());
+            process::exit(1);
+        }
+    };
+    let element_shape = match last_value(args, &["--element"]).map(String::as_str) {
+        None => "square",
+        Some(shape @ ("square" | "disk" | "cross")) => shape,
+        Some(shape) => {
+            eprintln!("Unknown structuring element '{}'. Supported shapes are square, disk and cross.", shape);
+            process::exit(1);
+        }
+    };
+    let anchor = match last_value(args, &["--anchor"]).map(String::as_str) {
+        None | Some("center") => Anchor::Center,
+        Some("top-left") => Anchor::TopLeft,
+        Some("top") => Anchor::Top,
+        Some("top-right") => Anchor::TopRight,
+        Some("left") => Anchor::Left,
+        Some("right") => Anchor::Right,
+        Some("bottom-left") => Anchor::BottomLeft,
+        Some("bottom") => Anchor::Bottom,
+        Some("bottom-right") => Anchor::BottomRight,
+        Some(anchor) => {
+            eprintln!("Unknown anchor '{}'. Supported anchors are center, top-left, top, top-right, left, right, bottom-left, bottom and bottom-right.", anchor);
+            process::exit(1);
+        }
+    };
+
+    for i in 2..args.len() {
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match args[i].as_ref() {
+
+            "-c" | "--copy" => {
+                // create image copy
+                write_image_on_completion = true;
+            }
+
+            "-f" | "--format" => {
+                // change the output format
+                // the argument after this one should be the new format
+                match args.get(i + 1) {
+                    Some(format) if is_supported_magic(format) => {
+                        // floating point images are converted to 16-bit integers
+                        image = match image {
+                            Image::F32(inner) => Image::U16(inner.to_integer(65535, format)),
+                            integer => integer,
+                        };
+                        match &mut image {
+                            Image::U8(inner) => inner.magic = format.clone(),
+                            Image::U16(inner) => inner.magic = format.clone(),
+                            Image::F32(_) => {}
+                        }
+                        skip_next = true; // skip the next argument since it's the format
+                    }
+
+                    Some(format) if format == "PF" || format == "Pf" => {
+                        // integer images are converted to floating point
+                        let mut inner = image.to_float();
+                        inner.magic = format.clone();
+                        image = Image::F32(inner);
+                        skip_next = true; // skip the next argument since it's the format
+                    }
+
+                    Some(format) => {
+                        eprintln!("Unknown format '{}'. Supported formats are P1 to P7, PF and Pf.", format);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No output format specified.");
+                    }
+                }
+            }
+
+            "-m" | "--max-color" => {
+                // rescale the image to a new max color value
+                // the argument after this one should be the new max color value
+                match args.get(i + 1).map(|maxc| maxc.parse::<u32>()) {
+                    Some(Ok(maxc)) if is_supported_maxc(maxc) => {
+                        // the samples are switched between 8-bit and 16-bit to fit the new max color value,
+                        // and floating point images are converted to integers with the new max color value
+                        image = image.convert_depth(maxc);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the max color value
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid max color value '{}'. It must be a number from 1 to 65535.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No max color value specified.");
+                    }
+                }
+            }
+
+            "-n" | "--negative" => {
+                // convert image to negative
+                modify!(image, negative);
+                write_image_on_completion = true;
+            }
+
+            arg if is_grayscale_option(arg) => {
+                // convert image to grayscale
+                // the mode is given after an equals sign, and defaults to the average
+                let mode = match arg.split_once('=') {
+                    None => GrayscaleMode::Average,
+                    Some((_, mode)) => match grayscale_mode(mode) {
+                        Some(mode) => mode,
+                        None => {
+                            eprintln!("Unknown grayscale mode '{}'. Supported modes are average, 601, 709, luminance, lightness, red, green and blue.", mode);
+                            process::exit(1);
+                        }
+                    },
+                };
+                modify!(image, grayscale, mode);
+                if single_channel {
+                    modify!(image, to_single_channel);
+                }
+                write_image_on_completion = true;
+            }
+
+            "--hue" | "--saturation" | "--lightness" => {
+                // change the hue, saturation, or lightness of every color
+                // the argument after this one should be the amount
+                let name = &args[i][2..];
+                match args.get(i + 1).map(|amount| amount.parse::<f32>()) {
+                    Some(Ok(amount)) if amount.is_finite() => {
+                        match name {
+                            "hue" => modify!(image, rotate_hue, amount),
+                            "saturation" => modify!(image, scale_saturation, amount),
+                            _ => modify!(image, adjust_lightness, amount),
+                        }
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the amount
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid {} '{}'. It must be a number, such as +30 or 1.2.", name, args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No {} specified.", name);
+                    }
+                }
+            }
+
+            "--brightness" | "--contrast" | "--gamma" | "--auto-levels" | "--auto-contrast" => {
+                // change the tones of the image
+                // the argument after this one should be the amount
+                let name = &args[i][2..];
+                let (minimum, example) = match name {
+                    "brightness" => (f32::MIN, "a percentage, such as +10 or -10"),
+                    "contrast" => (f32::MIN, "a factor, such as 1.5 or 0.5"),
+                    "gamma" => (f32::MIN_POSITIVE, "a positive number, such as 2.2"),
+                    _ => (0.0, "a percentage of samples to ignore, such as 0.5"),
+                };
+                match args.get(i + 1).map(|amount| amount.parse::<f32>()) {
+                    Some(Ok(amount)) if amount.is_finite() && amount >= minimum => {
+                        match name {
+                            "brightness" => modify!(image, adjust_brightness, amount / 100.0),
+                            "contrast" => modify!(image, adjust_contrast, amount),
+                            "gamma" => modify!(image, adjust_gamma, amount),
+                            "auto-levels" => modify!(image, auto_levels, amount / 100.0),
+                            _ => modify!(image, auto_contrast, amount / 100.0),
+                        }
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the amount
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid {} '{}'. It must be {}.", name, args[i + 1], example);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No {} specified.", name);
+                    }
+                }
+            }
+
+            "--levels" => {
+                // stretch the levels of the image
+                // the argument after this one should be the levels
+                match args.get(i + 1).map(|levels| parse_levels(levels)) {
+                    Some(Some(levels)) => {
+                        modify!(image, levels, levels);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the levels
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid levels '{}'. Use black,white with an optional gamma and output black,white, such as 16,235 or 16,235,1.2,0,255.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No levels specified.");
+                    }
+                }
+            }
+
+            "--curves" => {
+                // apply a tone curve to the image
+                // the argument after this one should be the curve points
+                match args.get(i + 1).map(|points| parse_curve(points)) {
+                    Some(Some(curve)) => {
+                        modify!(image, curves, &curve);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the points
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid curve points '{}'. Use at least two input:output points in increasing order, such as 0:0,64:48,255:255.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No curve points specified.");
+                    }
+                }
+            }
+
+            "--lut" => {
+                // grade the colors with a lookup table
+                // the argument after this one should be the LUT file
+                match args.get(i + 1) {
+                    Some(path) => {
+                        let lut = match read_lut(Path::new(path)) {
+                            Ok(lut) => lut,
+                            Err(PpmError::Io(_)) => {
+                                eprintln!("Error opening LUT file. Make sure '{}' exists.", path);
+                                process::exit(1);
+                            }
+                            Err(error) => {
+                                eprintln!("{}", error);
+                                process::exit(1);
+                            }
+                        };
+                        modify!(image, apply_lut, &lut, lut_interpolation);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the LUT file
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No LUT file specified.");
+                    }
+                }
+            }
+
+            "--hald" => {
+                // replace the image with an identity HALD image, keeping its max color value
+                // the argument after this one should be the HALD level
+                match args.get(i + 1).map(|level| level.parse::<u32>()) {
+                    Some(Ok(level)) if (2..=16).contains(&level) => {
+                        transform!(image, |inner| lut::hald_identity(level, inner.maxc));
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the level
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid HALD level '{}'. It must be a number from 2 to 16.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No HALD level specified.");
+                    }
+                }
+            }
+
+            "--blur" | "--gaussian" => {
+                // blur the image with a box or a gaussian
+                // the argument after this one should be the radius or sigma
+                let name = &args[i][2..];
+                match args.get(i + 1).map(|size| size.parse::<f32>()) {
+                    // the window of a gaussian reaches 3 sigma
+                    Some(Ok(size)) if size.is_finite() && size >= 0.0 && match name {
+                        "blur" => size.fract() == 0.0 && size <= MAX_RADIUS as f32,
+                        _ => size * 3.0 <= MAX_RADIUS as f32,
+                    } => {
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            match name {
+                                "blur" => imageactions::box_blur(inner, size as u32, edge),
+                                _ => imageactions::gaussian_blur(inner, size, edge),
+                            }
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the size
+                    }
+
+                    Some(_) => {
+                        match name {
+                            "blur" => eprintln!("Invalid blur radius '{}'. It must be a whole number of pixels up to {}, such as 2.", args[i + 1], MAX_RADIUS),
+                            _ => eprintln!("Invalid gaussian sigma '{}'. It must be a number of pixels up to {}, such as 1.5.", args[i + 1], MAX_RADIUS / 3),
+                        }
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No {} size specified.", name);
+                    }
+                }
+            }
+
+            "--sharpen" | "--emboss" | "--laplacian" => {
+                // convolve the image with one of the built in kernels
+                let kernel = match args[i].as_ref() {
+                    "--sharpen" => Kernel::sharpen(),
+                    "--emboss" => Kernel::emboss(),
+                    _ => Kernel::laplacian(),
+                };
+                transform!(image, |inner| {
+                    let edge = edge_mode(filter_edge, fill, inner.maxc);
+                    imageactions::convolve(inner, &kernel, edge)
+                });
+                write_image_on_completion = true;
+            }
+
+            "--unsharp" => {
+                // sharpen the image with an unsharp mask
+                // the argument after this one should be the amount, sigma, and optionally the threshold
+                match args.get(i + 1).map(|mask| parse_numbers(mask, 2).or_else(|| parse_numbers(mask, 3))) {
+                    Some(Some(mask)) if mask[1] > 0.0 && mask[1] * 3.0 <= MAX_RADIUS as f64 && mask.get(2).is_none_or(|&threshold| threshold >= 0.0) => {
+                        let (amount, sigma) = (mask[0] as f32, mask[1] as f32);
+                        let threshold = mask.get(2).map_or(0.0, |&threshold| threshold as f32 / 255.0);
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::unsharp_mask(inner, amount, sigma, threshold, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the mask settings
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid unsharp mask '{}'. Use amount,sigma or amount,sigma,threshold with the sigma up to {}, such as 1.5,2 or 1.5,2,4.", args[i + 1], MAX_RADIUS / 3);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No unsharp mask specified.");
+                    }
+                }
+            }
+
+            "--kernel" => {
+                // convolve the image with a kernel from a file
+                // the argument after this one should be the kernel file
+                match args.get(i + 1).map(|path| read_kernel(Path::new(path))) {
+                    Some(Ok(Some(kernel))) => {
+                        let kernel = match normalize {
+                            true => kernel.normalized(),
+                            false => kernel,
+                        };
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::convolve(inner, &kernel, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the kernel file
+                    }
+
+                    Some(Ok(None)) => {
+                        eprintln!("Invalid kernel file '{}'. It must have one row of numbers on each line, with every row the same length.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    Some(Err(_)) => {
+                        eprintln!("Error opening kernel file. Make sure '{}' exists.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No kernel file specified.");
+                    }
+                }
+            }
+
+            "--median" => {
+                // reduce noise with a median filter
+                // the argument after this one should be the radius
+                match args.get(i + 1).map(|radius| radius.parse::<u32>()) {
+                    Some(Ok(radius)) if radius <= MAX_RADIUS => {
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::median_filter(inner, radius, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the radius
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid median radius '{}'. It must be a whole number of pixels up to {}, such as 2.", args[i + 1], MAX_RADIUS);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No median radius specified.");
+                    }
+                }
+            }
+
+            "--bilateral" => {
+                // reduce noise while keeping edges with a bilateral filter
+                // the argument after this one should be the spatial and range sigmas
+                match args.get(i + 1).map(|sigmas| parse_numbers(sigmas, 2)) {
+                    // the window of the filter reaches 2 sigma
+                    Some(Some(sigmas)) if sigmas[0] > 0.0 && sigmas[0] * 2.0 <= MAX_RADIUS as f64 && sigmas[1] > 0.0 => {
+                        let (sigma_spatial, sigma_range) = (sigmas[0] as f32, sigmas[1] as f32 / 255.0);
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::bilateral_filter(inner, sigma_spatial, sigma_range, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the sigmas
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid bilateral filter '{}'. Use spatial,range with the spatial sigma up to {} and the range from 0 to 255, such as 3,25.", args[i + 1], MAX_RADIUS / 2);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No bilateral filter specified.");
+                    }
+                }
+            }
+
+            "--nlmeans" => {
+                // reduce noise with non-local means
+                // the argument after this one should be the strength, and optionally the patch and search radii
+                match args.get(i + 1).map(|settings| parse_numbers(settings, 1).or_else(|| parse_numbers(settings, 3))) {
+                    Some(Some(settings)) if settings[0] > 0.0 && settings[1..].iter().all(|&radius| radius >= 0.0 && radius.fract() == 0.0 && radius <= MAX_RADIUS as f64) => {
+                        let strength = settings[0] as f32 / 255.0;
+                        let (patch_radius, search_radius) = match settings.len() {
+                            3 => (settings[1] as u32, settings[2] as u32),
+                            _ => (1, 5),
+                        };
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::non_local_means(inner, strength, patch_radius, search_radius, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the settings
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid non-local means settings '{}'. Use strength or strength,patch,search with the strength from 0 to 255 and the radii up to {}, such as 10 or 10,1,5.", args[i + 1], MAX_RADIUS);
+                        process::exit(1);
+                    }
 
-fn print_help_text() {
-    let executable_name = env::args().nth(0).unwrap();
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No non-local means strength specified.");
+                    }
+                }
+            }
 
-    println!("PPM IMAGE TOOL\n");
-    println!("SYNTAX: {} <file> [options]\n", executable_name);
-    println!("If run with no options, the tool will output the width and height of the image.\n");
+            "--edges" => {
+                // find the edges with a gradient operator, or with the Canny detector
+                // the argument after this one should be the operator
+                match args.get(i + 1).map(String::as_str) {
+                    Some("canny") => {
+                        // the edges are black and white, so they are saved as a bitmap
+                        let (low, high) = canny_thresholds;
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::canny(inner, 1.4, low, high, edge)
+                        });
+                        image = match image {
+                            Image::F32(inner) => Image::U16(inner.to_integer(65535, "P4")),
+                            integer => integer,
+                        };
+                        modify!(image, to_bitmap);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the operator
+                    }
 
-    println!("-h, --help\t\tPrint this help text.");
-    println!("-c, --copy\t\tCreate an exact copy of the image.");
-    println!("-o [file_path], --output [file_path]\t\tSpecify where to save the image.");
-    println!("-t --time\t\tPrint the time it took to run the program.");
-    println!("\nImage Effects");
-    println!("-n, --negative\t\tConvert the image to a negative.");
-    println!("-g, --grayscale\t\tConvert the image to grayscale.");
-    println!("-rl, --rotate-left\t\tRotate the image 90 degrees counter-clockwise.");
-    println!("-rr, --rotate-right\t\tRotate the image 90 degrees clockwise.");
-    println!("-s, --shrink\t\tShrink the image by 2x.");
-    println!("-d, --double\t\tDouble the size of the image by turning each pixel into a 2x2 square.");
-    println!("-db --double-bilinear\t\tDouble the size of the image using bilinear interpolation.");
-    println!("-fh --flip-horizontal\t\tFlip the image horizontally.");
-    println!("-fv --flip-vertical\t\tFlip the image vertically.");
-    println!("-ir --isolate-red\t\tIsolate the red channel of the image.");
-    println!("-ig --isolate-green\t\tIsolate the green channel of the image.");
-    println!("-ib --isolate-blue\t\tIsolate the blue channel of the image.");
-    println!("-Dr --delete-red\t\tDelete the red channel of the image.");
-    println!("-Dg --delete-green\t\tDelete the green channel of the image.");
-    println!("-Db --delete-blue\t\tDelete the blue channel of the image.");
+                    Some(name @ ("sobel" | "prewitt" | "scharr")) => {
+                        // keep the magnitude of the gradient
+                        let operator = match name {
+                            "sobel" => GradientOperator::Sobel,
+                            "prewitt" => GradientOperator::Prewitt,
+                            _ => GradientOperator::Scharr,
+                        };
+                        transform!(image, |inner| {
+                            let edge = edge_mode(filter_edge, fill, inner.maxc);
+                            imageactions::gradient(inner, operator, edge).0
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the operator
+                    }
+
+                    Some(operator) => {
+                        eprintln!("Unknown edge detector '{}'. Supported detectors are sobel, prewitt, scharr and canny.", operator);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No edge detector specified.");
+                    }
+                }
+            }
+
+            "--erode" | "--dilate" | "--open" | "--close" | "--top-hat" | "--black-hat" | "--morph-gradient" => {
+                // change the shapes in the image with a morphological operation
+                // the argument after this one should be the radius of the --element shape, or a structuring element file
+                let element = match args.get(i + 1).map(|radius| radius.parse::<u32>()) {
+                    Some(Ok(radius)) => {
+                        let element = match element_shape {
+                            "disk" => StructuringElement::disk(radius),
+                            "cross" => StructuringElement::cross(radius),
+                            _ => StructuringElement::square(radius),
+                        };
+                        match element {
+                            Some(element) => element,
+                            None => {
+                                eprintln!("Invalid radius '{}'. The structuring element can't have more than {} cells.", args[i + 1], u32::MAX);
+                                process::exit(1);
+                            }
+                        }
+                    }
+
+                    Some(Err(_)) => match read_element(Path::new(&args[i + 1])) {
+                        Ok(Some(element)) => element,
+                        Ok(None) => {
+                            eprintln!("Invalid structuring element file '{}'. It must have one row of 1s and 0s on each line, with every row the same length.", args[i + 1]);
+                            process::exit(1);
+                        }
+                        Err(_) => {
+                            eprintln!("Invalid radius or structuring element file '{}'. Use a whole number of pixels, or make sure the file exists.", args[i + 1]);
+                            process::exit(1);
+                        }
+                    },
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No structuring element specified.");
+                        continue;
+                    }
+                };
+
+                let operation = args[i].as_str();
+                transform!(image, |inner| match operation {
+                    "--erode" => morphology::erode(inner, &element),
+                    "--dilate" => morphology::dilate(inner, &element),
+                    "--open" => morphology::open(inner, &element),
+                    "--close" => morphology::close(inner, &element),
+                    "--top-hat" => morphology::top_hat(inner, &element),
+                    "--black-hat" => morphology::black_hat(inner, &element),
+                    _ => morphology::gradient(inner, &element),
+                });
+                write_image_on_completion = true;
+                skip_next = true; // skip the next argument since it's the structuring element
+            }
+
+            "--threshold" => {
+                // turn the image black and white
+                // the argument after this one should be the threshold method
+                match args.get(i + 1).map(|method| parse_threshold(method)) {
+                    Some(Some(method)) => {
+                        transform!(image, |inner| threshold::threshold(inner, method).unwrap()); // the method was checked when it was parsed
+                        if pbm {
+                            // save the result as a packed bitmap
+                            image = match image {
+                                Image::F32(inner) => Image::U16(inner.to_integer(65535, "P4")),
+                                integer => integer,
+                            };
+                            modify!(image, to_bitmap);
+                            match &mut image {
+                                Image::U8(inner) => inner.magic = String::from("P4"),
+                                Image::U16(inner) => inner.magic = String::from("P4"),
+                                Image::F32(_) => {}
+                            }
+                        }
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the method
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid threshold '{}'. Use a level from 0 to 255, otsu, triangle, or mean, gaussian, niblack or sauvola with optional settings and a radius up to {}, such as sauvola,15,0.2.", args[i + 1], MAX_RADIUS);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No threshold specified.");
+                    }
+                }
+            }
+
+            "--histogram" => {
+                // print the histogram of the image, or replace the image with a chart of it
+                // the argument after this one should be print or chart, and optionally the number of bins
+                let mode = args.get(i + 1).map(|mode| match mode.split_once(',') {
+                    None => Some((mode.as_str(), None)),
+                    Some((mode, bins)) => bins.parse::<usize>().ok().filter(|bins| (2..=65536).contains(bins)).map(|bins| (mode, Some(bins))),
+                });
+                match mode {
+                    Some(Some(("print", bins))) => {
+                        // there is a bin for each level unless the number of bins is given
+                        let (histogram, maxc) = match &image {
+                            Image::U8(inner) => (Histogram::with_bins(inner, bins.unwrap_or(histogram::level_bins::<u8>(inner.maxc))), inner.maxc),
+                            Image::U16(inner) => (Histogram::with_bins(inner, bins.unwrap_or(histogram::level_bins::<u16>(inner.maxc))), inner.maxc),
+                            Image::F32(inner) => (Histogram::with_bins(inner, bins.unwrap_or(histogram::level_bins::<f32>(inner.maxc))), inner.maxc),
+                        };
+
+                        // print the level each bin is centered on, with the counts of each channel
+                        // this goes to stderr so it doesn't mix with an image written to stdout
+                        eprintln!("level\tred\tgreen\tblue\tluma");
+                        for bin in 0..histogram.bins() {
+                            let level = bin as f64 * maxc as f64 / (histogram.bins() - 1) as f64;
+                            eprintln!("{}\t{}\t{}\t{}\t{}", level, histogram.red[bin], histogram.green[bin], histogram.blue[bin], histogram.luma[bin]);
+                        }
+                        skip_next = true; // skip the next argument since it's the mode
+                    }
+
+                    Some(Some(("chart", bins))) => {
+                        // the chart has a column for each bin, 256 unless given
+                        let bins = bins.unwrap_or(256);
+                        let chart = match &image {
+                            Image::U8(inner) => Histogram::with_bins(inner, bins).chart(200),
+                            Image::U16(inner) => Histogram::with_bins(inner, bins).chart(200),
+                            Image::F32(inner) => Histogram::with_bins(inner, bins).chart(200),
+                        };
+                        image = Image::U8(chart);
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the mode
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid histogram mode '{}'. Use print or chart, optionally with the number of bins from 2 to 65536, such as print or chart,64.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No histogram mode specified.");
+                    }
+                }
+            }
+
+            "--equalize" => {
+                // spread the tones of the image evenly
+                transform!(image, histogram::equalize);
+                write_image_on_completion = true;
+            }
+
+            "--match-histogram" => {
+                // match the tones of the image to a reference image
+                // the argument after this one should be the reference image
+                let reference = args.get(i + 1).map(|path| File::open(path).map_err(PpmError::from).and_then(|file| read_any_image(&mut BufReader::new(file))));
+                match reference {
+                    Some(Ok(reference)) => {
+                        transform!(image, |inner| match &reference {
+                            Image::U8(reference) => histogram::match_histogram(inner, reference),
+                            Image::U16(reference) => histogram::match_histogram(inner, reference),
+                            Image::F32(reference) => histogram::match_histogram(inner, reference),
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the reference image
+                    }
+
+                    Some(Err(error)) => {
+                        eprintln!("Error reading reference image '{}': {}", args[i + 1], error);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No reference image specified.");
+                    }
+                }
+            }
+
+            "--clahe" => {
+                // equalize the tones of each tile of the image, limiting the contrast
+                // the argument after this one should be the tile size, and optionally the clip limit
+                match args.get(i + 1).map(|settings| parse_numbers(settings, 1).or_else(|| parse_numbers(settings, 2))) {
+                    Some(Some(settings)) if settings[0] >= 1.0 && settings[0].fract() == 0.0 && settings.get(1).is_none_or(|&clip_limit| clip_limit >= 0.0) => {
+                        let (tile_size, clip_limit) = (settings[0] as u32, settings.get(1).map_or(2.0, |&clip_limit| clip_limit as f32));
+                        transform!(image, |inner| histogram::clahe(inner, tile_size, clip_limit));
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the settings
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid CLAHE settings '{}'. Use the tile size in pixels and optionally the clip limit, such as 64 or 64,2.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No CLAHE tile size specified.");
+                    }
+                }
+            }
+
+            "-rl" | "--rotate-left" => {
+                // // rotate the image counter-clockwise
+                // transform!(image, imageactions::rotate_left);
+
+                transform!(image, imageactions::rotate_left);
+                write_image_on_completion = true;
+            }
+
+            "-rr" | "--rotate-right" => {
+                // rotate the image clockwise
+                transform!(image, imageactions::rotate_right);
+                write_image_on_completion = true;
+            }
+
+            "-r180" | "--rotate-180" => {
+                // rotate the image 180 degrees
+                transform!(image, imageactions::rotate_180);
+                write_image_on_completion = true;
+            }
+
+            "-ra" | "--rotate" => {
+                // rotate the image by any angle
+                // the argument after this one should be the angle in degrees
+                match args.get(i + 1).map(|degrees| degrees.parse::<f32>()) {
+                    Some(Ok(degrees)) if degrees.is_finite() => {
+                        transform!(image, |inner| {
+                            let background = fill_color(fill, inner.maxc);
+                            imageactions::rotate(inner, degrees, interpolation, background, expand)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the angle
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid angle '{}'. It must be a number of degrees, such as 1.7 or -90.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No angle specified.");
+                    }
+                }
+            }
+
+            "-af" | "--affine" => {
+                // warp the image with an affine matrix
+                // the argument after this one should be the six numbers of the matrix
+                match args.get(i + 1).map(|matrix| parse_numbers(matrix, 6)) {
+                    // the matrix must be invertible, or every pixel would come from the same line of the image
+                    Some(Some(m)) if m[0] * m[4] - m[1] * m[3] != 0.0 => {
+                        let (width, height) = warp_size.unwrap_or(image.dimensions());
+                        transform!(image, |inner| {
+                            let background = fill_color(fill, inner.maxc);
+                            imageactions::warp_affine(inner, [[m[0], m[1], m[2]], [m[3], m[4], m[5]]], width, height, interpolation, background)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the matrix
+                    }
+
+                    Some(_) => {
+                        eprintln!("Invalid affine matrix '{}'. It must be six numbers a,b,c,d,e,f that can be inverted, such as 1,0.3,0,0,1,0.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No affine matrix specified.");
+                    }
+                }
+            }
+
+            "-ps" | "--perspective" => {
+                // correct the perspective by moving four corners
+                // the argument after this one should be the corners
+                let (image_width, image_height) = image.dimensions();
+                match args.get(i + 1).map(|corners| parse_perspective(corners, warp_size, image_width, image_height)) {
+                    Some(Some((matrix, (width, height)))) => {
+                        transform!(image, |inner| {
+                            let background = fill_color(fill, inner.maxc);
+                            imageactions::warp_perspective(inner, matrix, width, height, interpolation, background)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the corners
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid corners '{}'. They must be 8 numbers x1,y1,...,x4,y4, or 16 to give where they go, with no three points on a line.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No corners specified.");
+                    }
+                }
+            }
+
+            "-tp" | "--transpose" => {
+                // flip the image over its main diagonal
+                transform!(image, imageactions::transpose);
+                write_image_on_completion = true;
+            }
+
+            "-tv" | "--transverse" => {
+                // flip the image over its anti-diagonal
+                transform!(image, imageactions::transverse);
+                write_image_on_completion = true;
+            }
+
+            "--expand" | "--single-channel" | "--normalize" | "--pbm" => {
+                // the rotation canvas, grayscale output, kernel and bitmap options were already read. Skip
+                continue;
+            }
+
+            "-s" | "--shrink" => {
+                // shrink the image by 2x
+                transform!(image, imageactions::half_size);
+                write_image_on_completion = true;
+            }
+
+            "-d" | "--double" => {
+                // double the size of the image
+                transform!(image, imageactions::double_size);
+                write_image_on_completion = true;
+            }
+
+            "-db" | "--double-bilinear" => {
+                // double the size of the image by using bicubic interpolation
+                transform!(image, imageactions::double_bilinear);
+                write_image_on_completion = true;
+            }
+
+            "-rs" | "--resize" | "-sc" | "--scale" => {
+                // resize the image to any size
+                // the argument after this one should be the new size or scale
+                let (width, height) = image.dimensions();
+                let scale = matches!(args[i].as_str(), "-sc" | "--scale");
+                let size = args.get(i + 1).map(|size| match scale {
+                    true => parse_scale(size, width, height),
+                    false => parse_size(size, width, height, aspect),
+                });
+                match size {
+                    Some(Some((new_width, new_height))) => {
+                        transform!(image, |inner| imageactions::resize(inner, new_width, new_height, filter));
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the size
+                    }
+
+                    Some(None) if scale => {
+                        eprintln!("Invalid scale '{}'. Use a percentage such as 37% or a factor such as 0.37, which doesn't make the image more than {} pixels.", args[i + 1], u32::MAX);
+                        process::exit(1);
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid size '{}'. Use WxH, Wx or xH, such as 800x600, of no more than {} pixels.", args[i + 1], u32::MAX);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No size specified.");
+                    }
+                }
+            }
+
+            "-cr" | "--crop" => {
+                // crop the image to a region
+                // the argument after this one should be the region
+                match args.get(i + 1).map(|region| parse_region(region)) {
+                    Some(Some((x, y, width, height))) => {
+                        transform!(image, |inner| imageactions::crop(inner, x, y, width, height));
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the region
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid crop region '{}'. Use WxH+X+Y, such as 640x480+10+20.", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No crop region specified.");
+                    }
+                }
+            }
+
+            "-pd" | "--pad" | "-bd" | "--border" => {
+                // pad the sides of the image, borders always use the fill color
+                // the argument after this one should be the sizes of the sides
+                let border = matches!(args[i].as_str(), "-bd" | "--border");
+                let mode = if border { "constant" } else { edge };
+                match args.get(i + 1).map(|sides| parse_sides(sides)) {
+                    Some(Some([top, right, bottom, left])) => {
+                        transform!(image, |inner| {
+                            let edge = edge_mode(mode, fill, inner.maxc);
+                            match imageactions::pad(inner, top, right, bottom, left, edge) {
+                                Some(padded) => padded,
+                                None => {
+                                    eprintln!("Invalid sizes '{}'. The padded image can't have more than {} pixels.", args[i + 1], u32::MAX);
+                                    process::exit(1);
+                                }
+                            }
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the sizes
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid sizes '{}'. Use one size, two sizes (vertical,horizontal), or four sizes (top,right,bottom,left).", args[i + 1]);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No sizes specified.");
+                    }
+                }
+            }
+
+            "-cv" | "--canvas" => {
+                // change the size of the canvas
+                // the argument after this one should be the new size
+                let size = args.get(i + 1).map(|size| size.split_once('x').and_then(|(width, height)| checked_size((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))));
+                match size {
+                    Some(Some((width, height))) => {
+                        transform!(image, |inner| {
+                            let edge = edge_mode(edge, fill, inner.maxc);
+                            imageactions::extend_canvas(inner, width, height, anchor, edge)
+                        });
+                        write_image_on_completion = true;
+                        skip_next = true; // skip the next argument since it's the size
+                    }
+
+                    Some(None) => {
+                        eprintln!("Invalid canvas size '{}'. Use WxH, such as 800x600, of no more than {} pixels.", args[i + 1], u32::MAX);
+                        process::exit(1);
+                    }
+
+                    // if there are no arguments after this one, then print an error message
+                    None => {
+                        eprintln!("No canvas size specified.");
+                    }
+                }
+            }
+
+            "--filter" | "--aspect" | "--edge" | "--fill" | "--anchor" | "--interpolation" | "--warp-size" | "--lut-interpolation" | "--canny-thresholds" | "--element" => {
+                // the resize, padding, warp, LUT, edge detection and morphology options were already read, skip the value
+                skip_next = true;
+            }
+
+            "-fh" | "--flip-horizontal" => {
+                // flip the image horizontally
+                transform!(image, imageactions::flip_horizontal);
+                write_image_on_completion = true;
+            }
+
+            "-fv" | "--flip-vertical" => {
+                // flip the image vertically
+                transform!(image, imageactions::flip_vertical);
+                write_image_on_completion = true;
+            }
+
+            "-ir" | "--isolate-red" => {
+                // isolate the red channel
+                modify!(image, isolate_channel, Channel::Red);
+                write_image_on_completion = true;
+            }
+
+            "-ig" | "--isolate-green" => {
+                // isolate the green channel
+                modify!(image, isolate_channel, Channel::Green);
+                write_image_on_completion = true;
+            }
+
+            "-ib" | "--isolate-blue" => {
+                // isolate the blue channel
+                modify!(image, isolate_channel, Channel::Blue);
+                write_image_on_completion = true;
+            }
+
+            "-Dr" | "--delete-red" => {
+                // delete the red channel
+                modify!(image, remove_channel, Channel::Red);
+                write_image_on_completion = true;
+            }
+
+            "-Dg" | "--delete-green" => {
+                // delete the green channel
+                modify!(image, remove_channel, Channel::Green);
+                write_image_on_completion = true;
+            }
+
+            "-Db" | "--delete-blue" => {
+                // delete the blue channel
+                modify!(image, remove_channel, Channel::Blue);
+                write_image_on_completion = true;
+            }
+
+            "-o" | "--output" => {
+                // the output path was already read, skip it
+                skip_next = true;
+            }
+
+            "-h" | "--help" | "-t" | "--time" => {
+                // help and time options are handled before the image is processed. Skip
+                continue;
+            }
+
+            _ => {
+                if report_unknown {
+                    eprintln!("Unknown option '{}'. Use option -h to print the help menu.", args[i]);
+                }
+            }
+        }
+    }
+
+    (image, write_image_on_completion)
 }
  
 fn main() {
@@ -72,87 +1921,98 @@ fn main() {
 
     /* Open Input File */
     let input_file_path = Path::new(&args[1]); // create a new path to represent the input file
+    let read_from_stdin = args[1] == "-"; // a path of '-' means the image is read from stdin
 
-    // open the file
-    let input_file =  match File::open(&input_file_path) {
-        Ok(file) => file, // set input_file to the file if the file exists
-        Err(_) => { // otherwise exit with an error message
-            eprintln!("Error opening file. Make sure the specified file exists.");
-            process::exit(1);
-        }
+    // create a buffered reader to read the file, or stdin
+    let mut reader: Box<dyn BufRead> = match read_from_stdin {
+        true => Box::new(io::stdin().lock()),
+        false => match File::open(input_file_path) {
+            Ok(file) => Box::new(BufReader::new(file)), // read from the file if the file exists
+            Err(_) => { // otherwise exit with an error message
+                eprintln!("Error opening file. Make sure the specified file exists.");
+                process::exit(1);
+            }
+        },
     };
 
-    let mut reader = BufReader::new(input_file); // create a buffered reader to read the file
-     
-    // create a PPM object which represents the original image in memory, and is what will be read and modified by the program
-    let mut image: PPM = PPM::new();
-
-    // process header information and get the end location of the header
-    let header_length = parse_header(&mut reader, &mut image);
+    // PFM images store floating point samples, so they are read into a different type of image
+    let is_pfm = match reader.fill_buf() {
+        Ok(bytes) => bytes.starts_with(b"PF") || bytes.starts_with(b"Pf"),
+        Err(_) => false,
+    };
 
     // if no options were used, print image header information
     if args.len() == 2 {
-        // print image width and height
-        println!("Image Dimensions: {} x {}", image.width, image.height);
+        match is_pfm {
+            true => {
+                let image = match read_pfm(&mut reader) {
+                    Ok(image) => image,
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        process::exit(1);
+                    }
+                };
 
-        // print format subtype
-        println!("Format Subtype: {}", image.magic);
+                // print image width and height
+                println!("Image Dimensions: {} x {}", image.width, image.height);
 
-        // print bit depth
-        let image_bit: &str = match image.maxc {
-            255 => "8-bit",
-            65535 => "16-bit",
-            _ => "Unknown",
-        };
-        println!("Bit Depth: {}", image_bit);
-    }
+                // print format subtype
+                println!("Format Subtype: {}", image.magic);
 
-    // if there are additional arguments, then perform image operations
-    if args.len() > 2 {
-        
-        // verify that the image is of P6 format
-        if image.magic != "P6" {
-            eprintln!("This image is in {} format. This tool only supports P6 format.", image.magic);
-            process::exit(1);
-        }
+                // PFM samples are always 32-bit floats
+                println!("Bit Depth: 32-bit float");
+            }
 
-        // read image pixel data
-        read_image_data(&mut reader, &mut image, header_length);
+            false => {
+                // only the header of the first image is read
+                let mut image: PPM = PPM::new();
+                if let Err(error) = parse_header(&mut reader, &mut image) {
+                    eprintln!("{}", error);
+                    process::exit(1);
+                }
 
-        // TEMP
-        // create new file to write to
-        let filename_no_extension = input_file_path.file_stem().unwrap_or("output".as_ref()).to_str().unwrap_or("output");
-        let filename = format!("{}_modified.ppm", filename_no_extension);
-        let mut output_file_path = input_file_path.parent().unwrap().join(filename);
+                // print image width and height
+                println!("Image Dimensions: {} x {}", image.width, image.height);
 
-        // for each additional argument after the input file, parse the argument and perform the specified operation
-        let mut skip_next = false;
-        let mut write_image_on_completion = false;
+                // print format subtype
+                println!("Format Subtype: {}", image.magic);
 
-        for i in 2..args.len() {
-            if skip_next {
-                skip_next = false;
-                continue;
+                // print bit depth, which is the number of bits needed to store the max color value
+                let image_bit = match image.magic.as_str() {
+                    "P1" | "P4" => 1,
+                    _ => 32 - image.maxc.leading_zeros(),
+                };
+                println!("Bit Depth: {}-bit", image_bit);
+
+                // print max color value
+                println!("Max Color Value: {}", image.maxc);
+
+                // print the tuple type of PAM images, which shows if there is an alpha channel
+                if image.magic == "P7" {
+                    println!("Tuple Type: {}", image.tupltype);
+                }
             }
-            match args[i].as_ref() {
+        }
+    }
+
+    // if there are additional arguments, then perform image operations
+    if args.len() > 2 {
 
+        // find the output path and print the help text before processing, since they apply to every frame
+        let mut output_file_path: Option<PathBuf> = None;
+        for i in 2..args.len() {
+            match args[i].as_ref() {
                 "-h" | "--help" => {
                     // print the help text
                     print_help_text();
                 }
 
-                "-c" | "--copy" => {
-                    // create image copy
-                    write_image_on_completion = true;
-                }
-
                 "-o" | "--output" => {
                     // change the output path name
                     // the argument after this one should be the new output path
                     match args.get(i + 1) {
                         Some(path) => {
-                            output_file_path = Path::new(path).to_path_buf();
-                            skip_next = true; // skip the next argument since it's the output path
+                            output_file_path = Some(Path::new(path).to_path_buf());
                         }
 
                         // if there are no arguments after this one, then print an error message
@@ -162,119 +2022,96 @@ fn main() {
                     }
                 }
 
-                "-n" | "--negative" => {
-                    // convert image to negative
-                    image.negative();
-                    write_image_on_completion = true;
-                }
-
-                "-g" | "--grayscale" => {
-                    // convert image to grayscale
-                    image.grayscale();
-                    write_image_on_completion = true;
-                }
-
-                "-rl" | "--rotate-left" => {
-                    // // rotate the image counter-clockwise
-                    // image = imageactions::rotate_left(image);
-
-                    image = imageactions::rotate_left(image);
-                    write_image_on_completion = true;
-                }
-
-                "-rr" | "--rotate-right" => {
-                    // rotate the image clockwise
-                    image = imageactions::rotate_right(image);
-                    write_image_on_completion = true;
-                }
-
-                "-s" | "--shrink" => {
-                    // shrink the image by 2x
-                    image = imageactions::half_size(image);
-                    write_image_on_completion = true;
-                }
-
-                "-d" | "--double" => {
-                    // double the size of the image
-                    image = imageactions::double_size(image);
-                    write_image_on_completion = true;
-                }
-
-                "-db" | "--double-bilinear" => {
-                    // double the size of the image by using bicubic interpolation
-                    image = imageactions::double_bilinear(image);
-                    write_image_on_completion = true;
-                }
-
-                "-fh" | "--flip-horizontal" => {
-                    // flip the image horizontally
-                    image = imageactions::flip_horizontal(image);
-                    write_image_on_completion = true;
-                }
-
-                "-fv" | "--flip-vertical" => {
-                    // flip the image vertically
-                    image = imageactions::flip_vertical(image);
-                    write_image_on_completion = true;
-                }
+                _ => {}
+            }
+        }
 
-                "-ir" | "--isolate-red" => {
-                    // isolate the red channel
-                    image.isolate_channel(Channel::Red);
-                    write_image_on_completion = true;
-                }
+        // the output is opened once the first frame is processed, since the default extension depends on the output format
+        let mut output = Output {
+            path: output_file_path,
+            input_file_path: input_file_path.to_path_buf(),
+            read_from_stdin,
+            opened: None,
+            frames_written: 0,
+        };
 
-                "-ig" | "--isolate-green" => {
-                    // isolate the green channel
-                    image.isolate_channel(Channel::Green);
-                    write_image_on_completion = true;
-                }
+        // rotations and vertical flips of a raw image file are read through a memory map, so that huge images fit in memory
+        #[cfg(feature = "mmap")]
+        let mapped = match read_from_stdin || is_pfm {
+            true => None,
+            false => process_mapped(&args, input_file_path, &mut output),
+        };
+        #[cfg(not(feature = "mmap"))]
+        let mapped: Option<Result<(), PpmError>> = None;
 
-                "-ib" | "--isolate-blue" => {
-                    // isolate the blue channel
-                    image.isolate_channel(Channel::Blue);
-                    write_image_on_completion = true;
-                }
+        // images are processed a few rows at a time when every effect allows it, so that huge images fit in memory
+        let streamed = match mapped {
+            Some(result) => Some(result),
+            None if !is_pfm && is_row_local(&args) => Some(stream_frames(&mut reader, &args, &mut output)),
+            None => None,
+        };
 
-                "-Dr" | "--delete-red" => {
-                    // delete the red channel
-                    image.remove_channel(Channel::Red);
-                    write_image_on_completion = true;
-                }
+        match streamed {
+            Some(Ok(())) => {}
+            Some(Err(error)) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
 
-                "-Dg" | "--delete-green" => {
-                    // delete the green channel
-                    image.remove_channel(Channel::Green);
-                    write_image_on_completion = true;
-                }
+            // otherwise every frame is read into memory all at once
+            None => {
+                // read every frame of the input, PFM images only have a single frame
+                let frames: Box<dyn Iterator<Item = Result<Image, PpmError>>> = match is_pfm {
+                    true => Box::new(std::iter::once(read_pfm(&mut reader).map(Image::F32))),
+                    false => Box::new(ImageStream::new(reader)),
+                };
 
-                "-Db" | "--delete-blue" => {
-                    // delete the blue channel
-                    image.remove_channel(Channel::Blue);
-                    write_image_on_completion = true;
-                }
+                for (frame_index, frame) in frames.enumerate() {
+                    let image = match frame {
+                        Ok(image) => image,
+                        Err(error) => {
+                            eprintln!("{}", error);
+                            process::exit(1);
+                        }
+                    };
 
-                "-t" | "--time" => {
-                    // time option, used for debugging and profiling. Skip
-                    continue;
-                }
+                    // apply the effect chain to the frame
+                    let (image, write_image_on_completion) = apply_effects(image, &args, frame_index == 0);
+                    if !write_image_on_completion {
+                        continue;
+                    }
 
-                _ => {
-                    println!("Unknown option '{}'. Use option -h to print the help menu.", args[i]);
+                    // write the frame, frames after the first are appended to form a stream
+                    let result = match &image {
+                        Image::U8(inner) => write_image(output.writer(&inner.magic), inner),
+                        Image::U16(inner) => write_image(output.writer(&inner.magic), inner),
+                        Image::F32(inner) => write_pfm(output.writer(&inner.magic), inner),
+                    };
+                    if let Err(error) = result {
+                        eprintln!("Error writing to output file. {}", error);
+                        process::exit(1);
+                    }
+                    output.frames_written += 1;
                 }
             }
         }
 
-        if write_image_on_completion {
-            write_image(&output_file_path, &image);
-            println!("Saved image as '{}'.", output_file_path.canonicalize().expect("Failed to resolve path.").display());
+        // only print where the image was saved if it wasn't written to stdout, since that would end up in the image data
+        if let Some((path, _)) = &output.opened {
+            if !write_to_stdout(path) {
+                match output.frames_written {
+                    1 => println!("Saved image as '{}'.", path.canonicalize().expect("Failed to resolve path.").display()),
+                    _ => println!("Saved {} frames as '{}'.", output.frames_written, path.canonicalize().expect("Failed to resolve path.").display()),
+                }
+            }
         }
 
         // print start time if the time option was used
         match start_time {
             Some(time) => {
+                // printed to stderr so that it doesn't end up in an image written to stdout
                 let duration = time.elapsed();
-                println!("Program completed in: {}ms", duration.as_millis());
+                eprintln!("Program completed in: {}ms", duration.as_millis());
             }
 
             None => {
@@ -282,4 +2119,4 @@ fn main() {
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,307 @@
+/*
+Contains the morphological operations, which change the shapes in an image by taking the
+darkest or brightest pixel under a structuring element placed on each pixel.
+
+Erosion takes the darkest pixel, so bright shapes shrink and dark ones grow, and dilation takes
+the brightest. The rest are built from those two. Each channel is done on its own, so grayscale
+and bitmap images work the same as color ones. On a bitmap, where the paper is white, erosion
+makes black text thicker and dilation makes it thinner.
+
+The darkest or brightest pixel along each row of the element is found with the van Herk/Gil-Werman
+algorithm, which takes the same time for any length, so large rectangles stay fast.
+*/
+
+use rayon::prelude::*;
+
+use crate::ppm::{PPM, Pixel, Sample};
+
+/*
+The StructuringElement type is the shape placed on each pixel by the morphological operations.
+It is centered on the pixel like a convolution kernel, and the pixels under its true cells are used.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructuringElement {
+    pub width: u32, // the number of columns, with the center column at width / 2
+    pub height: u32, // the number of rows, with the center row at height / 2
+    pub cells: Vec<bool>, // true for the cells that are part of the shape, one row after another
+}
+
+/**
+Returns the number of cells across an element reaching `radius` cells out from its center,
+or None if it doesn't fit in a u32.
+*/
+fn side(radius: u32) -> Option<u32> {
+    radius.checked_mul(2)?.checked_add(1)
+}
+
+impl StructuringElement {
+    // constructor
+    // returns None if either size is 0, there aren't width * height cells, or none of them are set
+    pub fn new(width: u32, height: u32, cells: Vec<bool>) -> Option<Self> {
+        match width > 0 && height > 0 && width.checked_mul(height).is_some_and(|count| cells.len() == count as usize) && cells.contains(&true) {
+            true => Some(StructuringElement { width, height, cells }),
+            false => None,
+        }
+    }
+
+    /**
+    Returns a rectangle reaching `radius_x` pixels to the sides of the center and `radius_y` pixels above and below.
+    Returns None if it would have more cells than a u32 can count.
+    */
+    pub fn rectangle(radius_x: u32, radius_y: u32) -> Option<Self> {
+        let (width, height) = (side(radius_x)?, side(radius_y)?);
+        StructuringElement::new(width, height, vec![true; width.checked_mul(height)? as usize])
+    }
+
+    /**
+    Returns a square reaching `radius` pixels out from the center, or None if it's too large.
+    */
+    pub fn square(radius: u32) -> Option<Self> {
+        StructuringElement::rectangle(radius, radius)
+    }
+
+    /**
+    Returns a disk with the given radius, holding the cells whose centers are within it,
+    or None if it's too large.
+    */
+    pub fn disk(radius: u32) -> Option<Self> {
+        let squared = radius as i64 * radius as i64;
+        StructuringElement::from_distance(radius, |x, y| x * x + y * y <= squared)
+    }
+
+    /**
+    Returns a cross, or plus sign, with arms reaching `radius` pixels out from the center,
+    or None if it's too large.
+    */
+    pub fn cross(radius: u32) -> Option<Self> {
+        StructuringElement::from_distance(radius, |x, y| x == 0 || y == 0)
+    }
+
+    /**
+    Builds a square element reaching `radius` pixels out from the center, with the cells
+    where `inside` is true for the x and y distance from the center. Returns None if it
+    would have more cells than a u32 can count.
+    */
+    fn from_distance(radius: u32, inside: impl Fn(i64, i64) -> bool) -> Option<Self> {
+        let size = side(radius)?;
+        let count = size.checked_mul(size)?;
+        let radius = radius as i64;
+        let cells = (0..count).map(|i| inside((i % size) as i64 - radius, (i / size) as i64 - radius)).collect();
+        Some(StructuringElement { width: size, height: size, cells })
+    }
+
+    /**
+    Returns the element turned halfway around its center, which dilation uses so that
+    opening and closing work with shapes that aren't symmetric.
+    */
+    fn reflected(&self) -> Self {
+        let mut cells = self.cells.clone();
+        cells.reverse();
+
+        // an even size has its center past the middle, so the reflected element gets an empty row or column to keep the same center
+        let (pad_x, pad_y) = ((1 - self.width % 2) as usize, (1 - self.height % 2) as usize);
+        let width = self.width as usize + pad_x;
+        let mut padded = vec![false; width * pad_y];
+        for row in cells.chunks(self.width as usize) {
+            padded.extend(std::iter::repeat_n(false, pad_x));
+            padded.extend_from_slice(row);
+        }
+        StructuringElement { width: width as u32, height: self.height + pad_y as u32, cells: padded }
+    }
+
+    /**
+    Returns the runs of set cells in each row, as the row and column of the first cell
+    relative to the center, and the number of cells in the run.
+    */
+    fn runs(&self) -> Vec<(i64, i64, usize)> {
+        let (center_x, center_y) = ((self.width / 2) as i64, (self.height / 2) as i64);
+        let mut runs = Vec::new();
+        for (y, row) in self.cells.chunks(self.width as usize).enumerate() {
+            let mut x = 0;
+            while x < row.len() {
+                let length = row[x..].iter().take_while(|&&cell| cell).count();
+                if length > 0 {
+                    runs.push((y as i64 - center_y, x as i64 - center_x, length));
+                }
+                x += length.max(1);
+            }
+        }
+        runs
+    }
+}
+
+/**
+Returns the darkest or brightest of each channel of two pixels.
+*/
+fn pick<S: Sample>(a: Pixel<S>, b: Pixel<S>, darkest: bool) -> Pixel<S> {
+    let pick = |a: S, b: S| match (a.to_f32() < b.to_f32()) == darkest {
+        true => a,
+        false => b,
+    };
+    Pixel { r: pick(a.r, b.r), g: pick(a.g, b.g), b: pick(a.b, b.b) }
+}
+
+/**
+Returns the darkest or brightest pixel of each run of `length` values, where the run for
+position i starts at i + `start`. Positions past the ends of the values are `outside`.
+
+Uses the van Herk/Gil-Werman algorithm. The values are split into blocks of `length`, and every
+run covers the end of one block and the start of the next, so taking the running result from the
+end of each block backwards and from the start of each block forwards gives every run with 3
+comparisons for each value, however long the runs are.
+*/
+fn sliding_pick<S: Sample>(values: &[Pixel<S>], start: i64, length: usize, darkest: bool, outside: Pixel<S>) -> Vec<Pixel<S>> {
+    // index j of the padded values is position j + start
+    let padded_length = values.len() + length - 1;
+    let value = |j: usize| {
+        let position = j as i64 + start;
+        match (0..values.len() as i64).contains(&position) {
+            true => values[position as usize],
+            false => outside,
+        }
+    };
+
+    let mut forward = Vec::with_capacity(padded_length);
+    for j in 0..padded_length {
+        forward.push(match j % length {
+            0 => value(j),
+            _ => pick(forward[j - 1], value(j), darkest),
+        });
+    }
+    let mut backward = vec![outside; padded_length];
+    for j in (0..padded_length).rev() {
+        backward[j] = match j % length == length - 1 || j == padded_length - 1 {
+            true => value(j),
+            false => pick(backward[j + 1], value(j), darkest),
+        };
+    }
+
+    (0..values.len()).map(|i| pick(backward[i], forward[i + length - 1], darkest)).collect()
+}
+
+/**
+Returns the darkest or brightest pixel under the element placed on each pixel.
+Pixels past the edges of the image are left out.
+*/
+fn extreme<S: Sample>(image: &PPM<S>, element: &StructuringElement, darkest: bool) -> Vec<Pixel<S>> {
+    let width = image.width as usize;
+    if image.pixels.is_empty() {
+        return Vec::new();
+    }
+
+    // a value that is never picked, used past the edges of the image
+    let limit = match (darkest, S::INTEGER) {
+        (true, true) => image.maxc as f32,
+        (true, false) => f32::INFINITY,
+        (false, true) => 0.0,
+        (false, false) => f32::NEG_INFINITY,
+    };
+    let outside = Pixel { r: S::from_f32(limit), g: S::from_f32(limit), b: S::from_f32(limit) };
+
+    let element = match darkest {
+        true => element.clone(),
+        false => element.reflected(),
+    };
+    let runs = element.runs();
+
+    // a full rectangle is separable, so the rows are done and then the columns
+    if !element.cells.contains(&false) {
+        let (run_y, run_x) = (runs[0].0, runs[0].1);
+        let rows = image.pixels.par_chunks(width).flat_map_iter(|row| sliding_pick(row, run_x, element.width as usize, darkest, outside)).collect::<Vec<Pixel<S>>>();
+        let columns = (0..width).into_par_iter().map(|x| {
+            let column = rows.iter().skip(x).step_by(width).copied().collect::<Vec<Pixel<S>>>();
+            sliding_pick(&column, run_y, element.height as usize, darkest, outside)
+        }).collect::<Vec<Vec<Pixel<S>>>>();
+        return (0..image.pixels.len()).into_par_iter().map(|index| columns[index % width][index / width]).collect();
+    }
+
+    // otherwise each row of the result combines the runs along the rows of the image under each row of the element
+    let mut pixels = vec![outside; image.pixels.len()];
+    pixels.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        for &(run_y, run_x, length) in &runs {
+            let source_y = y as i64 + run_y;
+            if !(0..image.height as i64).contains(&source_y) {
+                continue;
+            }
+            let source = &image.pixels[source_y as usize * width..(source_y as usize + 1) * width];
+            for (pixel, picked) in row.iter_mut().zip(sliding_pick(source, run_x, length, darkest, outside)) {
+                *pixel = pick(*pixel, picked, darkest);
+            }
+        }
+    });
+    pixels
+}
+
+/**
+Returns the difference of each channel of two sets of pixels, `a` minus `b`.
+*/
+fn difference<S: Sample>(a: &[Pixel<S>], b: &[Pixel<S>], maxc: u32) -> Vec<Pixel<S>> {
+    a.par_iter().zip(b.par_iter()).map(|(a, b)| Pixel {
+        r: S::from_f32_clamped(a.r.to_f32() - b.r.to_f32(), maxc),
+        g: S::from_f32_clamped(a.g.to_f32() - b.g.to_f32(), maxc),
+        b: S::from_f32_clamped(a.b.to_f32() - b.b.to_f32(), maxc),
+    }).collect()
+}
+
+/**
+Replaces each pixel with the darkest pixel under the element, which shrinks bright shapes
+and removes bright specks smaller than the element. The alpha channel is kept.
+*/
+pub fn erode<S: Sample>(mut image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    image.pixels = extreme(&image, element, true);
+    image
+}
+
+/**
+Replaces each pixel with the brightest pixel under the element, which grows bright shapes
+and removes dark specks smaller than the element. The alpha channel is kept.
+*/
+pub fn dilate<S: Sample>(mut image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    image.pixels = extreme(&image, element, false);
+    image
+}
+
+/**
+Erodes and then dilates the image, which removes bright specks and thin bright lines that
+the element doesn't fit in, while keeping the size of the bright shapes that are left.
+*/
+pub fn open<S: Sample>(image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    dilate(erode(image, element), element)
+}
+
+/**
+Dilates and then erodes the image, which fills dark specks and gaps that the element
+doesn't fit in, while keeping the size of the dark shapes that are left.
+*/
+pub fn close<S: Sample>(image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    erode(dilate(image, element), element)
+}
+
+/**
+Returns the image minus its opening, which keeps only the bright details smaller than the
+element, such as text on an unevenly lit page when it is light on dark.
+*/
+pub fn top_hat<S: Sample>(mut image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    let opened = open(image.clone(), element);
+    image.pixels = difference(&image.pixels, &opened.pixels, image.maxc);
+    image
+}
+
+/**
+Returns the closing of the image minus the image, which keeps only the dark details smaller
+than the element, such as dark text on an unevenly lit page, as bright shapes on black.
+*/
+pub fn black_hat<S: Sample>(mut image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    let closed = close(image.clone(), element);
+    image.pixels = difference(&closed.pixels, &image.pixels, image.maxc);
+    image
+}
+
+/**
+Returns the dilation of the image minus its erosion, which outlines the edges of the shapes.
+*/
+pub fn gradient<S: Sample>(mut image: PPM<S>, element: &StructuringElement) -> PPM<S> {
+    let (dilated, eroded) = (extreme(&image, element, false), extreme(&image, element, true));
+    image.pixels = difference(&dilated, &eroded, image.maxc);
+    image
+}
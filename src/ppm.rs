@@ -20,39 +20,344 @@ represents the g value, and third represents the b value.
 */
 
 use std::fmt;
+use crate::error::PpmError;
+use crate::color::{Hsl, Lab, srgb_to_linear, linear_to_srgb};
+use crate::lut::{Lut, LutInterpolation};
 use rayon::prelude::*;
 
+/*
+The Sample trait is implemented by the types a single channel of a pixel can be stored as.
+Integer samples (u8 and u16) range from 0 to the max color value of the image, while floating
+point samples (f32) are nominally 0.0 to 1.0 but may go above 1.0 for HDR images.
+Calculations on samples are done in f32, and converting back follows the same rules as an
+`as` cast, so integer results are truncated and clamped to the range of the type.
+*/
+pub trait Sample: Copy + Default + PartialEq + Send + Sync + fmt::Display + 'static {
+    // true if the samples are whole levels from 0 to the max color value, so effects can use a table of every level
+    const INTEGER: bool;
+
+    /**
+    Converts the sample to f32 for calculations.
+    */
+    fn to_f32(self) -> f32;
+
+    /**
+    Converts the result of a calculation back to a sample.
+    */
+    fn from_f32(value: f32) -> Self;
+
+    /**
+    Converts the result of a calculation that can overshoot, such as a filter with
+    negative weights, back to a sample. Integer samples are rounded and clamped to
+    the max color value, while floating point samples are kept as they are.
+    */
+    fn from_f32_clamped(value: f32, maxc: u32) -> Self;
+}
+
+impl Sample for u8 {
+    const INTEGER: bool = true;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value as u8
+    }
+
+    fn from_f32_clamped(value: f32, maxc: u32) -> Self {
+        value.round().clamp(0.0, maxc as f32) as u8
+    }
+}
+
+impl Sample for u16 {
+    const INTEGER: bool = true;
+
+    fn to_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value as u16
+    }
+
+    fn from_f32_clamped(value: f32, maxc: u32) -> Self {
+        value.round().clamp(0.0, maxc as f32) as u16
+    }
+}
+
+impl Sample for f32 {
+    const INTEGER: bool = false;
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn from_f32_clamped(value: f32, _maxc: u32) -> Self {
+        value
+    }
+}
+
+/*
+The IntegerSample trait is implemented by the sample types that Netpbm images can be
+read into and written from. 8-bit samples (u8) hold max color values up to 255, and
+16-bit samples (u16) hold every max color value up to 65535. Samples are converted
+through u16, which is the widest sample in a Netpbm file.
+*/
+pub trait IntegerSample: Sample {
+    /**
+    The largest max color value this type of sample can hold.
+    */
+    const MAX: u32;
+
+    /**
+    Converts a sample read from a file. The value must not be above MAX.
+    */
+    fn from_u16(value: u16) -> Self;
+
+    /**
+    Converts the sample so it can be written to a file.
+    */
+    fn to_u16(self) -> u16;
+}
+
+impl IntegerSample for u8 {
+    const MAX: u32 = 255;
+
+    fn from_u16(value: u16) -> Self {
+        value as u8
+    }
+
+    fn to_u16(self) -> u16 {
+        self as u16
+    }
+}
+
+impl IntegerSample for u16 {
+    const MAX: u32 = 65535;
+
+    fn from_u16(value: u16) -> Self {
+        value
+    }
+
+    fn to_u16(self) -> u16 {
+        self
+    }
+}
+
+#[derive(Copy, Clone)]
 pub enum Channel {
     Red,
     Green,
     Blue
 }
 
+/*
+The GrayscaleMode type picks how the r, g, and b values are combined into one gray level.
+The luma modes weigh the channels by how bright they look, so that greens aren't too dark,
+and luminance does the same on linear light instead of the gamma encoded values.
+*/
 #[derive(Copy, Clone)]
-pub struct Pixel {
-    pub r: u16, // red pixel value
-    pub g: u16, // green pixel value
-    pub b: u16 // blue pixel value
+pub enum GrayscaleMode {
+    Average, // the average of r, g, and b
+    Rec601, // Rec. 601 luma, 0.299 r + 0.587 g + 0.114 b
+    Rec709, // Rec. 709 luma, 0.2126 r + 0.7152 g + 0.0722 b
+    Luminance, // Rec. 709 weights on linear light, decoding and encoding the samples as sRGB
+    Lightness, // the average of the largest and smallest channel
+    Channel(Channel), // a single channel
+}
+
+/*
+The Levels type describes a levels adjustment. Samples between the input black and
+white points are stretched to fill the output range, and the gamma bends the middle.
+Every value is a fraction of the max color value from 0.0 to 1.0, so the same levels
+work on images of any depth.
+*/
+#[derive(Copy, Clone, Debug)]
+pub struct Levels {
+    pub input_black: f32, // samples at or below this become the output black
+    pub input_white: f32, // samples at or above this become the output white
+    pub gamma: f32, // above 1.0 brightens the middle, below 1.0 darkens it
+    pub output_black: f32, // the darkest output level
+    pub output_white: f32, // the brightest output level
 }
 
-impl Pixel {
+impl Default for Levels {
+    fn default() -> Self {
+        Levels { input_black: 0.0, input_white: 1.0, gamma: 1.0, output_black: 0.0, output_white: 1.0 }
+    }
+}
+
+impl Levels {
+    /**
+    Returns the adjusted value of a sample from 0.0 to 1.0.
+    */
+    pub fn apply(&self, value: f32) -> f32 {
+        let range = (self.input_white - self.input_black).max(f32::EPSILON);
+        let position = ((value - self.input_black) / range).clamp(0.0, 1.0);
+        self.output_black + position.powf(1.0 / self.gamma) * (self.output_white - self.output_black)
+    }
+}
+
+/*
+The Curve type is a tone curve through control points, which are pairs of input and
+output levels from 0.0 to 1.0. The points are joined with a monotone cubic spline, so
+the curve is smooth but never overshoots between points, and a rising set of points
+never gives a curve that dips. Inputs outside of the points keep the nearest output.
+*/
+#[derive(Clone, Debug)]
+pub struct Curve {
+    points: Vec<(f32, f32)>, // the control points, sorted by input level
+    tangents: Vec<f32>, // the slope of the curve at each control point
+}
+
+impl Curve {
+    /**
+    Creates a curve through the control points, which must be in order of increasing input
+    level. Returns None if there are fewer than 2 points, or the inputs don't increase.
+    */
+    pub fn new(points: &[(f32, f32)]) -> Option<Self> {
+        if points.len() < 2 || points.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+            return None;
+        }
+
+        // start with the average slope of the lines on either side of each point,
+        // or a flat tangent where the curve changes direction
+        let slopes = points.windows(2).map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0)).collect::<Vec<f32>>();
+        let mut tangents = vec![0.0; points.len()];
+        tangents[0] = slopes[0];
+        tangents[points.len() - 1] = slopes[slopes.len() - 1];
+        for i in 1..points.len() - 1 {
+            if slopes[i - 1] * slopes[i] > 0.0 {
+                tangents[i] = (slopes[i - 1] + slopes[i]) / 2.0;
+            }
+        }
+
+        // limit the tangents so that each piece stays monotone (Fritsch-Carlson)
+        for (i, &slope) in slopes.iter().enumerate() {
+            if slope == 0.0 {
+                tangents[i] = 0.0;
+                tangents[i + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (tangents[i] / slope, tangents[i + 1] / slope);
+            let length = a.hypot(b);
+            if length > 3.0 {
+                tangents[i] = 3.0 / length * a * slope;
+                tangents[i + 1] = 3.0 / length * b * slope;
+            }
+        }
+
+        Some(Curve { points: points.to_vec(), tangents })
+    }
+
+    /**
+    Returns the output level of the curve for an input level.
+    */
+    pub fn apply(&self, value: f32) -> f32 {
+        let last = self.points.len() - 1;
+        if value <= self.points[0].0 {
+            return self.points[0].1;
+        }
+        if value >= self.points[last].0 {
+            return self.points[last].1;
+        }
+
+        // find the piece of the curve the value is on, and interpolate it with a cubic hermite spline
+        let i = self.points.partition_point(|point| point.0 <= value) - 1;
+        let ((x0, y0), (x1, y1)) = (self.points[i], self.points[i + 1]);
+        let width = x1 - x0;
+        let t = (value - x0) / width;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * width * self.tangents[i]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * width * self.tangents[i + 1]
+    }
+}
+
+// the number of bins in the histograms used by auto levels, enough for every level of a 16-bit image
+const LEVEL_BINS: usize = 65536;
+
+/**
+Returns the lowest and highest level of a histogram, as fractions from 0.0 to 1.0, after
+ignoring the `clip` fraction of the samples at each end.
+*/
+fn clipped_range(histogram: &[u64], clip: f32) -> (f32, f32) {
+    let total = histogram.iter().sum::<u64>();
+    let limit = (total as f64 * clip.clamp(0.0, 0.5) as f64) as u64;
+
+    // count up from the bottom, and down from the top, until more than the clipped samples are passed
+    let passed = |bins: &mut dyn Iterator<Item = &u64>| bins.scan(0, |count, &bin| {
+        *count += bin;
+        Some(*count)
+    }).position(|count| count > limit).unwrap_or(0);
+    let low = passed(&mut histogram.iter());
+    let high = histogram.len() - 1 - passed(&mut histogram.iter().rev());
+    let bins = (LEVEL_BINS - 1) as f32;
+    (low as f32 / bins, high as f32 / bins)
+}
+
+#[derive(Copy, Clone)]
+pub struct Pixel<S: Sample = u16> {
+    pub r: S, // red pixel value
+    pub g: S, // green pixel value
+    pub b: S // blue pixel value
+}
+
+impl<S: Sample> Pixel<S> {
     // constructor
     pub fn new() -> Self {
         Pixel {
-            r: 0,
-            g: 0,
-            b: 0
+            r: S::default(),
+            g: S::default(),
+            b: S::default()
         }
     }
+
+    /**
+    Returns the average of the r, g, and b values.
+    */
+    pub fn average(&self) -> S {
+        S::from_f32((self.r.to_f32() + self.g.to_f32() + self.b.to_f32()) / 3.0)
+    }
+
+    /**
+    Returns the r, g, and b values scaled from 0.0 to 1.0, for an image with the given
+    max color value. The color module converts these to other color models.
+    */
+    pub fn to_rgb(&self, maxc: u32) -> [f32; 3] {
+        let maxc = maxc as f32;
+        [self.r.to_f32() / maxc, self.g.to_f32() / maxc, self.b.to_f32() / maxc]
+    }
+
+    /**
+    Creates a pixel from r, g, and b values from 0.0 to 1.0, rounding and clamping
+    them to the levels of an image with the given max color value.
+    */
+    pub fn from_rgb(rgb: [f32; 3], maxc: u32) -> Self {
+        let level = |value: f32| S::from_f32_clamped(value * maxc as f32, maxc);
+        Pixel { r: level(rgb[0]), g: level(rgb[1]), b: level(rgb[2]) }
+    }
+}
+
+impl<S: Sample> Default for Pixel<S> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl fmt::Display for Pixel {
+impl<S: Sample> fmt::Display for Pixel<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "({}, {}, {})", self.r, self.g, self.b)
     }
 }
 
-impl fmt::Debug for Pixel {
+impl<S: Sample> fmt::Debug for Pixel<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Pixel {{r: {}, g: {}, b: {}}}", self.r, self.g, self.b)
     }
@@ -64,16 +369,37 @@ It contains all the information stored in the file header,
 such as width, height, maxc, and the magic text, or version,
 of the PPM file. It also contains the pixels of the image,
 represented by a 1 dimensional array of pixels.
+
+Grayscale (P2, P5) and bitmap (P1, P4) images use the same
+representation, with the r, g, and b values of each pixel set
+to the same level, so every effect works on every subtype.
+
+PAM (P7) images may also have an alpha channel. It is stored
+separately from the pixels, one value per pixel in the same
+order, so that color effects leave it untouched.
+
+PFM images are stored as PPM<f32>, with a max color value of 1.
+Every effect works the same on integer and floating point images.
 */
-pub struct PPM {
+#[derive(Clone)]
+#[allow(clippy::upper_case_acronyms)]
+pub struct PPM<S: Sample = u16> {
     pub width: u32, // image width
     pub height: u32, // image height
-    pub maxc: u32, // max color value (usually 255)
-    pub magic: String, // magic number (usually P6)
-    pub pixels: Vec<Pixel>, // 1d pixel array
+    pub maxc: u32, // max color value from 1 to 65535 (usually 255, always 255 for P1 and P4 bitmaps, always 1 for PFM)
+    pub magic: String, // magic number (P1 to P7, PF or Pf, usually P6)
+    pub pixels: Vec<Pixel<S>>, // 1d pixel array
+    pub alpha: Option<Vec<S>>, // 1d alpha array, if the image has an alpha channel
+    pub tupltype: String, // PAM tuple type without the alpha suffix (GRAYSCALE or RGB)
 }
 
-impl PPM {
+impl<S: Sample> Default for PPM<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Sample> PPM<S> {
     // constructor
     pub fn new() -> Self {
         PPM {
@@ -81,14 +407,16 @@ impl PPM {
             height: 0,
             maxc: 0,
             magic: String::new(),
-            pixels: Vec::new()
+            pixels: Vec::new(),
+            alpha: None,
+            tupltype: String::new()
         }
     }
 
     /**
     Returns the pixel at the given x and y coordinate.
     */
-    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&Pixel> {
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&Pixel<S>> {
         // if x or y are negative, then it will wrap to the other side of the image
         if (x >= self.width) || (y >= self.height) {
             None
@@ -97,7 +425,7 @@ impl PPM {
         }
     }
 
-    /**
+    /*
     Sets the pixel at the given x and y coordinate.
     */
     // pub fn set_pixel(&mut self, x: u32, y: u32, pixel: &Pixel) {
@@ -106,10 +434,161 @@ impl PPM {
     // }
 
     /**
-    Returns the total number of pixels in the image.
+    Returns the alpha value at the given x and y coordinate.
+    Images without an alpha channel are fully opaque.
+    */
+    pub fn get_alpha(&self, x: u32, y: u32) -> Option<S> {
+        if (x >= self.width) || (y >= self.height) {
+            None
+        } else {
+            match &self.alpha {
+                Some(alpha) => Some(alpha[(y * self.width + x) as usize]),
+                None => Some(S::from_f32(self.maxc as f32)),
+            }
+        }
+    }
+
+    /**
+    Returns the total number of pixels in the image, or an error if there are more than fit in a
+    u32, since the pixels are found by their u32 index. A header can give any width and height, so
+    this is checked before reading pixel data.
+    */
+    pub fn pixel_count(&self) -> Result<usize, PpmError> {
+        match self.width.checked_mul(self.height) {
+            Some(count) => Ok(count as usize),
+            None => Err(PpmError::ImageTooLarge(self.width, self.height)),
+        }
+    }
+
+    /**
+    Replaces every color with its new color from the LUT.
+    */
+    pub fn apply_lut(&mut self, lut: &Lut, interpolation: LutInterpolation) {
+        self.change_rgb(|rgb| lut.apply(rgb, interpolation));
+    }
+
+    /**
+    Changes every sample by scaling it to 0.0 to 1.0, passing it through `change` along
+    with its channel (0 for red, 1 for green, 2 for blue), and scaling it back.
+    */
+    fn change_samples(&mut self, change: impl Fn(usize, f32) -> f32 + Sync) {
+        let maxc = self.maxc;
+        let scale = maxc as f32;
+        let level = |channel: usize, value: S| S::from_f32_clamped(change(channel, value.to_f32() / scale) * scale, maxc);
+
+        match S::INTEGER {
+            true => {
+                // integer images have at most 65536 levels, so every level is changed once and looked up after
+                let table = (0..=maxc).map(|value| [0, 1, 2].map(|channel| level(channel, S::from_f32(value as f32)))).collect::<Vec<[S; 3]>>();
+                let index = |value: S| (value.to_f32() as usize).min(maxc as usize);
+                self.pixels.par_iter_mut().for_each(|pixel| {
+                    pixel.r = table[index(pixel.r)][0];
+                    pixel.g = table[index(pixel.g)][1];
+                    pixel.b = table[index(pixel.b)][2];
+                });
+            }
+            false => {
+                // loop over each pixel
+                self.pixels.par_iter_mut().for_each(|pixel| {
+                    pixel.r = level(0, pixel.r);
+                    pixel.g = level(1, pixel.g);
+                    pixel.b = level(2, pixel.b);
+                });
+            }
+        }
+    }
+
+    /**
+    Adds the given fraction of the max color value to every sample, so that 0.1 brightens
+    the image by 10% and -0.1 darkens it.
+    */
+    pub fn adjust_brightness(&mut self, amount: f32) {
+        self.change_samples(|_, value| value + amount);
+    }
+
+    /**
+    Stretches every sample away from the middle level by the given factor, so that 1.5
+    increases the contrast, 0.5 reduces it, and 0 makes the image flat gray.
+    */
+    pub fn adjust_contrast(&mut self, factor: f32) {
+        self.change_samples(|_, value| (value - 0.5) * factor + 0.5);
+    }
+
+    /**
+    Applies gamma correction, raising every sample to the power of 1 / gamma,
+    so that gamma above 1.0 brightens the middle levels and below 1.0 darkens them.
+    Black and white are unchanged.
+    */
+    pub fn adjust_gamma(&mut self, gamma: f32) {
+        self.change_samples(|_, value| value.max(0.0).powf(1.0 / gamma));
+    }
+
+    /**
+    Applies the levels adjustment to every sample.
+    */
+    pub fn levels(&mut self, levels: Levels) {
+        self.change_samples(|_, value| levels.apply(value));
+    }
+
+    /**
+    Applies the tone curve to every sample.
+    */
+    pub fn curves(&mut self, curve: &Curve) {
+        self.change_samples(|_, value| curve.apply(value));
+    }
+
+    /**
+    Counts the samples of each channel at each level, in LEVEL_BINS bins spread from 0 to the max color value.
+    */
+    fn level_histograms(&self) -> [Vec<u64>; 3] {
+        let scale = (LEVEL_BINS - 1) as f32 / self.maxc as f32;
+        let bin = |value: S| (value.to_f32() * scale).round().clamp(0.0, (LEVEL_BINS - 1) as f32) as usize;
+
+        // count each chunk of pixels in parallel, then add the counts together
+        self.pixels.par_chunks(65536).fold(|| [vec![0; LEVEL_BINS], vec![0; LEVEL_BINS], vec![0; LEVEL_BINS]], |mut histograms, pixels| {
+            for pixel in pixels {
+                histograms[0][bin(pixel.r)] += 1;
+                histograms[1][bin(pixel.g)] += 1;
+                histograms[2][bin(pixel.b)] += 1;
+            }
+            histograms
+        }).reduce(|| [vec![0; LEVEL_BINS], vec![0; LEVEL_BINS], vec![0; LEVEL_BINS]], |mut total, histograms| {
+            for (total, histogram) in total.iter_mut().zip(histograms.iter()) {
+                total.iter_mut().zip(histogram.iter()).for_each(|(total, count)| *total += count);
+            }
+            total
+        })
+    }
+
+    /**
+    Stretches each channel separately so its darkest level becomes black and its brightest becomes
+    white, which also removes color casts. The `clip` fraction of the samples at each end of each
+    channel is ignored, such as 0.005 for 0.5%, so that a few stray pixels don't stop the stretch.
     */
-    pub fn pixel_count(&self) -> u32 {
-        self.width * self.height
+    pub fn auto_levels(&mut self, clip: f32) {
+        let levels = self.level_histograms().map(|histogram| {
+            let (input_black, input_white) = clipped_range(&histogram, clip);
+            Levels { input_black, input_white, ..Levels::default() }
+        });
+
+        // a channel with a single level has nothing to stretch, so it is left alone
+        self.change_samples(|channel, value| match levels[channel].input_black < levels[channel].input_white {
+            true => levels[channel].apply(value),
+            false => value,
+        });
+    }
+
+    /**
+    Stretches all of the channels together so the darkest level in the image becomes black and the
+    brightest becomes white, keeping the colors the same. `clip` works the same as in auto_levels.
+    */
+    pub fn auto_contrast(&mut self, clip: f32) {
+        let [red, green, blue] = self.level_histograms();
+        let histogram = red.iter().zip(green.iter()).zip(blue.iter()).map(|((r, g), b)| r + g + b).collect::<Vec<u64>>();
+        let (input_black, input_white) = clipped_range(&histogram, clip);
+        if input_black < input_white {
+            self.levels(Levels { input_black, input_white, ..Levels::default() });
+        }
     }
 
     /**
@@ -117,29 +596,132 @@ impl PPM {
     */
     pub fn negative(&mut self) {
         // loop over each pixel
+        let maxc = self.maxc as f32;
         self.pixels.par_iter_mut().for_each(|pixel| {
-            pixel.r = (self.maxc - pixel.r as u32) as u16;
-            pixel.g = (self.maxc - pixel.g as u32) as u16;
-            pixel.b = (self.maxc - pixel.b as u32) as u16;
+            pixel.r = S::from_f32(maxc - pixel.r.to_f32());
+            pixel.g = S::from_f32(maxc - pixel.g.to_f32());
+            pixel.b = S::from_f32(maxc - pixel.b.to_f32());
         });
     }
 
     /**
-    Converts the image to grayscale.
+    Converts the image to grayscale, combining the channels with the given mode.
+    The image keeps its subtype, use to_single_channel to save it with one sample per pixel.
     */
-    pub fn grayscale(&mut self) {
+    pub fn grayscale(&mut self, mode: GrayscaleMode) {
+        let maxc = self.maxc;
+
         // loop over each pixel
         self.pixels.par_iter_mut().for_each(|pixel| {
-            // calculate the average value of the r, g, and b pixels
-            let avg = ((pixel.r as u32 + pixel.g as u32 + pixel.b as u32) / 3) as u16;
+            let (r, g, b) = (pixel.r.to_f32(), pixel.g.to_f32(), pixel.b.to_f32());
+
+            // calculate the gray level of the pixel
+            let level = match mode {
+                GrayscaleMode::Average => pixel.average(),
+                GrayscaleMode::Rec601 => S::from_f32_clamped(0.299 * r + 0.587 * g + 0.114 * b, maxc),
+                GrayscaleMode::Rec709 => S::from_f32_clamped(0.2126 * r + 0.7152 * g + 0.0722 * b, maxc),
+                GrayscaleMode::Luminance => {
+                    let linear = |value: f32| srgb_to_linear(value / maxc as f32);
+                    let luminance = 0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b);
+                    S::from_f32_clamped(linear_to_srgb(luminance) * maxc as f32, maxc)
+                }
+                GrayscaleMode::Lightness => S::from_f32_clamped((r.max(g).max(b) + r.min(g).min(b)) / 2.0, maxc),
+                GrayscaleMode::Channel(Channel::Red) => pixel.r,
+                GrayscaleMode::Channel(Channel::Green) => pixel.g,
+                GrayscaleMode::Channel(Channel::Blue) => pixel.b,
+            };
 
-            // save the average value to each pixel
-            pixel.r = avg;
-            pixel.g = avg;
-            pixel.b = avg;
+            // save the gray level to each channel
+            pixel.r = level;
+            pixel.g = level;
+            pixel.b = level;
         });
     }
 
+    /**
+    Changes the color of every pixel by converting it to rgb values from 0.0 to 1.0,
+    passing it through `change`, and converting it back.
+    */
+    fn change_rgb(&mut self, change: impl Fn([f32; 3]) -> [f32; 3] + Sync) {
+        let maxc = self.maxc;
+
+        // loop over each pixel
+        self.pixels.par_iter_mut().for_each(|pixel| {
+            *pixel = Pixel::from_rgb(change(pixel.to_rgb(maxc)), maxc);
+        });
+    }
+
+    /**
+    Changes the HSL color of every pixel with `change`. HSL only reaches up to white, so HDR
+    pixels of floating point images, which go above 1.0, are scaled down until their largest
+    channel is 1.0 while their color is changed, and scaled back up afterwards.
+    */
+    fn change_hsl(&mut self, change: impl Fn(&mut Hsl) + Sync) {
+        self.change_rgb(|rgb| {
+            let scale = rgb[0].max(rgb[1]).max(rgb[2]).max(1.0);
+            let mut hsl = Hsl::from_rgb(rgb.map(|value| value / scale));
+            change(&mut hsl);
+            hsl.to_rgb().map(|value| value * scale)
+        });
+    }
+
+    /**
+    Rotates the hue of every pixel around the color wheel by the given number of degrees,
+    so that 120 turns red into green. The saturation and lightness are kept.
+    */
+    pub fn rotate_hue(&mut self, degrees: f32) {
+        self.change_hsl(|hsl| hsl.h = (hsl.h + degrees).rem_euclid(360.0));
+    }
+
+    /**
+    Multiplies the saturation of every pixel by the given factor, so that 0 makes the
+    image gray and numbers above 1 make the colors stronger. The hue and lightness are kept.
+    */
+    pub fn scale_saturation(&mut self, factor: f32) {
+        self.change_hsl(|hsl| hsl.s = (hsl.s * factor).clamp(0.0, 1.0));
+    }
+
+    /**
+    Adds the given amount to the CIELAB lightness of every pixel, which goes from 0 for
+    black to 100 for white, so that the change looks the same on every color.
+    */
+    pub fn adjust_lightness(&mut self, amount: f32) {
+        self.change_rgb(|rgb| {
+            let mut lab = Lab::from_rgb(rgb);
+            lab.l = (lab.l + amount).clamp(0.0, 100.0);
+            lab.to_rgb()
+        });
+    }
+
+    /**
+    Switches the image to the single channel version of its subtype (P2, P5, Pf, or a
+    GRAYSCALE PAM), so that it is saved with one sample per pixel instead of three.
+    The channels are averaged when saving, so the image should be grayscale first.
+    Bitmaps and images that are already single channel are left alone.
+    */
+    pub fn to_single_channel(&mut self) {
+        match self.magic.as_str() {
+            "P3" => self.magic = String::from("P2"),
+            "P6" => self.magic = String::from("P5"),
+            "PF" => self.magic = String::from("Pf"),
+            "P7" => self.tupltype = String::from("GRAYSCALE"),
+            _ => {}
+        }
+    }
+
+    /**
+    Switches the image to the bitmap version of its subtype, P1 for the plain subtypes (P1, P2,
+    and P3) and P4 for the rest, and drops the alpha channel. Pixels darker than half the max
+    color value are saved as black, so the image should only have black and white pixels first.
+    */
+    pub fn to_bitmap(&mut self) {
+        self.magic = String::from(match self.magic.as_str() {
+            "P1" | "P2" | "P3" => "P1",
+            _ => "P4",
+        });
+        self.alpha = None;
+    }
+
     /**
     Isolates a color channel.
     */
@@ -148,16 +730,16 @@ impl PPM {
         self.pixels.par_iter_mut().for_each(|pixel| {
             match channel {
                 Channel::Red => {
-                    pixel.g = 0;
-                    pixel.b = 0;
+                    pixel.g = S::default();
+                    pixel.b = S::default();
                 },
                 Channel::Green => {
-                    pixel.r = 0;
-                    pixel.b = 0;
+                    pixel.r = S::default();
+                    pixel.b = S::default();
                 },
                 Channel::Blue => {
-                    pixel.r = 0;
-                    pixel.g = 0;
+                    pixel.r = S::default();
+                    pixel.g = S::default();
                 }
             }
         });
@@ -171,16 +753,152 @@ impl PPM {
         self.pixels.par_iter_mut().for_each(|pixel| {
             match channel {
                 Channel::Red => {
-                    pixel.r = 0;
+                    pixel.r = S::default();
                 },
                 Channel::Green => {
-                    pixel.g = 0;
+                    pixel.g = S::default();
                 },
                 Channel::Blue => {
-                    pixel.b = 0;
+                    pixel.b = S::default();
                 }
             }
         });
     }
 
-}
\ No newline at end of file
+}
+
+impl<S: IntegerSample> PPM<S> {
+    /**
+    Rescales every sample to a new max color value, rounding to the nearest level.
+    For example, converting from 65535 to 255 maps 65535 to 255 and 32896 to 128,
+    instead of truncating the upper byte away. The new samples can be a different
+    type, as long as it can hold the new max color value.
+    */
+    pub fn convert_depth<T: IntegerSample>(&self, new_maxc: u32) -> PPM<T> {
+        let old_maxc = self.maxc as u64;
+        let new_maxc_wide = new_maxc as u64;
+
+        // rescale a single sample with rounding, adding half of the old max before dividing
+        let rescale = |sample: S| -> T {
+            T::from_u16(((sample.to_u16() as u64 * new_maxc_wide + old_maxc / 2) / old_maxc) as u16)
+        };
+
+        PPM {
+            width: self.width,
+            height: self.height,
+            maxc: new_maxc,
+            magic: self.magic.clone(),
+            pixels: self.pixels.par_iter().map(|pixel| Pixel {
+                r: rescale(pixel.r),
+                g: rescale(pixel.g),
+                b: rescale(pixel.b)
+            }).collect(),
+            // the alpha channel uses the same max color value
+            alpha: self.alpha.as_ref().map(|alpha| alpha.par_iter().map(|&value| rescale(value)).collect()),
+            tupltype: self.tupltype.clone()
+        }
+    }
+
+    /**
+    Converts the image to floating point, with every sample divided by the max color value.
+    */
+    pub fn to_float(&self) -> PPM<f32> {
+        let maxc = self.maxc as f32;
+        let to_float = |sample: S| sample.to_f32() / maxc;
+
+        PPM {
+            width: self.width,
+            height: self.height,
+            maxc: 1,
+            magic: String::from(if self.tupltype == "GRAYSCALE" { "Pf" } else { "PF" }),
+            pixels: self.pixels.par_iter().map(|pixel| Pixel {
+                r: to_float(pixel.r),
+                g: to_float(pixel.g),
+                b: to_float(pixel.b)
+            }).collect(),
+            alpha: self.alpha.as_ref().map(|alpha| alpha.par_iter().map(|&value| to_float(value)).collect()),
+            tupltype: self.tupltype.clone()
+        }
+    }
+}
+
+impl PPM<f32> {
+    /**
+    Converts a floating point image to integers with the given max color value.
+    Samples are clamped to 0.0 to 1.0 and rounded to the nearest level.
+    */
+    pub fn to_integer<T: IntegerSample>(&self, maxc: u32, magic: &str) -> PPM<T> {
+        let scale = maxc as f32;
+        let to_integer = |sample: f32| T::from_f32((sample.clamp(0.0, 1.0) * scale).round());
+
+        PPM {
+            width: self.width,
+            height: self.height,
+            maxc,
+            magic: magic.to_string(),
+            pixels: self.pixels.par_iter().map(|pixel| Pixel {
+                r: to_integer(pixel.r),
+                g: to_integer(pixel.g),
+                b: to_integer(pixel.b)
+            }).collect(),
+            alpha: self.alpha.as_ref().map(|alpha| alpha.par_iter().map(|&value| to_integer(value)).collect()),
+            tupltype: self.tupltype.clone()
+        }
+    }
+}
+
+/*
+The Image type holds an image with whichever type of samples suits it. Images with a
+max color value below 256 use 8-bit samples, so each pixel only takes 3 bytes of
+memory, and the rest use 16-bit samples. PFM images use floating point samples.
+*/
+pub enum Image {
+    U8(PPM<u8>), // an integer image with a max color value up to 255
+    U16(PPM<u16>), // an integer image with a max color value above 255
+    F32(PPM<f32>), // a floating point (PFM) image
+}
+
+impl Image {
+    /**
+    Returns the width and height of the image.
+    */
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Image::U8(inner) => (inner.width, inner.height),
+            Image::U16(inner) => (inner.width, inner.height),
+            Image::F32(inner) => (inner.width, inner.height),
+        }
+    }
+
+    /**
+    Rescales the image to a new max color value, switching between 8-bit and 16-bit
+    samples if needed. Floating point images become integer images, saved as P5 if
+    they are grayscale and P6 otherwise.
+    */
+    pub fn convert_depth(self, new_maxc: u32) -> Image {
+        match (self, new_maxc <= u8::MAX as u32) {
+            (Image::U8(inner), true) => Image::U8(inner.convert_depth(new_maxc)),
+            (Image::U8(inner), false) => Image::U16(inner.convert_depth(new_maxc)),
+            (Image::U16(inner), true) => Image::U8(inner.convert_depth(new_maxc)),
+            (Image::U16(inner), false) => Image::U16(inner.convert_depth(new_maxc)),
+            (Image::F32(inner), fits_u8) => {
+                let magic = if inner.magic == "Pf" { "P5" } else { "P6" };
+                match fits_u8 {
+                    true => Image::U8(inner.to_integer(new_maxc, magic)),
+                    false => Image::U16(inner.to_integer(new_maxc, magic)),
+                }
+            }
+        }
+    }
+
+    /**
+    Converts the image to floating point, if it isn't already.
+    */
+    pub fn to_float(self) -> PPM<f32> {
+        match self {
+            Image::U8(inner) => inner.to_float(),
+            Image::U16(inner) => inner.to_float(),
+            Image::F32(inner) => inner,
+        }
+    }
+}
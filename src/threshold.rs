@@ -0,0 +1,215 @@
+/*
+Contains the thresholding methods, which turn an image into black and white, such as for
+scanned pages that are read by OCR.
+
+Every method works on the luma of the image, with the Rec. 601 weights, and makes the pixels
+brighter than the threshold white and the rest black. Global methods use one threshold for the
+whole image, picked from its histogram, while local methods pick a threshold for each pixel from
+the pixels around it, so shadows and uneven lighting don't turn parts of the page black.
+*/
+
+use rayon::prelude::*;
+
+use crate::imageactions::{blur_plane, box_weights, gaussian_weights, gray_image, MAX_RADIUS};
+use crate::ppm::{PPM, Sample};
+
+/*
+The ThresholdMethod type picks how the threshold of each pixel is found. Levels, offsets and
+ranges are fractions of the max color value, so they work the same at any bit depth.
+*/
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ThresholdMethod {
+    Fixed(f32), // the same level for every pixel
+    Otsu, // the level that best splits the histogram into two groups
+    Triangle, // the level farthest below the line from the peak of the histogram to its far end, for pages that are mostly paper
+    AdaptiveMean { radius: u32, offset: f32 }, // the mean of the square reaching radius around the pixel, minus the offset
+    AdaptiveGaussian { sigma: f32, offset: f32 }, // the gaussian weighted mean around the pixel, minus the offset
+    Niblack { radius: u32, k: f32 }, // the local mean plus k times the local standard deviation, where k is usually -0.2
+    Sauvola { radius: u32, k: f32, range: f32 }, // the local mean times 1 + k (deviation / range - 1), where k is usually 0.2 to 0.5 and range is 0.5
+}
+
+impl ThresholdMethod {
+    /**
+    Returns whether the settings of the method can be used. Every level, offset and k must be
+    a finite number, the sigma can't be negative, and the range must be above 0. The radius can
+    be at most MAX_RADIUS, and the window of the gaussian, which reaches 3 sigma, too.
+    */
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            ThresholdMethod::Fixed(level) => level.is_finite(),
+            ThresholdMethod::Otsu | ThresholdMethod::Triangle => true,
+            ThresholdMethod::AdaptiveMean { radius, offset } => radius <= MAX_RADIUS && offset.is_finite(),
+            ThresholdMethod::AdaptiveGaussian { sigma, offset } => sigma.is_finite() && sigma >= 0.0 && sigma * 3.0 <= MAX_RADIUS as f32 && offset.is_finite(),
+            ThresholdMethod::Niblack { radius, k } => radius <= MAX_RADIUS && k.is_finite(),
+            ThresholdMethod::Sauvola { radius, k, range } => radius <= MAX_RADIUS && k.is_finite() && range.is_finite() && range > 0.0,
+        }
+    }
+}
+
+/**
+Returns the Rec. 601 luma of each pixel, as a fraction of the max color value.
+*/
+fn luma_levels<S: Sample>(image: &PPM<S>) -> Vec<f32> {
+    let scale = 1.0 / image.maxc as f32;
+    image.pixels.par_iter().map(|pixel| (0.299 * pixel.r.to_f32() + 0.587 * pixel.g.to_f32() + 0.114 * pixel.b.to_f32()) * scale).collect()
+}
+
+/**
+Returns the histogram of the levels, with one bin for each level of an integer image with the
+given max color value, up to `most_bins` bins, and `most_bins` bins for floating point images.
+*/
+fn level_histogram<S: Sample>(levels: &[f32], maxc: u32, most_bins: usize) -> Vec<u64> {
+    let bins = match S::INTEGER {
+        true => (maxc as usize + 1).min(most_bins),
+        false => most_bins,
+    };
+    let last = (bins - 1) as f32;
+    levels.par_iter().fold(|| vec![0u64; bins], |mut histogram, &level| {
+        histogram[(level.clamp(0.0, 1.0) * last).round() as usize] += 1;
+        histogram
+    }).reduce(|| vec![0u64; bins], |mut a, b| {
+        a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+        a
+    })
+}
+
+// Otsu's method can use a bin for every level of a 16-bit image
+const OTSU_BINS: usize = 65536;
+
+// the triangle method needs the bins between the peak and the tail to be filled, which they
+// aren't with a bin for every level of an 8-bit image saved as 16-bit, so it uses fewer bins
+const TRIANGLE_BINS: usize = 256;
+
+/**
+Returns the bin of the histogram picked by Otsu's method, which splits the levels into a dark
+and a bright group with the largest difference between their means, weighted by their sizes.
+The bin goes with the dark group.
+*/
+fn otsu_bin(histogram: &[u64]) -> usize {
+    let total = histogram.iter().sum::<u64>() as f64;
+    let total_sum = histogram.iter().enumerate().map(|(bin, &count)| bin as f64 * count as f64).sum::<f64>();
+
+    let (mut dark, mut dark_sum) = (0.0, 0.0);
+    let (mut best_bin, mut best_variance) = (0, -1.0);
+    for (bin, &count) in histogram.iter().enumerate() {
+        dark += count as f64;
+        dark_sum += bin as f64 * count as f64;
+        let bright = total - dark;
+        if dark == 0.0 || bright == 0.0 {
+            continue;
+        }
+
+        // the variance between the two groups
+        let difference = dark_sum / dark - (total_sum - dark_sum) / bright;
+        let variance = dark * bright * difference * difference;
+        if variance > best_variance {
+            best_bin = bin;
+            best_variance = variance;
+        }
+    }
+    best_bin
+}
+
+/**
+Returns the bin of the histogram picked by the triangle method. A line is drawn from the peak of
+the histogram to the end of its longer tail, and the bin farthest below the line is picked.
+The bin goes with the dark group.
+*/
+fn triangle_bin(histogram: &[u64]) -> usize {
+    let (Some(first), Some(last)) = (histogram.iter().position(|&count| count > 0), histogram.iter().rposition(|&count| count > 0)) else {
+        return 0;
+    };
+    let peak = (first..=last).max_by_key(|&bin| histogram[bin]).unwrap_or(first);
+
+    // the line goes down the longer tail of the histogram
+    let (start, end) = match peak - first > last - peak {
+        true => (first, peak),
+        false => (peak, last),
+    };
+    let (start_height, end_height) = (histogram[start] as f64, histogram[end] as f64);
+    let distance = |bin: usize| ((end_height - start_height) * (bin - start) as f64 - (end - start) as f64 * (histogram[bin] as f64 - start_height)).abs();
+    (start..=end).max_by(|&a, &b| distance(a).total_cmp(&distance(b))).unwrap_or(start)
+}
+
+/**
+Returns the threshold picked from the histogram of the levels by `pick_bin`, as a fraction of the max color value.
+*/
+fn histogram_threshold<S: Sample>(levels: &[f32], maxc: u32, pick_bin: fn(&[u64]) -> usize, most_bins: usize) -> f32 {
+    let histogram = level_histogram::<S>(levels, maxc, most_bins);
+    pick_bin(&histogram) as f32 / (histogram.len() - 1) as f32
+}
+
+/**
+Returns the threshold picked by Otsu's method for the image, as a fraction of the max color value.
+Pixels at or below it are black.
+*/
+pub fn otsu_threshold<S: Sample>(image: &PPM<S>) -> f32 {
+    histogram_threshold::<S>(&luma_levels(image), image.maxc, otsu_bin, OTSU_BINS)
+}
+
+/**
+Returns the threshold picked by the triangle method for the image, as a fraction of the max color
+value. Pixels at or below it are black.
+*/
+pub fn triangle_threshold<S: Sample>(image: &PPM<S>) -> f32 {
+    histogram_threshold::<S>(&luma_levels(image), image.maxc, triangle_bin, TRIANGLE_BINS)
+}
+
+/**
+Returns the mean and standard deviation of the levels in the square reaching `radius` around
+each pixel, repeating the pixels on the edges past the edge of the image.
+*/
+fn local_statistics(levels: &[f32], width: u32, height: u32, radius: u32) -> Vec<(f32, f32)> {
+    let weights = box_weights(radius);
+    let means = blur_plane(levels, width, height, &weights);
+    let squares = levels.par_iter().map(|level| level * level).collect::<Vec<f32>>();
+    let mean_squares = blur_plane(&squares, width, height, &weights);
+    means.into_par_iter().zip(mean_squares).map(|(mean, mean_square)| (mean, (mean_square - mean * mean).max(0.0).sqrt())).collect()
+}
+
+/**
+Turns the image black and white with the method, making the pixels whose luma is above their
+threshold white and the rest black. The result has no alpha channel, use to_bitmap on it to
+save it as a bitmap. Returns None if the settings of the method aren't valid.
+*/
+pub fn threshold<S: Sample>(image: PPM<S>, method: ThresholdMethod) -> Option<PPM<S>> {
+    if !method.is_valid() {
+        return None;
+    }
+
+    let levels = luma_levels(&image);
+    let (width, height) = (image.width, image.height);
+
+    // find the threshold of each pixel
+    let thresholds = match method {
+        ThresholdMethod::Fixed(level) => vec![level; levels.len()],
+        ThresholdMethod::Otsu => vec![histogram_threshold::<S>(&levels, image.maxc, otsu_bin, OTSU_BINS); levels.len()],
+        ThresholdMethod::Triangle => vec![histogram_threshold::<S>(&levels, image.maxc, triangle_bin, TRIANGLE_BINS); levels.len()],
+        ThresholdMethod::AdaptiveMean { radius, offset } => {
+            blur_plane(&levels, width, height, &box_weights(radius)).into_par_iter().map(|mean| mean - offset).collect()
+        }
+        ThresholdMethod::AdaptiveGaussian { sigma, offset } => {
+            blur_plane(&levels, width, height, &gaussian_weights(sigma)).into_par_iter().map(|mean| mean - offset).collect()
+        }
+        ThresholdMethod::Niblack { radius, k } => {
+            local_statistics(&levels, width, height, radius).into_par_iter().map(|(mean, deviation)| mean + k * deviation).collect()
+        }
+        ThresholdMethod::Sauvola { radius, k, range } => {
+            local_statistics(&levels, width, height, radius).into_par_iter().map(|(mean, deviation)| mean * (1.0 + k * (deviation / range - 1.0))).collect()
+        }
+    };
+
+    // integer images are compared at whole levels, the same as a grayscale copy of the image would be
+    let maxc = image.maxc as f32;
+    let black_and_white = levels.into_par_iter().zip(thresholds).map(|(level, threshold)| {
+        let level = match S::INTEGER {
+            true => (level * maxc).round() / maxc,
+            false => level,
+        };
+        match level > threshold {
+            true => maxc,
+            false => 0.0,
+        }
+    });
+    Some(gray_image(&image, black_and_white))
+}
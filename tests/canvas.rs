@@ -0,0 +1,113 @@
+/*
+Checks cropping, padding and extending the canvas against pixels worked out by hand for each
+edge mode, and that sizes too large to count are rejected instead of overflowing.
+*/
+
+mod common;
+
+use common::{image, levels};
+use ppm_parser::imageactions::{crop, extend_canvas, pad, Anchor, Edge};
+use ppm_parser::ppm::{PPM, Pixel};
+
+/**
+Returns the red levels of each row of the image.
+*/
+fn red_rows(image: &PPM<u8>) -> Vec<Vec<f32>> {
+    levels(&image.pixels).chunks(image.width as usize).map(|row| row.iter().map(|pixel| pixel[0]).collect()).collect()
+}
+
+/**
+Returns a 3x1 row of 10, 20 and 30.
+*/
+fn row() -> PPM<u8> {
+    image(3, 1, 255, |x, _| [(x + 1) as f32 * 10.0; 3])
+}
+
+const FILL: Pixel<u8> = Pixel { r: 99, g: 99, b: 99 };
+
+#[test]
+fn padding_a_row_with_each_edge_mode() {
+    let padded = |edge| red_rows(&pad(row(), 0, 2, 0, 2, edge).unwrap());
+    assert_eq!(padded(Edge::Constant(FILL)), vec![vec![99.0, 99.0, 10.0, 20.0, 30.0, 99.0, 99.0]]);
+    assert_eq!(padded(Edge::Replicate), vec![vec![10.0, 10.0, 10.0, 20.0, 30.0, 30.0, 30.0]]);
+    // reflect mirrors around the edge pixel without repeating it
+    assert_eq!(padded(Edge::Reflect), vec![vec![30.0, 20.0, 10.0, 20.0, 30.0, 20.0, 10.0]]);
+    assert_eq!(padded(Edge::Wrap), vec![vec![20.0, 30.0, 10.0, 20.0, 30.0, 10.0, 20.0]]);
+}
+
+#[test]
+fn padding_past_more_than_one_period() {
+    // a 2 pixel row reflects with a period of 2, and wraps with a period of 2
+    let short = || image::<u8>(2, 1, 255, |x, _| [(x + 1) as f32; 3]);
+    assert_eq!(red_rows(&pad(short(), 0, 3, 0, 3, Edge::Reflect).unwrap()), vec![vec![2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0]]);
+    assert_eq!(red_rows(&pad(short(), 0, 3, 0, 3, Edge::Wrap).unwrap()), vec![vec![2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0, 1.0]]);
+
+    // a single pixel is repeated by every mode except constant
+    for edge in [Edge::Replicate, Edge::Reflect, Edge::Wrap] {
+        let single = image::<u8>(1, 1, 255, |_, _| [7.0; 3]);
+        assert_eq!(red_rows(&pad(single, 2, 2, 2, 2, edge).unwrap()), vec![vec![7.0; 5]; 5]);
+    }
+}
+
+#[test]
+fn padding_the_corners() {
+    // the corners come from the pixel found along both axes
+    let square = || image::<u8>(2, 2, 255, |x, y| [(y * 2 + x + 1) as f32; 3]);
+    assert_eq!(red_rows(&pad(square(), 1, 1, 1, 1, Edge::Wrap).unwrap()), vec![
+        vec![4.0, 3.0, 4.0, 3.0],
+        vec![2.0, 1.0, 2.0, 1.0],
+        vec![4.0, 3.0, 4.0, 3.0],
+        vec![2.0, 1.0, 2.0, 1.0],
+    ]);
+    assert_eq!(red_rows(&pad(square(), 1, 0, 0, 1, Edge::Replicate).unwrap()), vec![
+        vec![1.0, 1.0, 2.0],
+        vec![1.0, 1.0, 2.0],
+        vec![3.0, 3.0, 4.0],
+    ]);
+}
+
+#[test]
+fn constant_padding_is_opaque() {
+    let mut image = row();
+    image.alpha = Some(vec![0, 128, 255]);
+    let padded = pad(image, 0, 1, 0, 1, Edge::Constant(FILL)).unwrap();
+    assert_eq!(padded.alpha, Some(vec![255, 0, 128, 255, 255]));
+}
+
+#[test]
+fn padding_too_large_is_rejected() {
+    assert!(pad(row(), 0, u32::MAX, 0, 0, Edge::Replicate).is_none());
+    assert!(pad(row(), u32::MAX, 0, 0, 0, Edge::Replicate).is_none());
+    assert!(pad(row(), 70000, 70000, 0, 0, Edge::Replicate).is_none());
+}
+
+#[test]
+fn crop_keeps_the_region_inside_the_image() {
+    let grid = || image::<u8>(4, 3, 255, |x, y| [(y * 4 + x) as f32; 3]);
+    assert_eq!(red_rows(&crop(grid(), 1, 1, 2, 2)), vec![vec![5.0, 6.0], vec![9.0, 10.0]]);
+
+    // the part of the region past the edge is left out
+    assert_eq!(red_rows(&crop(grid(), 2, 1, 10, 10)), vec![vec![6.0, 7.0], vec![10.0, 11.0]]);
+    let outside = crop(grid(), 10, 10, 2, 2);
+    assert_eq!((outside.width, outside.height), (0, 0));
+}
+
+#[test]
+fn extending_the_canvas_at_each_anchor() {
+    let extended = |anchor| red_rows(&extend_canvas(row(), 5, 2, anchor, Edge::Constant(FILL)));
+    assert_eq!(extended(Anchor::TopLeft), vec![vec![10.0, 20.0, 30.0, 99.0, 99.0], vec![99.0; 5]]);
+    assert_eq!(extended(Anchor::Center), vec![vec![99.0, 10.0, 20.0, 30.0, 99.0], vec![99.0; 5]]);
+    assert_eq!(extended(Anchor::BottomRight), vec![vec![99.0; 5], vec![99.0, 99.0, 10.0, 20.0, 30.0]]);
+
+    // a smaller canvas crops the sides away from the anchor
+    let shrunk = |anchor| red_rows(&extend_canvas(row(), 1, 1, anchor, Edge::Replicate));
+    assert_eq!(shrunk(Anchor::Left), vec![vec![10.0]]);
+    assert_eq!(shrunk(Anchor::Center), vec![vec![20.0]]);
+    assert_eq!(shrunk(Anchor::Right), vec![vec![30.0]]);
+}
+
+#[test]
+fn canvas_of_an_empty_image_is_the_fill() {
+    let empty = image::<u8>(0, 0, 255, |_, _| [0.0; 3]);
+    assert_eq!(red_rows(&extend_canvas(empty, 2, 1, Anchor::Center, Edge::Wrap)), vec![vec![0.0, 0.0]]);
+}
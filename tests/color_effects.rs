@@ -0,0 +1,66 @@
+/*
+Checks the HSL color effects on floating point images, whose HDR samples go above 1.0
+and used to reach the HSL conversion past white.
+*/
+
+use ppm_parser::color::Hsl;
+use ppm_parser::ppm::{PPM, Pixel};
+
+/**
+Returns a PFM image with one pixel for each of the given colors.
+*/
+fn pfm(colors: &[[f32; 3]]) -> PPM<f32> {
+    let mut image = PPM::new();
+    image.magic = String::from("PF");
+    image.maxc = 1;
+    image.width = colors.len() as u32;
+    image.height = 1;
+    image.pixels = colors.iter().map(|&[r, g, b]| Pixel { r, g, b }).collect();
+    image
+}
+
+/**
+Asserts that every pixel of the image is within a small distance of the given colors.
+*/
+fn assert_colors(image: &PPM<f32>, colors: &[[f32; 3]]) {
+    for (pixel, expected) in image.pixels.iter().zip(colors) {
+        let actual = [pixel.r, pixel.g, pixel.b];
+        assert!(actual.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-4), "{:?} is not {:?}", actual, expected);
+    }
+}
+
+const HDR_COLORS: [[f32; 3]; 4] = [[1.5, 0.5, 0.5], [2.0, 1.0, 1.0], [4.0, 4.0, 4.0], [0.2, 3.0, 0.6]];
+
+#[test]
+fn hsl_past_white_is_finite() {
+    for rgb in HDR_COLORS {
+        let hsl = Hsl::from_rgb(rgb);
+        assert!(hsl.s.is_finite() && hsl.s >= 0.0, "{:?} gave {:?}", rgb, hsl);
+    }
+}
+
+#[test]
+fn rotating_hdr_hue_all_the_way_around_keeps_it() {
+    let mut image = pfm(&HDR_COLORS);
+    image.rotate_hue(360.0);
+    assert_colors(&image, &HDR_COLORS);
+}
+
+#[test]
+fn rotating_hdr_hue() {
+    let mut image = pfm(&[[1.5, 0.5, 0.5]]);
+    image.rotate_hue(120.0);
+    assert_colors(&image, &[[0.5, 1.5, 0.5]]);
+}
+
+#[test]
+fn scaling_hdr_saturation() {
+    let mut image = pfm(&HDR_COLORS);
+    image.scale_saturation(1.0);
+    assert_colors(&image, &HDR_COLORS);
+
+    image.scale_saturation(0.0);
+    for pixel in &image.pixels {
+        assert!(pixel.r.is_finite() && pixel.r == pixel.g && pixel.g == pixel.b, "{:?}", [pixel.r, pixel.g, pixel.b]);
+    }
+}
@@ -0,0 +1,46 @@
+/*
+Test images and helpers shared by the integration tests. Each test file only uses some of them.
+*/
+#![allow(dead_code)]
+
+use ppm_parser::ppm::{PPM, Pixel, Sample};
+
+/**
+Returns a P6 test image of pseudo random pixels from 0 to maxc.
+*/
+pub fn test_image<S: Sample>(width: u32, height: u32, maxc: u32) -> PPM<S> {
+    let mut state = 987654321u32;
+    let mut next = || {
+        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+        ((state >> 8) % (maxc + 1)) as f32
+    };
+    image(width, height, maxc, |_, _| [next(), next(), next()])
+}
+
+/**
+Returns a P6 image with the given r, g, and b levels at each pixel, which are rounded for
+integer samples. The pixels are made one row after another, left to right.
+*/
+pub fn image<S: Sample>(width: u32, height: u32, maxc: u32, mut levels: impl FnMut(u32, u32) -> [f32; 3]) -> PPM<S> {
+    let mut image = PPM::new();
+    image.magic = String::from("P6");
+    image.maxc = maxc;
+    image.width = width;
+    image.height = height;
+    let level = |value: f32| S::from_f32(match S::INTEGER {
+        true => value.round(),
+        false => value,
+    });
+    image.pixels = (0..width * height).map(|index| {
+        let [r, g, b] = levels(index % width, index / width);
+        Pixel { r: level(r), g: level(g), b: level(b) }
+    }).collect();
+    image
+}
+
+/**
+Returns the r, g, and b values of every pixel.
+*/
+pub fn levels<S: Sample>(pixels: &[Pixel<S>]) -> Vec<[f32; 3]> {
+    pixels.iter().map(|pixel| [pixel.r.to_f32(), pixel.g.to_f32(), pixel.b.to_f32()]).collect()
+}
@@ -0,0 +1,112 @@
+/*
+Checks the convolution engine: normalized kernels keep a flat image flat with every edge mode,
+separable filters match the full kernel they stand for, and kernel sizes are validated.
+*/
+
+mod common;
+
+use common::{image, levels, test_image};
+use ppm_parser::imageactions::{box_blur, box_weights, convolve, convolve_separable, gaussian_blur, gaussian_weights, Edge, Kernel, MAX_RADIUS};
+use ppm_parser::ppm::{PPM, Pixel};
+
+const COLOR: [f32; 3] = [200.0, 17.0, 90.0];
+
+/**
+Returns a flat 9x7 image of COLOR.
+*/
+fn flat() -> PPM<u8> {
+    image(9, 7, 255, |_, _| COLOR)
+}
+
+/**
+Returns every edge mode, with the constant one the same color as the flat image.
+*/
+fn edges() -> [Edge<u8>; 4] {
+    [Edge::Constant(Pixel { r: 200, g: 17, b: 90 }), Edge::Replicate, Edge::Reflect, Edge::Wrap]
+}
+
+#[test]
+fn normalized_kernels_keep_a_flat_image() {
+    // an uneven kernel that isn't symmetric, larger than the image in one direction
+    let weights = (0..11 * 3).map(|i| (i % 7 + 1) as f32).collect();
+    let kernel = Kernel::new(11, 3, weights).unwrap().normalized();
+    assert!((kernel.weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    for edge in edges() {
+        assert!(levels(&convolve(flat(), &kernel, edge).pixels).iter().all(|&pixel| pixel == COLOR));
+        assert!(levels(&box_blur(flat(), 4, edge).pixels).iter().all(|&pixel| pixel == COLOR));
+        assert!(levels(&gaussian_blur(flat(), 2.5, edge).pixels).iter().all(|&pixel| pixel == COLOR));
+    }
+}
+
+#[test]
+fn zero_sum_kernels_are_centered_on_the_offset() {
+    // a laplacian finds no edges in a flat image, which leaves the gray offset
+    let kernel = Kernel::laplacian();
+    assert_eq!(kernel.clone().normalized().weights, kernel.weights);
+    assert!(levels(&convolve(flat(), &kernel, Edge::Replicate).pixels).iter().all(|&pixel| pixel == [128.0; 3]));
+}
+
+#[test]
+fn identity_kernel_keeps_the_image() {
+    let mut weights = vec![0.0; 9];
+    weights[4] = 1.0;
+    for kernel in [Kernel::new(1, 1, vec![1.0]).unwrap(), Kernel::new(3, 3, weights).unwrap()] {
+        for edge in edges() {
+            assert_eq!(levels(&convolve(test_image::<u8>(6, 5, 255), &kernel, edge).pixels), levels(&test_image::<u8>(6, 5, 255).pixels));
+        }
+    }
+}
+
+#[test]
+fn separable_matches_the_full_kernel() {
+    let horizontal = [0.1, 0.5, 0.2, 0.2];
+    let vertical = [0.3, 0.3, 0.4];
+    let weights = vertical.iter().flat_map(|v| horizontal.iter().map(move |h| h * v)).collect();
+    let kernel = Kernel::new(4, 3, weights).unwrap();
+    for edge in [Edge::Constant(Pixel { r: 10, g: 20, b: 30 }), Edge::Replicate, Edge::Reflect, Edge::Wrap] {
+        let full = levels(&convolve(test_image::<u8>(8, 6, 255), &kernel, edge).pixels);
+        let separable = levels(&convolve_separable(test_image::<u8>(8, 6, 255), &horizontal, &vertical, edge).pixels);
+
+        // the two passes round once more, so they can differ by a level
+        for (full, separable) in full.iter().zip(&separable) {
+            assert!(full.iter().zip(separable).all(|(a, b)| (a - b).abs() <= 1.0), "{:?} {:?}", full, separable);
+        }
+    }
+}
+
+#[test]
+fn constant_black_is_a_zero_edge() {
+    // a box blur of white with a black edge darkens the pixels on the edge
+    let white = || image::<u8>(5, 5, 255, |_, _| [255.0; 3]);
+    let blurred = levels(&box_blur(white(), 1, Edge::Constant(Pixel::new())).pixels);
+    assert_eq!(blurred[0], [113.0; 3]); // 4 of the 9 pixels are white
+    assert_eq!(blurred[1], [170.0; 3]); // 6 of the 9 pixels are white
+    assert_eq!(blurred[12], [255.0; 3]);
+}
+
+#[test]
+fn weights_add_up_to_one() {
+    for radius in [0, 1, 7] {
+        let weights = box_weights(radius);
+        assert_eq!(weights.len(), 2 * radius as usize + 1);
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+    for sigma in [0.0, 0.2, 1.0, 3.5] {
+        let weights = gaussian_weights(sigma);
+        assert!((weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(weights.iter().zip(weights.iter().rev()).all(|(a, b)| a == b));
+    }
+
+    // huge radii are held to the largest window instead of overflowing
+    assert_eq!(box_weights(u32::MAX).len(), 2 * MAX_RADIUS as usize + 1);
+    assert_eq!(gaussian_weights(1e9).len(), 2 * MAX_RADIUS as usize + 1);
+}
+
+#[test]
+fn invalid_kernels_are_rejected() {
+    assert!(Kernel::new(0, 3, vec![]).is_none());
+    assert!(Kernel::new(3, 0, vec![]).is_none());
+    assert!(Kernel::new(3, 3, vec![1.0; 8]).is_none());
+    assert!(Kernel::new(65536, 65536, vec![1.0]).is_none());
+    assert!(Kernel::new(u32::MAX, 2, vec![1.0; 2]).is_none());
+}
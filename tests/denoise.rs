@@ -0,0 +1,132 @@
+/*
+Checks the denoising filters: the sliding histogram median against a naive median of each sorted
+window, and that the bilateral and non-local means filters leave a flat image alone.
+*/
+
+mod common;
+
+use common::{image, levels, test_image};
+use ppm_parser::imageactions::{bilateral_filter, median_filter, non_local_means, Edge};
+use ppm_parser::ppm::{PPM, Pixel, Sample};
+
+/**
+Returns the pixel at a position that may be past the edge of the image, with the edge mode.
+Reflect mirrors the image without repeating the edge pixel.
+*/
+fn edge_pixel<S: Sample>(image: &PPM<S>, x: i64, y: i64, edge: Edge<S>) -> [f32; 3] {
+    let index = |i: i64, size: u32| {
+        let size = size as i64;
+        match edge {
+            _ if (0..size).contains(&i) => Some(i),
+            Edge::Constant(_) => None,
+            Edge::Replicate => Some(i.clamp(0, size - 1)),
+            Edge::Wrap => Some(i.rem_euclid(size)),
+            Edge::Reflect => {
+                let period = (2 * (size - 1)).max(1);
+                let i = i.rem_euclid(period);
+                Some(if i < size { i } else { period - i })
+            }
+        }
+    };
+    match (index(x, image.width), index(y, image.height), edge) {
+        (Some(x), Some(y), _) => levels(&[*image.get_pixel(x as u32, y as u32).unwrap()])[0],
+        (_, _, Edge::Constant(fill)) => levels(&[fill])[0],
+        _ => unreachable!(),
+    }
+}
+
+/**
+Returns the median of each channel of the square reaching `radius` around each pixel, by sorting it.
+*/
+fn naive_median<S: Sample>(image: &PPM<S>, radius: i64, edge: Edge<S>) -> Vec<[f32; 3]> {
+    let mut result = Vec::new();
+    for y in 0..image.height as i64 {
+        for x in 0..image.width as i64 {
+            let window = (-radius..=radius).flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy))).map(|(dx, dy)| edge_pixel(image, x + dx, y + dy, edge)).collect::<Vec<[f32; 3]>>();
+            result.push([0, 1, 2].map(|channel| {
+                let mut values = window.iter().map(|pixel| pixel[channel]).collect::<Vec<f32>>();
+                values.sort_by(f32::total_cmp);
+                values[values.len() / 2]
+            }));
+        }
+    }
+    result
+}
+
+/**
+Checks the median filter against the naive median for a few radii and every edge mode.
+*/
+fn check_median<S: Sample>(image: &PPM<S>) {
+    let fill = Pixel { r: S::from_f32(image.maxc as f32), g: S::from_f32(0.0), b: S::from_f32((image.maxc / 2) as f32) };
+    for radius in [1, 2, 4] {
+        for (name, edge) in [("replicate", Edge::Replicate), ("reflect", Edge::Reflect), ("wrap", Edge::Wrap), ("constant", Edge::Constant(fill))] {
+            let filtered = median_filter(image.clone(), radius, edge);
+            assert_eq!(levels(&filtered.pixels), naive_median(image, radius as i64, edge), "radius {} with {} edges at maxc {}", radius, name, image.maxc);
+        }
+    }
+}
+
+#[test]
+fn median_matches_sorting_8_bit() {
+    check_median(&test_image::<u8>(13, 11, 255));
+    check_median(&test_image::<u8>(3, 17, 255));
+}
+
+#[test]
+fn median_matches_sorting_16_bit() {
+    check_median(&test_image::<u16>(13, 11, 65535));
+    check_median(&test_image::<u16>(9, 7, 1000));
+    check_median(&test_image::<u16>(9, 7, 3));
+}
+
+#[test]
+fn median_matches_sorting_float() {
+    check_median(&test_image::<f32>(10, 9, 1000));
+}
+
+#[test]
+fn median_keeps_alpha() {
+    let mut image = test_image::<u8>(5, 5, 255);
+    image.magic = String::from("P7");
+    image.tupltype = String::from("RGB_ALPHA");
+    image.alpha = Some((0..25).map(|index| index as u8 * 10).collect());
+    let filtered = median_filter(image, 1, Edge::Replicate);
+    assert_eq!(filtered.alpha, Some((0..25).map(|index| index as u8 * 10).collect()));
+}
+
+/**
+Returns a flat image where every pixel is the same color.
+*/
+fn flat_image<S: Sample>(maxc: u32, color: [f32; 3]) -> PPM<S> {
+    image(12, 9, maxc, |_, _| color)
+}
+
+/**
+Asserts that every channel of two sets of levels is within `tolerance` of each other.
+*/
+fn assert_close(actual: &[[f32; 3]], expected: &[[f32; 3]], tolerance: f32) {
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!((0..3).all(|c| (actual[c] - expected[c]).abs() <= tolerance), "{:?} is not {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn flat_image_is_unchanged() {
+    for edge in [Edge::Replicate, Edge::Reflect, Edge::Wrap] {
+        let image = flat_image::<u8>(255, [200.0, 17.0, 90.0]);
+        let expected = levels(&image.pixels);
+        assert_eq!(levels(&bilateral_filter(image.clone(), 2.0, 0.1, edge).pixels), expected);
+        assert_eq!(levels(&non_local_means(image.clone(), 0.1, 1, 3, edge).pixels), expected);
+    }
+
+    let image = flat_image::<u16>(65535, [40000.0, 123.0, 65535.0]);
+    let expected = levels(&image.pixels);
+    assert_eq!(levels(&bilateral_filter(image.clone(), 3.0, 0.05, Edge::Reflect).pixels), expected);
+    assert_eq!(levels(&non_local_means(image.clone(), 0.05, 2, 5, Edge::Reflect).pixels), expected);
+
+    // floating point images aren't rounded, so they can be off by a tiny amount
+    let image = flat_image::<f32>(1, [0.25, 0.5, 2.0]);
+    let expected = levels(&image.pixels);
+    assert_close(&levels(&bilateral_filter(image.clone(), 2.0, 0.1, Edge::Replicate).pixels), &expected, 1e-5);
+    assert_close(&levels(&non_local_means(image.clone(), 0.1, 1, 3, Edge::Replicate).pixels), &expected, 1e-5);
+}
@@ -0,0 +1,80 @@
+/*
+Checks that the Canny edge detector thins a step to a single line one pixel wide, and finds
+nothing in a flat image.
+*/
+
+use ppm_parser::imageactions::{canny, Edge};
+use ppm_parser::ppm::{PPM, Pixel};
+
+/**
+Returns a P6 image where each pixel is black or white, picked by `white`.
+*/
+fn black_and_white(width: u32, height: u32, white: impl Fn(u32, u32) -> bool) -> PPM<u8> {
+    let mut image = PPM::new();
+    image.magic = String::from("P6");
+    image.maxc = 255;
+    image.width = width;
+    image.height = height;
+    image.pixels = (0..width * height).map(|index| {
+        let level = match white(index % width, index / width) {
+            true => 255,
+            false => 0,
+        };
+        Pixel { r: level, g: level, b: level }
+    }).collect();
+    image
+}
+
+/**
+Returns the positions of the edge pixels found by Canny, checking that every pixel is black or white.
+*/
+fn edges(image: PPM<u8>) -> Vec<(u32, u32)> {
+    let width = image.width;
+    let found = canny(image, 1.4, 0.1, 0.3, Edge::Replicate);
+    assert!(found.alpha.is_none());
+    found.pixels.iter().enumerate().filter_map(|(index, pixel)| {
+        assert!(pixel.r == pixel.g && pixel.g == pixel.b && (pixel.r == 0 || pixel.r == 255), "edge pixels are black or white");
+        (pixel.r == 255).then_some((index as u32 % width, index as u32 / width))
+    }).collect()
+}
+
+#[test]
+fn vertical_step_is_one_line() {
+    let found = edges(black_and_white(20, 12, |x, _| x >= 10));
+
+    // one pixel in every row, all in the same column next to the step
+    assert_eq!(found.len(), 12, "{:?}", found);
+    let column = found[0].0;
+    assert!(column == 9 || column == 10, "{:?}", found);
+    assert!((0..12).all(|y| found.contains(&(column, y))), "{:?}", found);
+}
+
+#[test]
+fn horizontal_step_is_one_line() {
+    let found = edges(black_and_white(15, 16, |_, y| y >= 7));
+    assert_eq!(found.len(), 15, "{:?}", found);
+    let row = found[0].1;
+    assert!(row == 6 || row == 7, "{:?}", found);
+    assert!((0..15).all(|x| found.contains(&(x, row))), "{:?}", found);
+}
+
+#[test]
+fn flat_image_has_no_edges() {
+    assert!(edges(black_and_white(17, 9, |_, _| true)).is_empty());
+    assert!(edges(black_and_white(17, 9, |_, _| false)).is_empty());
+
+    let mut gray = black_and_white(17, 9, |_, _| false);
+    gray.pixels.iter_mut().for_each(|pixel| *pixel = Pixel { r: 90, g: 140, b: 30 });
+    assert!(edges(gray).is_empty());
+}
+
+#[test]
+fn weak_step_is_not_an_edge() {
+    // a step of 10 out of 255 is below both thresholds
+    let mut image = black_and_white(20, 12, |x, _| x >= 10);
+    image.pixels.iter_mut().for_each(|pixel| {
+        let level = 100 + pixel.r / 25;
+        *pixel = Pixel { r: level, g: level, b: level };
+    });
+    assert!(edges(image).is_empty());
+}
@@ -0,0 +1,83 @@
+/*
+Checks each grayscale mode against levels worked out by hand, that gray pixels stay the same in
+every mode, and that images switch to the single channel version of their subtype.
+*/
+
+mod common;
+
+use common::{image, levels};
+use ppm_parser::ppm::{Channel, GrayscaleMode, PPM};
+
+const MODES: [GrayscaleMode; 8] = [
+    GrayscaleMode::Average,
+    GrayscaleMode::Rec601,
+    GrayscaleMode::Rec709,
+    GrayscaleMode::Luminance,
+    GrayscaleMode::Lightness,
+    GrayscaleMode::Channel(Channel::Red),
+    GrayscaleMode::Channel(Channel::Green),
+    GrayscaleMode::Channel(Channel::Blue),
+];
+
+/**
+Returns the gray level of each pixel of the image after converting it with the mode, checking
+that every channel was given the same level.
+*/
+fn gray_levels(mut image: PPM<u8>, mode: GrayscaleMode) -> Vec<f32> {
+    image.grayscale(mode);
+    levels(&image.pixels).iter().map(|&[r, g, b]| {
+        assert!(r == g && g == b);
+        r
+    }).collect()
+}
+
+#[test]
+fn each_mode_on_known_colors() {
+    // 30, 60, 90 and pure red
+    let colors = || image::<u8>(2, 1, 255, |x, _| match x {
+        0 => [30.0, 60.0, 90.0],
+        _ => [255.0, 0.0, 0.0],
+    });
+    let expected = [
+        [60.0, 85.0], // the average, truncated
+        [54.0, 76.0], // 0.299 r + 0.587 g + 0.114 b, rounded
+        [56.0, 54.0], // 0.2126 r + 0.7152 g + 0.0722 b, rounded
+        [58.0, 127.0], // the Rec. 709 weights on linear light
+        [60.0, 128.0], // the middle of the largest and smallest channel
+        [30.0, 255.0],
+        [60.0, 0.0],
+        [90.0, 0.0],
+    ];
+    for (mode, expected) in MODES.into_iter().zip(expected) {
+        assert_eq!(gray_levels(colors(), mode), expected);
+    }
+}
+
+#[test]
+fn gray_pixels_stay_the_same() {
+    let ramp = || image::<u8>(256, 1, 255, |x, _| [x as f32; 3]);
+    for mode in MODES {
+        assert_eq!(gray_levels(ramp(), mode), (0..256).map(|level| level as f32).collect::<Vec<f32>>());
+    }
+
+    // floating point images can be brighter than white
+    let mut hdr = image::<f32>(1, 1, 1, |_, _| [2.5; 3]);
+    hdr.grayscale(GrayscaleMode::Rec709);
+    assert!((hdr.pixels[0].r - 2.5).abs() < 1e-5);
+}
+
+#[test]
+fn single_channel_subtypes() {
+    for (magic, single) in [("P3", "P2"), ("P6", "P5"), ("PF", "Pf"), ("P2", "P2"), ("P1", "P1"), ("P4", "P4")] {
+        let mut image = image::<u8>(1, 1, 255, |_, _| [0.0; 3]);
+        image.magic = String::from(magic);
+        image.to_single_channel();
+        assert_eq!(image.magic, single);
+    }
+
+    let mut pam = image::<u8>(1, 1, 255, |_, _| [0.0; 3]);
+    pam.magic = String::from("P7");
+    pam.tupltype = String::from("RGB");
+    pam.to_single_channel();
+    assert_eq!((pam.magic.as_str(), pam.tupltype.as_str()), ("P7", "GRAYSCALE"));
+}
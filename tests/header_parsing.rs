@@ -0,0 +1,200 @@
+/*
+Corpus of tricky Netpbm headers, checking that parse_header reads every block
+correctly and reports the exact byte offset where the pixel data starts.
+*/
+
+use std::io::{Cursor, Read};
+
+use ppm_parser::imageio::{parse_header, read_image_data, read_pfm};
+use ppm_parser::ppm::PPM;
+use ppm_parser::PpmError;
+
+/**
+Parses the header of the given bytes and returns the image and the data offset.
+*/
+fn parse(bytes: &[u8]) -> Result<(PPM, u64), PpmError> {
+    let mut reader = Cursor::new(bytes.to_vec());
+    let mut image = PPM::new();
+    let offset = parse_header(&mut reader, &mut image)?;
+    Ok((image, offset))
+}
+
+/**
+Asserts that the header parses to the given values, and that the pixel data starts at `offset`.
+*/
+fn assert_header(bytes: &[u8], magic: &str, width: u32, height: u32, maxc: u32, offset: u64) {
+    let (image, data_offset) = parse(bytes).unwrap();
+    assert_eq!(image.magic, magic);
+    assert_eq!(image.width, width);
+    assert_eq!(image.height, height);
+    assert_eq!(image.maxc, maxc);
+    assert_eq!(data_offset, offset, "wrong data offset for {:?}", String::from_utf8_lossy(bytes));
+}
+
+#[test]
+fn simple_header() {
+    assert_header(b"P6\n2 1\n255\n", "P6", 2, 1, 255, 11);
+}
+
+#[test]
+fn header_on_one_line() {
+    assert_header(b"P6 2 1 255 ", "P6", 2, 1, 255, 11);
+}
+
+#[test]
+fn tabs_and_multiple_spaces() {
+    assert_header(b"P6\t\t2   \t1\n\n  255\n", "P6", 2, 1, 255, 18);
+}
+
+#[test]
+fn crlf_line_endings() {
+    // only the CR is consumed after the max color value, the LF is the first byte of pixel data
+    assert_header(b"P5\r\n4\r\n3\r\n255\r\n", "P5", 4, 3, 255, 14);
+}
+
+#[test]
+fn comment_lines() {
+    assert_header(b"P6\n# a comment\n# another comment\n2 1\n255\n", "P6", 2, 1, 255, 41);
+}
+
+#[test]
+fn comment_after_block() {
+    assert_header(b"P6 # the magic number\n2 # width\n1# height\n255\n", "P6", 2, 1, 255, 46);
+}
+
+#[test]
+fn comment_between_digits_ends_the_block() {
+    assert_header(b"P6\n2#0\n1\n255\n", "P6", 2, 1, 255, 13);
+}
+
+#[test]
+fn comment_with_carriage_return() {
+    assert_header(b"P6\n# old mac comment\r2 1\n255\n", "P6", 2, 1, 255, 29);
+}
+
+#[test]
+fn bitmap_has_no_max_color() {
+    assert_header(b"P4\n# bitmap\n8 2\n", "P4", 8, 2, 255, 16);
+}
+
+#[test]
+fn pixel_data_looks_like_header() {
+    // the pixel data begins with bytes that are whitespace and digits, which must not be skipped
+    let (_, offset) = parse(b"P5 2 1 255\n 7").unwrap();
+    assert_eq!(offset, 11);
+}
+
+#[test]
+fn reader_is_left_at_pixel_data() {
+    let mut reader = Cursor::new(b"P5\n# comment\n2 1\n255\n\n\x09".to_vec());
+    let mut image: PPM = PPM::new();
+    parse_header(&mut reader, &mut image).unwrap();
+
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"\n\x09");
+}
+
+#[test]
+fn pixel_data_is_read_after_header() {
+    let bytes = b"P6 # comment\n1 1 255\n\x0a\x20\x09";
+    let mut reader = Cursor::new(bytes.to_vec());
+    let mut image: PPM = PPM::new();
+    parse_header(&mut reader, &mut image).unwrap();
+    read_image_data(&mut reader, &mut image).unwrap();
+
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((pixel.r, pixel.g, pixel.b), (0x0a, 0x20, 0x09));
+}
+
+#[test]
+fn plain_header_followed_by_data_on_same_line() {
+    let bytes = b"P3 1 1 255 1 2 3";
+    let mut reader = Cursor::new(bytes.to_vec());
+    let mut image: PPM = PPM::new();
+    parse_header(&mut reader, &mut image).unwrap();
+    read_image_data(&mut reader, &mut image).unwrap();
+
+    let pixel = image.get_pixel(0, 0).unwrap();
+    assert_eq!((pixel.r, pixel.g, pixel.b), (1, 2, 3));
+}
+
+#[test]
+fn truncated_headers() {
+    for bytes in [&b""[..], b"P", b"P6", b"P6\n2", b"P6 2 1", b"P6 2 1 # no max color"] {
+        assert!(matches!(parse(bytes), Err(PpmError::TruncatedHeader)), "{:?}", String::from_utf8_lossy(bytes));
+    }
+}
+
+#[test]
+fn invalid_blocks() {
+    assert!(matches!(parse(b"P6 x 1 255\n"), Err(PpmError::InvalidWidth(_))));
+    assert!(matches!(parse(b"P6 2 -1 255\n"), Err(PpmError::InvalidHeight(_))));
+    assert!(matches!(parse(b"P6 2 1 2.5\n"), Err(PpmError::InvalidMaxColor(_))));
+    assert!(matches!(parse(b"P6 2 1 0\n"), Err(PpmError::UnsupportedMaxColor(0))));
+    assert!(matches!(parse(b"P6 2 1 65536\n"), Err(PpmError::UnsupportedMaxColor(65536))));
+    assert!(matches!(parse(b"P9 2 1 255\n"), Err(PpmError::UnsupportedMagic(_))));
+}
+
+#[test]
+fn pam_header() {
+    let bytes = b"P7\nWIDTH 2\n# comment\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n";
+    assert_header(bytes, "P7", 2, 1, 255, bytes.len() as u64);
+    assert_eq!(parse(bytes).unwrap().0.tupltype, "RGB_ALPHA");
+}
+
+#[test]
+fn pam_header_without_tuple_type() {
+    let (image, _) = parse(b"P7 DEPTH 2 MAXVAL 1 HEIGHT 1 WIDTH 1 ENDHDR\n").unwrap();
+    assert_eq!(image.tupltype, "GRAYSCALE_ALPHA");
+}
+
+#[test]
+fn invalid_pam_headers() {
+    assert!(matches!(parse(b"P7\nWIDTH 1\nHEIGHT 1\nMAXVAL 255\nENDHDR\n"), Err(PpmError::InvalidPamHeader(_))));
+    assert!(matches!(parse(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE CMYK\nENDHDR\n"), Err(PpmError::UnsupportedTupleType(_))));
+    assert!(matches!(parse(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n"), Err(PpmError::UnsupportedTupleType(_))));
+}
+
+#[test]
+fn too_many_pixels() {
+    assert!(matches!(parse(b"P6 70000 70000 255\n"), Err(PpmError::ImageTooLarge(70000, 70000))));
+    assert!(matches!(parse(b"P4 4294967295 2\n"), Err(PpmError::ImageTooLarge(_, _))));
+    assert!(matches!(parse(b"P7\nWIDTH 70000\nHEIGHT 70000\nDEPTH 3\nMAXVAL 255\nENDHDR\n"), Err(PpmError::ImageTooLarge(70000, 70000))));
+}
+
+#[test]
+fn plain_header_larger_than_data() {
+    // the header promises billions of samples, but the data runs out after two
+    let mut reader = Cursor::new(b"P2 65535 65535 65535\n1 2".to_vec());
+    let mut image: PPM<u16> = PPM::new();
+    parse_header(&mut reader, &mut image).unwrap();
+    assert!(matches!(read_image_data(&mut reader, &mut image), Err(PpmError::ShortPixelData)));
+}
+
+#[test]
+fn raw_header_larger_than_data() {
+    // gigabytes of pixels are promised, but only a few bytes follow
+    for bytes in [&b"P5 65535 65535 65535\n\x01\x02"[..], b"P6 60000 60000 255\nabc", b"P4 4000000000 1\n\xff", b"P7 WIDTH 60000 HEIGHT 60000 DEPTH 4 MAXVAL 255 ENDHDR\nabcd"] {
+        let mut reader = Cursor::new(bytes.to_vec());
+        let mut image: PPM<u16> = PPM::new();
+        parse_header(&mut reader, &mut image).unwrap();
+        assert!(matches!(read_image_data(&mut reader, &mut image), Err(PpmError::ShortPixelData)), "{:?}", String::from_utf8_lossy(bytes));
+    }
+}
+
+#[test]
+fn pfm_rows_are_flipped() {
+    // a 1x2 grayscale PFM, with the bottom row first
+    let mut bytes = b"Pf 1 2 -1.0\n".to_vec();
+    bytes.extend_from_slice(&0.25f32.to_le_bytes());
+    bytes.extend_from_slice(&2.0f32.to_le_bytes());
+    let image = read_pfm(&mut Cursor::new(bytes)).unwrap();
+    assert_eq!(image.pixels.iter().map(|pixel| pixel.g).collect::<Vec<f32>>(), vec![2.0, 0.25]);
+}
+
+#[test]
+fn pfm_header_larger_than_data() {
+    assert!(matches!(read_pfm(&mut Cursor::new(b"PF 70000 70000 -1.0\n".to_vec())), Err(PpmError::ImageTooLarge(70000, 70000))));
+    assert!(matches!(read_pfm(&mut Cursor::new(b"PF 60000 60000 -1.0\nabcd".to_vec())), Err(PpmError::ShortPixelData)));
+}
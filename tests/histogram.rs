@@ -0,0 +1,82 @@
+/*
+Checks the histogram effects on images where the answer is known: an even ramp is already
+equalized, an image matched to itself doesn't change, and CLAHE with a single unclipped tile is
+plain equalization.
+*/
+
+mod common;
+
+use common::{image, levels};
+use ppm_parser::histogram::{clahe, equalize, match_histogram};
+use ppm_parser::ppm::{PPM, Sample};
+
+/**
+Returns a small amount of noise for each pixel and channel, from -range to range.
+*/
+fn noise(x: u32, y: u32, channel: u32, range: f32) -> f32 {
+    let hash = (x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263) ^ channel.wrapping_mul(2246822519)).wrapping_mul(1274126177) >> 16;
+    (hash % 1000) as f32 / 999.0 * 2.0 * range - range
+}
+
+/**
+Returns a foggy color photo: a few gradients squeezed into the middle of the range, with noise.
+*/
+fn foggy<S: Sample>(width: u32, height: u32, maxc: u32) -> PPM<S> {
+    let maxc_f = maxc as f32;
+    image(width, height, maxc, |x, y| {
+        let base = 0.35 + 0.2 * x as f32 / width as f32 + 0.1 * y as f32 / height as f32;
+        [0.0, 0.05, -0.05].map(|tint| ((base + tint) * maxc_f + noise(x, y, tint.to_bits(), maxc_f / 50.0)).clamp(0.0, maxc_f))
+    })
+}
+
+#[test]
+fn equalizing_an_even_ramp_changes_nothing() {
+    // every gray level is used by the same number of pixels, so the ramp is already equalized
+    let ramp = image::<u8>(256, 3, 255, |x, _| [x as f32; 3]);
+    assert_eq!(levels(&equalize(ramp.clone()).pixels), levels(&ramp.pixels));
+
+    let ramp = image::<u16>(1024, 2, 1023, |x, _| [x as f32; 3]);
+    assert_eq!(levels(&equalize(ramp.clone()).pixels), levels(&ramp.pixels));
+}
+
+#[test]
+fn matching_an_image_to_itself_changes_nothing() {
+    let photo = foggy::<u8>(40, 30, 255);
+    assert_eq!(levels(&match_histogram(photo.clone(), &photo).pixels), levels(&photo.pixels));
+
+    let photo = foggy::<u16>(40, 30, 4095);
+    assert_eq!(levels(&match_histogram(photo.clone(), &photo).pixels), levels(&photo.pixels));
+}
+
+#[test]
+fn clahe_with_one_unclipped_tile_is_equalize() {
+    // a gray image, where the luma is exactly a level, and a color image
+    let gray = image::<u8>(48, 32, 255, |x, y| [(60.0 + 2.0 * x as f32 + y as f32 + noise(x, y, 0, 10.0)).round(); 3]);
+    let photo = foggy::<u8>(48, 32, 255);
+    for image in [gray, photo] {
+        let equalized = levels(&equalize(image.clone()).pixels);
+        for tile_size in [48, 64, 1000] {
+            assert_eq!(levels(&clahe(image.clone(), tile_size, 0.0).pixels), equalized, "tile size {}", tile_size);
+        }
+    }
+}
+
+#[test]
+fn tiles_larger_than_the_image_dont_panic() {
+    for (width, height) in [(1, 1), (5, 3), (3, 40), (0, 0)] {
+        for tile_size in [1, 2, 7, 100, u32::MAX] {
+            for clip_limit in [0.0, 1.0, 4.0] {
+                let photo = foggy::<u8>(width, height, 255);
+                let result = clahe(photo.clone(), tile_size, clip_limit);
+                assert_eq!(result.pixels.len(), photo.pixels.len());
+
+                let photo = foggy::<u16>(width, height, 65535);
+                assert_eq!(clahe(photo.clone(), tile_size, clip_limit).pixels.len(), photo.pixels.len());
+
+                let photo = foggy::<f32>(width, height, 1);
+                let result = clahe(photo.clone(), tile_size, clip_limit);
+                assert!(result.pixels.iter().all(|pixel| pixel.r.is_finite() && pixel.g.is_finite() && pixel.b.is_finite()));
+            }
+        }
+    }
+}
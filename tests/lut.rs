@@ -0,0 +1,149 @@
+/*
+Checks the .cube parser, and that identity LUTs leave every color alone with either interpolation.
+*/
+
+use std::io::Cursor;
+
+use ppm_parser::lut::{hald_identity, Lut, LutInterpolation};
+use ppm_parser::PpmError;
+
+/**
+Parses a .cube file from a string.
+*/
+fn parse(cube: &str) -> Result<Lut, PpmError> {
+    Lut::from_cube(Cursor::new(cube.as_bytes().to_vec()))
+}
+
+/**
+Returns the text of an identity 3D .cube file with `size` entries along each axis.
+*/
+fn identity_cube(size: usize) -> String {
+    let mut cube = format!("TITLE \"identity\"\nLUT_3D_SIZE {}\n", size);
+    let last = (size - 1) as f32;
+    for index in 0..size * size * size {
+        let [r, g, b] = [index % size, index / size % size, index / (size * size)].map(|level| level as f32 / last);
+        cube.push_str(&format!("{} {} {}\n", r, g, b));
+    }
+    cube
+}
+
+/**
+Asserts that two rgb values are within a small distance of each other.
+*/
+fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    assert!(actual.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} is not {:?}", actual, expected);
+}
+
+// colors spread through the cube, including both ends and the grays
+const COLORS: [[f32; 3]; 7] = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [0.5, 0.5, 0.5], [0.1, 0.7, 0.3], [0.9, 0.2, 0.6], [0.33, 0.33, 0.8], [1.0, 0.0, 0.42]];
+
+#[test]
+fn title_and_comments() {
+    let lut = parse("# made by hand\nTITLE \"Warm look\"\n\nLUT_1D_SIZE 2 # two levels\n0 0 0\n1 1 1\n").unwrap();
+    assert_eq!(lut.title, "Warm look");
+    assert!(lut.shaper.is_some() && lut.cube.is_none());
+}
+
+#[test]
+fn unknown_keywords_are_skipped() {
+    let lut = parse("LUT_3D_SIZE 2\nLUT_IN_VIDEO_RANGE\n0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n").unwrap();
+    assert!(lut.title.is_empty() && lut.cube.is_some());
+}
+
+#[test]
+fn one_and_three_dimensional_sizes() {
+    let lut = parse("LUT_1D_SIZE 3\n0 0 0\n0.25 0.25 0.25\n1 1 1\n").unwrap();
+    assert_eq!(lut.shaper.as_ref().unwrap().size, 3);
+    assert_close(lut.apply([0.5, 0.75, 0.25], LutInterpolation::Trilinear), [0.25, 0.625, 0.125]);
+
+    let lut = parse(&identity_cube(3)).unwrap();
+    assert_eq!(lut.cube.as_ref().unwrap().size, 3);
+    assert_eq!(lut.cube.as_ref().unwrap().entries.len(), 27);
+
+    // a shaper that inverts the colors, followed by an identity cube
+    let both = format!("LUT_1D_SIZE 2\n1 1 1\n0 0 0\n{}", identity_cube(2));
+    let lut = parse(&both).unwrap();
+    assert_eq!(lut.shaper.as_ref().unwrap().entries.len(), 2);
+    assert_eq!(lut.cube.as_ref().unwrap().entries.len(), 8);
+    assert_close(lut.apply([0.2, 0.5, 0.9], LutInterpolation::Tetrahedral), [0.8, 0.5, 0.1]);
+}
+
+#[test]
+fn domain_scales_the_input() {
+    let lut = parse("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 4 1\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n").unwrap();
+    assert_close(lut.apply([0.5, 0.5, 0.5], LutInterpolation::Trilinear), [0.25, 0.125, 0.5]);
+
+    // a table's own input range replaces the domain
+    let lut = parse("DOMAIN_MAX 2 2 2\nLUT_1D_INPUT_RANGE 0 4\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n").unwrap();
+    assert_close(lut.apply([1.0, 2.0, 3.0], LutInterpolation::Trilinear), [0.25, 0.5, 0.75]);
+
+    assert!(matches!(parse("DOMAIN_MIN 1 0 0\nDOMAIN_MAX 1 1 1\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("DOMAIN_MIN 0 0\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n"), Err(PpmError::InvalidLut(_))));
+}
+
+#[test]
+fn wrong_number_of_entries() {
+    // one entry short of a 2x2x2 cube, and one too many
+    let cube = identity_cube(2);
+    let short = cube.lines().take(cube.lines().count() - 1).collect::<Vec<&str>>().join("\n");
+    assert!(matches!(parse(&short), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse(&format!("{}0 0 0\n", cube)), Err(PpmError::InvalidLut(_))));
+
+    // the shaper and the cube together
+    assert!(matches!(parse(&format!("LUT_1D_SIZE 2\n0 0 0\n{}", cube)), Err(PpmError::InvalidLut(_))));
+}
+
+#[test]
+fn invalid_sizes_and_entries() {
+    assert!(matches!(parse("0 0 0\n1 1 1\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("LUT_1D_SIZE 1\n0 0 0\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("LUT_3D_SIZE 2.5\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("LUT_3D_SIZE 300\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("LUT_1D_SIZE 2\n0 0 0\n1 1\n"), Err(PpmError::InvalidLut(_))));
+    assert!(matches!(parse("LUT_1D_SIZE 2\n0 0 0\n1 x 1\n"), Err(PpmError::InvalidLut(_))));
+}
+
+#[test]
+fn identity_cube_keeps_every_color() {
+    for size in [2, 5, 17] {
+        let lut = parse(&identity_cube(size)).unwrap();
+        for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+            for color in COLORS {
+                assert_close(lut.apply(color, interpolation), color);
+            }
+        }
+    }
+}
+
+#[test]
+fn identity_hald_keeps_every_color() {
+    let lut = Lut::from_hald(&hald_identity::<u16>(4, 65535)).unwrap();
+    for interpolation in [LutInterpolation::Trilinear, LutInterpolation::Tetrahedral] {
+        for color in COLORS {
+            let result = lut.apply(color, interpolation);
+            assert!(result.iter().zip(color).all(|(a, b)| (a - b).abs() < 1e-4), "{:?} is not {:?}", result, color);
+        }
+    }
+}
+
+#[test]
+fn interpolations_agree_on_entries() {
+    // each channel is multiplied by the next one, which mixes the channels, so the
+    // interpolations only agree on the entries themselves
+    let size = 3;
+    let mut cube = format!("LUT_3D_SIZE {}\n", size);
+    for index in 0..size * size * size {
+        let [r, g, b] = [index % size, index / size % size, index / (size * size)].map(|level| level as f32 / 2.0);
+        cube.push_str(&format!("{} {} {}\n", r * g, g * b, b * r));
+    }
+    let lut = parse(&cube).unwrap();
+
+    for color in [[0.0, 0.5, 1.0], [0.5, 0.5, 0.5], [1.0, 0.0, 0.5]] {
+        let [r, g, b] = color;
+        assert_close(lut.apply(color, LutInterpolation::Trilinear), [r * g, g * b, b * r]);
+        assert_close(lut.apply(color, LutInterpolation::Tetrahedral), [r * g, g * b, b * r]);
+    }
+    let color = [0.1, 0.7, 0.3];
+    let (trilinear, tetrahedral) = (lut.apply(color, LutInterpolation::Trilinear), lut.apply(color, LutInterpolation::Tetrahedral));
+    assert!(trilinear.iter().zip(tetrahedral).any(|(a, b)| (a - b).abs() > 1e-4), "{:?} {:?}", trilinear, tetrahedral);
+}
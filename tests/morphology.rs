@@ -0,0 +1,138 @@
+/*
+Checks the morphological operations against a naive version that looks at every cell of the
+element for every pixel, on images whose sizes aren't a multiple of the element, so the blocks
+of the van Herk/Gil-Werman algorithm end in every possible place.
+*/
+
+mod common;
+
+use common::{levels, test_image};
+use ppm_parser::morphology::{self, StructuringElement};
+use ppm_parser::ppm::{PPM, Sample};
+
+/**
+Erodes or dilates the image by looking at every cell of the element for every pixel. Erosion
+takes the darkest pixel under the element, and dilation takes the brightest pixel under the
+element turned halfway around its center. Pixels past the edges of the image are left out.
+*/
+fn naive<S: Sample>(image: &PPM<S>, element: &StructuringElement, darkest: bool) -> Vec<[f32; 3]> {
+    let (center_x, center_y) = ((element.width / 2) as i64, (element.height / 2) as i64);
+    let mut result = Vec::new();
+    for y in 0..image.height as i64 {
+        for x in 0..image.width as i64 {
+            let mut picked: Option<[f32; 3]> = None;
+            for (cell, &set) in element.cells.iter().enumerate() {
+                let (dx, dy) = ((cell as u32 % element.width) as i64 - center_x, (cell as u32 / element.width) as i64 - center_y);
+                let (source_x, source_y) = match darkest {
+                    true => (x + dx, y + dy),
+                    false => (x - dx, y - dy),
+                };
+                if !set || source_x < 0 || source_y < 0 || source_x >= image.width as i64 || source_y >= image.height as i64 {
+                    continue;
+                }
+                let pixel = image.get_pixel(source_x as u32, source_y as u32).unwrap();
+                let value = [pixel.r.to_f32(), pixel.g.to_f32(), pixel.b.to_f32()];
+                picked = Some(match picked {
+                    None => value,
+                    Some(picked) => [0, 1, 2].map(|c| match darkest {
+                        true => picked[c].min(value[c]),
+                        false => picked[c].max(value[c]),
+                    }),
+                });
+            }
+            result.push(picked.expect("the center of every element is in the image"));
+        }
+    }
+    result
+}
+
+/**
+Returns the elements checked by every test: squares, crosses, disks, a rectangle, and an
+asymmetric element with even sides.
+*/
+fn elements() -> Vec<StructuringElement> {
+    let asymmetric = StructuringElement::new(4, 2, vec![true, false, true, true, false, true, true, false]).unwrap();
+    vec![
+        StructuringElement::square(1).unwrap(),
+        StructuringElement::square(3).unwrap(),
+        StructuringElement::cross(1).unwrap(),
+        StructuringElement::cross(3).unwrap(),
+        StructuringElement::disk(2).unwrap(),
+        StructuringElement::disk(4).unwrap(),
+        StructuringElement::rectangle(3, 1).unwrap(),
+        asymmetric,
+    ]
+}
+
+/**
+Checks erosion and dilation against the naive version for every element.
+*/
+fn check_erode_and_dilate<S: Sample>(image: &PPM<S>) {
+    for element in elements() {
+        let eroded = morphology::erode(image.clone(), &element);
+        assert_eq!(levels(&eroded.pixels), naive(image, &element, true), "erode {}x{} with {:?}", image.width, image.height, element);
+        let dilated = morphology::dilate(image.clone(), &element);
+        assert_eq!(levels(&dilated.pixels), naive(image, &element, false), "dilate {}x{} with {:?}", image.width, image.height, element);
+    }
+}
+
+#[test]
+fn erode_and_dilate_match_naive_8_bit() {
+    for (width, height) in [(13, 9), (11, 7), (1, 1), (2, 3), (17, 1)] {
+        check_erode_and_dilate(&test_image::<u8>(width, height, 255));
+    }
+}
+
+#[test]
+fn erode_and_dilate_match_naive_16_bit() {
+    check_erode_and_dilate(&test_image::<u16>(13, 10, 65535));
+}
+
+#[test]
+fn erode_and_dilate_match_naive_float() {
+    check_erode_and_dilate(&test_image::<f32>(10, 13, 1000));
+}
+
+#[test]
+fn hats_and_gradient_are_differences() {
+    let image = test_image::<u8>(13, 9, 255);
+    for element in elements() {
+        let opened = levels(&morphology::open(image.clone(), &element).pixels);
+        let closed = levels(&morphology::close(image.clone(), &element).pixels);
+        let original = levels(&image.pixels);
+        let difference = |a: &[[f32; 3]], b: &[[f32; 3]]| a.iter().zip(b).map(|(a, b)| [0, 1, 2].map(|c| a[c] - b[c])).collect::<Vec<[f32; 3]>>();
+
+        assert_eq!(levels(&morphology::top_hat(image.clone(), &element).pixels), difference(&original, &opened), "{:?}", element);
+        assert_eq!(levels(&morphology::black_hat(image.clone(), &element).pixels), difference(&closed, &original), "{:?}", element);
+        let gradient = difference(&naive(&image, &element, false), &naive(&image, &element, true));
+        assert_eq!(levels(&morphology::gradient(image.clone(), &element).pixels), gradient, "{:?}", element);
+    }
+}
+
+#[test]
+fn opening_never_brightens_and_closing_never_darkens() {
+    let image = test_image::<u8>(13, 9, 255);
+    let original = levels(&image.pixels);
+    for element in elements() {
+        let opened = levels(&morphology::open(image.clone(), &element).pixels);
+        let closed = levels(&morphology::close(image.clone(), &element).pixels);
+        for ((opened, closed), original) in opened.iter().zip(&closed).zip(&original) {
+            assert!((0..3).all(|c| opened[c] <= original[c] && closed[c] >= original[c]), "{:?}", element);
+        }
+    }
+}
+
+#[test]
+fn elements_too_large_to_count_are_rejected() {
+    // 2 * 40000 + 1 cells across is more than a u32 can count in a square
+    assert!(StructuringElement::square(40000).is_none());
+    assert!(StructuringElement::disk(40000).is_none());
+    assert!(StructuringElement::cross(u32::MAX).is_none());
+    assert!(StructuringElement::rectangle(u32::MAX / 2 + 1, 0).is_none());
+    assert!(StructuringElement::new(65536, 65536, vec![true]).is_none());
+
+    // a long thin rectangle is fine
+    let line = StructuringElement::rectangle(40000, 0).unwrap();
+    assert_eq!((line.width, line.height), (80001, 1));
+    assert_eq!(StructuringElement::disk(1).unwrap().cells, vec![false, true, false, true, true, true, false, true, false]);
+}
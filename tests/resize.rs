@@ -0,0 +1,57 @@
+/*
+Checks the resampling filters on images where the answer is known: a constant image stays
+constant, resizing to the same size keeps every pixel, and the nearest and box filters copy and
+average whole pixels. Also checks that halving keeps the last row and column of odd sizes.
+*/
+
+mod common;
+
+use common::{image, levels, test_image};
+use ppm_parser::imageactions::{half_size, resize, Filter};
+
+const FILTERS: [Filter; 5] = [Filter::Nearest, Filter::Bilinear, Filter::Bicubic, Filter::Lanczos3, Filter::Box];
+
+#[test]
+fn constant_image_stays_constant() {
+    // the weights of every filter add up to 1, so even bicubic and lanczos don't ring
+    for filter in FILTERS {
+        for (width, height) in [(1, 1), (3, 7), (16, 9), (40, 25)] {
+            let resized = resize(image::<u8>(10, 6, 255, |_, _| [200.0, 17.0, 255.0]), width, height, filter);
+            assert_eq!((resized.width, resized.height), (width, height));
+            assert!(levels(&resized.pixels).iter().all(|&pixel| pixel == [200.0, 17.0, 255.0]), "{:?} to {}x{}", filter, width, height);
+        }
+    }
+}
+
+#[test]
+fn same_size_keeps_every_pixel() {
+    // each new pixel is centered on an old one, where every filter has a weight of 1 and its neighbors 0
+    for filter in FILTERS {
+        let resized = resize(test_image::<u16>(13, 8, 65535), 13, 8, filter);
+        assert_eq!(levels(&resized.pixels), levels(&test_image::<u16>(13, 8, 65535).pixels), "{:?}", filter);
+    }
+}
+
+#[test]
+fn nearest_doubling_copies_each_pixel() {
+    let original = test_image::<u8>(5, 3, 255);
+    let resized = resize(test_image::<u8>(5, 3, 255), 10, 6, Filter::Nearest);
+    let expected = (0..60).map(|index| levels(&original.pixels)[(index / 10 / 2) * 5 + index % 10 / 2]).collect::<Vec<[f32; 3]>>();
+    assert_eq!(levels(&resized.pixels), expected);
+}
+
+#[test]
+fn box_halving_averages_squares() {
+    let original = image::<u8>(4, 2, 255, |x, y| [(x * 10 + y * 40) as f32, 100.0, (x * 2) as f32]);
+    let resized = resize(original, 2, 1, Filter::Box);
+    assert_eq!(levels(&resized.pixels), vec![[25.0, 100.0, 1.0], [45.0, 100.0, 5.0]]);
+}
+
+#[test]
+fn half_size_keeps_odd_edges() {
+    // the last column of a 3x3 image is averaged in pairs, and the last pixel is kept as it is
+    let original = image::<u8>(3, 3, 255, |x, y| [(x * 10 + y * 100) as f32; 3]);
+    let halved = half_size(original);
+    assert_eq!((halved.width, halved.height), (2, 2));
+    assert_eq!(levels(&halved.pixels).iter().map(|pixel| pixel[0]).collect::<Vec<f32>>(), vec![55.0, 70.0, 205.0, 220.0]);
+}
@@ -0,0 +1,95 @@
+/*
+Checks the exact rotations and flips against each other, since each one can be built from the
+others, and that rotating by any angle keeps the middle of a flat image and fills the corners.
+*/
+
+mod common;
+
+use common::{levels, test_image};
+use ppm_parser::imageactions::{flip_horizontal, flip_vertical, rotate, rotate_180, rotate_left, rotate_right, transpose, transverse, Interpolation};
+use ppm_parser::ppm::{PPM, Pixel};
+
+/**
+Returns the size and the levels of every pixel of the image, to compare images of any shape.
+*/
+fn contents(image: &PPM<u8>) -> (u32, u32, Vec<[f32; 3]>) {
+    (image.width, image.height, levels(&image.pixels))
+}
+
+/**
+Returns a 5x3 test image with an alpha channel, so the alpha is checked along with the pixels.
+*/
+fn original() -> PPM<u8> {
+    let mut image = test_image::<u8>(5, 3, 255);
+    image.alpha = Some((0..15).map(|index| index * 17).collect());
+    image
+}
+
+#[test]
+fn flips_over_a_diagonal_undo_themselves() {
+    assert_eq!(contents(&transpose(transpose(original()))), contents(&original()));
+    assert_eq!(contents(&transverse(transverse(original()))), contents(&original()));
+    assert_eq!(contents(&rotate_180(rotate_180(original()))), contents(&original()));
+    assert_eq!(transpose(transpose(original())).alpha, original().alpha);
+}
+
+#[test]
+fn transpose_and_transverse_are_rotations_and_flips() {
+    assert_eq!(contents(&transpose(original())), contents(&flip_horizontal(rotate_right(original()))));
+    assert_eq!(contents(&transverse(original())), contents(&flip_horizontal(rotate_left(original()))));
+    assert_eq!(contents(&rotate_180(original())), contents(&flip_horizontal(flip_vertical(original()))));
+    assert_eq!(transverse(original()).alpha, flip_horizontal(rotate_left(original())).alpha);
+}
+
+#[test]
+fn transpose_swaps_rows_and_columns() {
+    let image = original();
+    let transposed = transpose(original());
+    assert_eq!((transposed.width, transposed.height), (3, 5));
+    for y in 0..5 {
+        for x in 0..3 {
+            assert_eq!(levels(&[*transposed.get_pixel(x, y).unwrap()]), levels(&[*image.get_pixel(y, x).unwrap()]));
+        }
+    }
+}
+
+#[test]
+fn right_angles_use_the_exact_rotations() {
+    let black = Pixel::new();
+    let rotated = |degrees| contents(&rotate(original(), degrees, Interpolation::Bilinear, black, true));
+    assert_eq!(rotated(0.0), contents(&original()));
+    assert_eq!(rotated(360.0), contents(&original()));
+    assert_eq!(rotated(90.0), contents(&rotate_right(original())));
+    assert_eq!(rotated(-90.0), contents(&rotate_left(original())));
+    assert_eq!(rotated(180.0), contents(&rotate_180(original())));
+    assert_eq!(rotated(450.0), contents(&rotate_right(original())));
+}
+
+#[test]
+fn any_angle_keeps_the_middle_and_fills_the_corners() {
+    let flat = || {
+        let mut image = common::image::<u8>(20, 20, 255, |_, _| [200.0, 100.0, 50.0]);
+        image.alpha = Some(vec![128; 400]);
+        image
+    };
+    let background = Pixel { r: 0, g: 0, b: 255 };
+    for interpolation in [Interpolation::Nearest, Interpolation::Bilinear, Interpolation::Bicubic] {
+        // the expanded canvas fits the whole square turned 45 degrees, 20 * sqrt(2) pixels wide
+        let rotated = rotate(flat(), 45.0, interpolation, background, true);
+        assert_eq!((rotated.width, rotated.height), (29, 29));
+        let pixel = |x: u32, y: u32| levels(&[*rotated.get_pixel(x, y).unwrap()])[0];
+        assert_eq!(pixel(14, 14), [200.0, 100.0, 50.0]);
+        assert_eq!(pixel(0, 0), [0.0, 0.0, 255.0]);
+        assert_eq!(pixel(28, 28), [0.0, 0.0, 255.0]);
+
+        // the background is opaque, and the image keeps its alpha
+        let alpha = rotated.alpha.unwrap();
+        assert_eq!((alpha[0], alpha[14 * 29 + 14]), (255, 128));
+
+        // without expanding, the canvas keeps its size and the corners are cut off
+        let cropped = rotate(flat(), 30.0, interpolation, background, false);
+        assert_eq!((cropped.width, cropped.height), (20, 20));
+        assert_eq!(levels(&[*cropped.get_pixel(0, 0).unwrap()])[0], [0.0, 0.0, 255.0]);
+        assert_eq!(levels(&[*cropped.get_pixel(10, 10).unwrap()])[0], [200.0, 100.0, 50.0]);
+    }
+}
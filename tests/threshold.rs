@@ -0,0 +1,192 @@
+/*
+Checks the global thresholds picked from histograms, and that the local methods turn a page
+with uneven lighting black and white without losing the text in the shadow.
+*/
+
+use ppm_parser::imageactions::MAX_RADIUS;
+use ppm_parser::ppm::{PPM, Pixel, Sample};
+use ppm_parser::threshold::{otsu_threshold, threshold, triangle_threshold, ThresholdMethod};
+
+/**
+Returns a P5 image with the given gray level at each pixel.
+*/
+fn gray<S: Sample>(width: u32, height: u32, maxc: u32, level: impl Fn(u32, u32) -> f32) -> PPM<S> {
+    let mut image = PPM::new();
+    image.magic = String::from("P5");
+    image.tupltype = String::from("GRAYSCALE");
+    image.maxc = maxc;
+    image.width = width;
+    image.height = height;
+    image.pixels = (0..width * height).map(|index| {
+        let level = S::from_f32(level(index % width, index / width).round());
+        Pixel { r: level, g: level, b: level }
+    }).collect();
+    image
+}
+
+/**
+Returns a small amount of noise for each pixel, from -range to range.
+*/
+fn noise(x: u32, y: u32, range: f32) -> f32 {
+    let hash = (x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263)).wrapping_mul(1274126177) >> 16;
+    (hash % 1000) as f32 / 999.0 * 2.0 * range - range
+}
+
+/**
+Whether a pixel of the test page is part of the text, which is rows of small letter sized blocks.
+*/
+fn is_text(x: u32, y: u32) -> bool {
+    y % 12 >= 4 && y % 12 < 9 && x % 7 >= 2 && x % 7 < 5
+}
+
+/**
+Returns a page of 8-bit text, lit from the left so the right side is in shadow. The paper goes
+from 245 down to 110, and the text is 90 darker than the paper around it, so the text on the
+left is brighter than the paper on the right and no single threshold works.
+*/
+fn shaded_page() -> PPM<u8> {
+    gray(96, 48, 255, |x, y| {
+        let paper = 245.0 - 135.0 * x as f32 / 95.0 + noise(x, y, 4.0);
+        match is_text(x, y) {
+            true => paper - 90.0,
+            false => paper,
+        }
+    })
+}
+
+/**
+Returns the fraction of the pixels of the black and white page that don't match the text.
+*/
+fn page_errors(page: &PPM<u8>) -> f32 {
+    let wrong = page.pixels.iter().enumerate().filter(|&(index, pixel)| {
+        let (x, y) = (index as u32 % page.width, index as u32 / page.width);
+        (pixel.r == 0) != is_text(x, y)
+    }).count();
+    wrong as f32 / page.pixels.len() as f32
+}
+
+/**
+Returns a bimodal image, with dark pixels around `dark` and bright pixels around `bright`, and
+whether each pixel is one of the dark ones.
+*/
+fn bimodal<S: Sample>(maxc: u32, dark: f32, bright: f32) -> (PPM<S>, Vec<bool>) {
+    let spread = maxc as f32 / 25.0;
+    let is_dark = |x: u32, y: u32| (x / 4 + y / 3).is_multiple_of(3);
+    let image = gray(40, 30, maxc, |x, y| match is_dark(x, y) {
+        true => dark + noise(x, y, spread),
+        false => bright + noise(x, y, spread),
+    });
+    let dark = (0..40 * 30).map(|index| is_dark(index % 40, index / 40)).collect();
+    (image, dark)
+}
+
+/**
+Asserts that the method makes exactly the dark pixels of a bimodal image black.
+*/
+fn assert_splits<S: Sample>((image, dark): (PPM<S>, Vec<bool>), method: ThresholdMethod) {
+    let maxc = image.maxc;
+    let result = threshold(image, method).unwrap();
+    let black = result.pixels.iter().map(|pixel| pixel.r.to_f32() == 0.0).collect::<Vec<bool>>();
+    assert_eq!(black, dark, "{:?} at maxc {}", method, maxc);
+}
+
+#[test]
+fn otsu_splits_between_the_modes() {
+    let threshold = otsu_threshold(&bimodal::<u8>(255, 50.0, 200.0).0);
+    assert!((50.0 / 255.0..190.0 / 255.0).contains(&threshold), "{}", threshold);
+
+    assert_splits(bimodal::<u8>(255, 50.0, 200.0), ThresholdMethod::Otsu);
+    assert_splits(bimodal::<u8>(255, 100.0, 140.0), ThresholdMethod::Otsu);
+    assert_splits(bimodal::<u16>(65535, 10000.0, 30000.0), ThresholdMethod::Otsu);
+    assert_splits(bimodal::<u16>(1023, 700.0, 1000.0), ThresholdMethod::Otsu);
+}
+
+#[test]
+fn triangle_splits_between_the_modes() {
+    // mostly paper, with a little ink
+    let page = |maxc: u32| {
+        let ink = |x: u32| x.is_multiple_of(8);
+        let image = gray::<u16>(40, 30, maxc, |x, y| match ink(x) {
+            true => maxc as f32 * 0.15 + noise(x, y, maxc as f32 * 0.02),
+            false => maxc as f32 * 0.85 + noise(x, y, maxc as f32 * 0.04),
+        });
+        (image, (0..40 * 30).map(|index| ink(index % 40)).collect())
+    };
+    for maxc in [255, 65535] {
+        let threshold = triangle_threshold(&page(maxc).0);
+        assert!(threshold > 0.2 && threshold < 0.85, "{} at maxc {}", threshold, maxc);
+        assert_splits(page(maxc), ThresholdMethod::Triangle);
+    }
+}
+
+#[test]
+fn empty_and_flat_histograms_dont_panic() {
+    let empty = gray::<u8>(0, 0, 255, |_, _| 0.0);
+    otsu_threshold(&empty);
+    triangle_threshold(&empty);
+    for method in [ThresholdMethod::Otsu, ThresholdMethod::Triangle, ThresholdMethod::Sauvola { radius: 3, k: 0.2, range: 0.5 }] {
+        assert!(threshold(gray::<u8>(0, 0, 255, |_, _| 0.0), method).unwrap().pixels.is_empty());
+    }
+
+    for level in [0.0, 128.0, 255.0] {
+        let flat = || gray::<u8>(9, 7, 255, |_, _| level);
+        assert!((0.0..=1.0).contains(&otsu_threshold(&flat())));
+        assert!((0.0..=1.0).contains(&triangle_threshold(&flat())));
+        for method in [ThresholdMethod::Otsu, ThresholdMethod::Triangle] {
+            let result = threshold(flat(), method).unwrap();
+            let first = result.pixels[0].r;
+            assert!(result.pixels.iter().all(|pixel| pixel.r == first), "a flat image stays one color");
+        }
+    }
+}
+
+#[test]
+fn fixed_threshold() {
+    let image = gray::<u8>(3, 1, 255, |x, _| [100.0, 128.0, 200.0][x as usize]);
+    let result = threshold(image, ThresholdMethod::Fixed(128.0 / 255.0)).unwrap();
+    assert_eq!(result.pixels.iter().map(|pixel| pixel.r).collect::<Vec<u8>>(), vec![0, 0, 255]);
+    assert!(result.alpha.is_none());
+}
+
+#[test]
+fn global_threshold_fails_on_a_shaded_page() {
+    // the reason for the local methods: Otsu loses the text on one side of the page
+    let page = threshold(shaded_page(), ThresholdMethod::Otsu).unwrap();
+    assert!(page_errors(&page) > 0.1, "{}", page_errors(&page));
+}
+
+#[test]
+fn adaptive_mean_binarizes_a_shaded_page() {
+    let page = threshold(shaded_page(), ThresholdMethod::AdaptiveMean { radius: 7, offset: 10.0 / 255.0 }).unwrap();
+    assert!(page_errors(&page) < 0.01, "{}", page_errors(&page));
+}
+
+#[test]
+fn sauvola_binarizes_a_shaded_page() {
+    let page = threshold(shaded_page(), ThresholdMethod::Sauvola { radius: 7, k: 0.2, range: 0.5 }).unwrap();
+    assert!(page_errors(&page) < 0.01, "{}", page_errors(&page));
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    let invalid = [
+        ThresholdMethod::Fixed(f32::NAN),
+        ThresholdMethod::Fixed(f32::INFINITY),
+        ThresholdMethod::AdaptiveMean { radius: 5, offset: f32::NAN },
+        ThresholdMethod::AdaptiveGaussian { sigma: -1.0, offset: 0.0 },
+        ThresholdMethod::AdaptiveGaussian { sigma: f32::NAN, offset: 0.0 },
+        ThresholdMethod::Niblack { radius: 5, k: f32::NEG_INFINITY },
+        ThresholdMethod::Sauvola { radius: 5, k: 0.2, range: 0.0 },
+        ThresholdMethod::Sauvola { radius: 5, k: 0.2, range: f32::NAN },
+        ThresholdMethod::AdaptiveMean { radius: 3_000_000_000, offset: 0.0 },
+        ThresholdMethod::AdaptiveGaussian { sigma: 1e9, offset: 0.0 },
+        ThresholdMethod::Niblack { radius: MAX_RADIUS + 1, k: -0.2 },
+        ThresholdMethod::Sauvola { radius: u32::MAX, k: 0.2, range: 0.5 },
+    ];
+    for method in invalid {
+        assert!(!method.is_valid(), "{:?}", method);
+        assert!(threshold(shaded_page(), method).is_none(), "{:?}", method);
+    }
+    assert!(ThresholdMethod::Fixed(0.5).is_valid());
+    assert!(ThresholdMethod::AdaptiveGaussian { sigma: 0.0, offset: -0.1 }.is_valid());
+}
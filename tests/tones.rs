@@ -0,0 +1,115 @@
+/*
+Checks the tone adjustments on ramps and small images with levels worked out by hand: brightness,
+contrast, gamma, levels, curves, and the automatic stretches.
+*/
+
+mod common;
+
+use common::{image, levels};
+use ppm_parser::ppm::{Curve, Levels, PPM};
+
+/**
+Returns a ramp of every level of an image with the given max color value.
+*/
+fn ramp(maxc: u32) -> PPM<u16> {
+    image(maxc + 1, 1, maxc, |x, _| [x as f32; 3])
+}
+
+/**
+Returns the red level of each pixel.
+*/
+fn reds(image: &PPM<u16>) -> Vec<f32> {
+    levels(&image.pixels).iter().map(|pixel| pixel[0]).collect()
+}
+
+#[test]
+fn default_levels_change_nothing() {
+    for maxc in [1, 255, 1000, 65535] {
+        let mut adjusted = ramp(maxc);
+        adjusted.levels(Levels::default());
+        assert_eq!(reds(&adjusted), reds(&ramp(maxc)));
+    }
+}
+
+#[test]
+fn levels_stretch_the_input_range() {
+    let levels = Levels { input_black: 0.2, input_white: 0.6, gamma: 1.0, output_black: 0.0, output_white: 1.0 };
+    assert_eq!(levels.apply(0.1), 0.0);
+    assert!((levels.apply(0.4) - 0.5).abs() < 1e-6);
+    assert_eq!(levels.apply(0.9), 1.0);
+
+    // the output range is filled from its black to its white
+    let levels = Levels { output_black: 0.25, output_white: 0.75, ..Levels::default() };
+    assert_eq!((levels.apply(0.0), levels.apply(0.5), levels.apply(1.0)), (0.25, 0.5, 0.75));
+
+    // gamma bends the middle and keeps the ends
+    let levels = Levels { gamma: 2.0, ..Levels::default() };
+    assert_eq!((levels.apply(0.0), levels.apply(0.25), levels.apply(1.0)), (0.0, 0.5, 1.0));
+}
+
+#[test]
+fn brightness_contrast_and_gamma() {
+    let mut brighter = ramp(255);
+    brighter.adjust_brightness(0.2);
+    let expected = (0..256).map(|level| (level + 51).min(255) as f32).collect::<Vec<f32>>();
+    assert_eq!(reds(&brighter), expected);
+
+    // no contrast at all makes every level the middle one
+    let mut flat = ramp(255);
+    flat.adjust_contrast(0.0);
+    assert!(reds(&flat).iter().all(|&level| level == 128.0));
+
+    let mut same = ramp(255);
+    same.adjust_contrast(1.0);
+    assert_eq!(reds(&same), reds(&ramp(255)));
+
+    let mut gamma = ramp(255);
+    gamma.adjust_gamma(2.2);
+    let gamma = reds(&gamma);
+    assert_eq!((gamma[0], gamma[255]), (0.0, 255.0));
+    assert!(gamma.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!(gamma[128] > 128.0);
+}
+
+#[test]
+fn curves_go_through_their_points_without_overshooting() {
+    let identity = Curve::new(&[(0.0, 0.0), (1.0, 1.0)]).unwrap();
+    let mut unchanged = ramp(1000);
+    unchanged.curves(&identity);
+    assert_eq!(reds(&unchanged), reds(&ramp(1000)));
+
+    // an s-curve with a flat step in the middle
+    let points = [(0.1, 0.05), (0.3, 0.2), (0.5, 0.5), (0.6, 0.5), (0.9, 0.95)];
+    let curve = Curve::new(&points).unwrap();
+    for (input, output) in points {
+        assert!((curve.apply(input) - output).abs() < 1e-6);
+    }
+    let outputs = (0..=1000).map(|step| curve.apply(step as f32 / 1000.0)).collect::<Vec<f32>>();
+    assert!(outputs.windows(2).all(|pair| pair[0] <= pair[1] + 1e-6));
+    assert!(outputs[500..=600].iter().all(|&output| (output - 0.5).abs() < 1e-6));
+
+    // inputs past the points keep the nearest output
+    assert_eq!((curve.apply(0.0), curve.apply(1.0)), (0.05, 0.95));
+}
+
+#[test]
+fn invalid_curves_are_rejected() {
+    assert!(Curve::new(&[]).is_none());
+    assert!(Curve::new(&[(0.5, 0.5)]).is_none());
+    assert!(Curve::new(&[(0.5, 0.0), (0.5, 1.0)]).is_none());
+    assert!(Curve::new(&[(0.6, 0.0), (0.2, 1.0)]).is_none());
+}
+
+#[test]
+fn auto_levels_stretch_each_channel() {
+    // red goes from 50 to 200, green is a single level, and blue already fills the range
+    let colors = || image::<u16>(4, 1, 255, |x, _| [[50.0, 100.0, 150.0, 200.0][x as usize], 80.0, [0.0, 85.0, 170.0, 255.0][x as usize]]);
+    let mut stretched = colors();
+    stretched.auto_levels(0.0);
+    assert_eq!(levels(&stretched.pixels), vec![[0.0, 80.0, 0.0], [85.0, 80.0, 85.0], [170.0, 80.0, 170.0], [255.0, 80.0, 255.0]]);
+
+    // auto contrast stretches the channels together, from 0 to 255 here, so nothing changes
+    let mut together = colors();
+    together.auto_contrast(0.0);
+    assert_eq!(levels(&together.pixels), levels(&colors().pixels));
+}
@@ -0,0 +1,121 @@
+/*
+Checks the projective and affine warps, and the homography solved for them from four points.
+*/
+
+use ppm_parser::imageactions::{homography, warp_affine, warp_perspective, Interpolation, Matrix};
+use ppm_parser::ppm::{PPM, Pixel};
+
+/**
+Returns a P6 test image where every pixel has a different color.
+*/
+fn test_image(width: u32, height: u32) -> PPM<u16> {
+    let mut image = PPM::new();
+    image.magic = String::from("P6");
+    image.maxc = 255;
+    image.width = width;
+    image.height = height;
+    image.pixels = (0..width * height).map(|index| Pixel { r: (index * 7 % 256) as u16, g: (index * 31 % 256) as u16, b: (255 - index % 256) as u16 }).collect();
+    image
+}
+
+/**
+Returns the r, g, and b values of every pixel of the image.
+*/
+fn levels<S: ppm_parser::ppm::Sample>(image: &PPM<S>) -> Vec<[f32; 3]> {
+    image.pixels.iter().map(|pixel| [pixel.r.to_f32(), pixel.g.to_f32(), pixel.b.to_f32()]).collect()
+}
+
+/**
+Moves a point with a projective transform.
+*/
+fn transform(m: &Matrix, (x, y): (f64, f64)) -> (f64, f64) {
+    let w = m[2][0] * x + m[2][1] * y + m[2][2];
+    ((m[0][0] * x + m[0][1] * y + m[0][2]) / w, (m[1][0] * x + m[1][1] * y + m[1][2]) / w)
+}
+
+const IDENTITY: Matrix = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+const BACKGROUND: Pixel<u16> = Pixel { r: 1, g: 2, b: 3 };
+
+#[test]
+fn identity_warp_keeps_the_image() {
+    let image = test_image(7, 5);
+    for interpolation in [Interpolation::Nearest, Interpolation::Bilinear, Interpolation::Bicubic] {
+        let warped = warp_perspective(test_image(7, 5), IDENTITY, 7, 5, interpolation, BACKGROUND);
+        assert_eq!(levels(&warped), levels(&image), "{:?}", interpolation);
+    }
+}
+
+#[test]
+fn translation_moves_every_pixel() {
+    let image = test_image(6, 4);
+    for interpolation in [Interpolation::Nearest, Interpolation::Bilinear, Interpolation::Bicubic] {
+        let warped = warp_affine(test_image(6, 4), [[1.0, 0.0, 2.0], [0.0, 1.0, 1.0]], 6, 4, interpolation, BACKGROUND);
+        for y in 0..4 {
+            for x in 0..6 {
+                let pixel = warped.get_pixel(x, y).unwrap();
+                let expected = match x >= 2 && y >= 1 {
+                    true => *image.get_pixel(x - 2, y - 1).unwrap(),
+                    false => BACKGROUND,
+                };
+                assert_eq!([pixel.r, pixel.g, pixel.b], [expected.r, expected.g, expected.b], "{:?} at {}, {}", interpolation, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn homography_moves_the_corners_to_their_targets() {
+    let from = [(0.0, 0.0), (99.0, 0.0), (99.0, 49.0), (0.0, 49.0)];
+    let to = [(10.0, 5.0), (80.0, 12.0), (95.0, 60.0), (-3.0, 40.0)];
+    let matrix = homography(from, to).unwrap();
+    for (point, target) in from.into_iter().zip(to) {
+        let (x, y) = transform(&matrix, point);
+        assert!((x - target.0).abs() < 1e-9 && (y - target.1).abs() < 1e-9, "{:?} went to {:?} instead of {:?}", point, (x, y), target);
+    }
+
+    // the inverse transform brings them back
+    let back = homography(to, from).unwrap();
+    for (point, target) in to.into_iter().zip(from) {
+        let (x, y) = transform(&back, point);
+        assert!((x - target.0).abs() < 1e-9 && (y - target.1).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn homography_of_a_translation() {
+    let from = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+    let to = from.map(|(x, y)| (x + 3.0, y - 2.0));
+    let matrix = homography(from, to).unwrap();
+    let expected: Matrix = [[1.0, 0.0, 3.0], [0.0, 1.0, -2.0], [0.0, 0.0, 1.0]];
+    for (row, expected_row) in matrix.iter().zip(expected) {
+        for (value, expected) in row.iter().zip(expected_row) {
+            assert!((value - expected).abs() < 1e-9, "{:?}", matrix);
+        }
+    }
+}
+
+#[test]
+fn degenerate_quads_have_no_homography() {
+    let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+    let collinear = [(0.0, 0.0), (5.0, 5.0), (10.0, 10.0), (0.0, 10.0)];
+    let all_on_a_line = [(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)];
+    let repeated = [(0.0, 0.0), (0.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+    for quad in [collinear, all_on_a_line, repeated] {
+        assert_eq!(homography(square, quad), None, "{:?}", quad);
+        assert_eq!(homography(quad, square), None, "{:?}", quad);
+    }
+}
+
+#[test]
+fn singular_matrix_gives_the_background() {
+    let mut image: PPM<f32> = PPM::new();
+    image.magic = String::from("PF");
+    image.maxc = 1;
+    image.width = 3;
+    image.height = 3;
+    image.pixels = vec![Pixel { r: 0.5, g: 0.25, b: 2.0 }; 9];
+
+    let flatten = [[1.0, 2.0, 0.0], [2.0, 4.0, 0.0], [0.0, 0.0, 1.0]];
+    let warped = warp_perspective(image, flatten, 3, 3, Interpolation::Bilinear, Pixel { r: 0.0, g: 0.0, b: 0.0 });
+    assert!(levels(&warped).iter().all(|pixel| *pixel == [0.0; 3]));
+}